@@ -16,6 +16,24 @@ pub struct Package {
     pub path: PathBuf,
 }
 
+#[derive(StructOpt, Debug)]
+pub struct Run {
+    #[structopt(flatten)]
+    pub package: Package,
+    /// Prints the compiled bytecode before running it
+    #[structopt(long)]
+    pub bytecode: bool,
+    /// Fails to run the package if compiling it raises any warnings
+    #[structopt(long)]
+    pub deny_warnings: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Explain {
+    /// The code to explain, like `E0012`
+    pub code: String,
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(name = "Aspen", bin_name = "aspen", about)]
 pub enum Aspen {
@@ -24,8 +42,10 @@ pub enum Aspen {
     // Update,
     // Publish,
     /// Runs the specified package
-    Run(Package),
+    Run(Run),
     Repl,
+    /// Explains an error or warning code at length
+    Explain(Explain),
     // Test,
     // Bench,
     // Doc,
@@ -0,0 +1,10 @@
+use passerine::Code;
+
+/// Prints the long-form explanation of an error or warning code,
+/// like `E0012`, which is shown alongside each error.
+pub fn explain(code: &str) -> Result<(), String> {
+    let code = Code::from_id(code)
+        .ok_or_else(|| format!("`{}` is not the code of an error or warning", code))?;
+    print!("{}", code.explanation());
+    Ok(())
+}
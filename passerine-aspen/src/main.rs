@@ -10,6 +10,7 @@ pub mod add;
 pub mod bench;
 pub mod debug;
 pub mod doc;
+pub mod explain;
 pub mod new;
 pub mod publish;
 pub mod repl;
@@ -29,12 +30,13 @@ fn main() {
 
     let result = match subcommand {
         Aspen::New(package) => new::new(package.path),
-        Aspen::Run(package) => run::run(package.path),
+        Aspen::Run(run) => run::run(run.package.path, run.bytecode, run.deny_warnings),
         Aspen::Repl => repl::repl(),
-        _ => unimplemented!(),
+        Aspen::Explain(explain) => explain::explain(&explain.code),
     };
 
     if let Err(r) = result {
-        Status::fatal().log(&r)
+        Status::fatal().log(&r);
+        std::process::exit(1);
     }
 }
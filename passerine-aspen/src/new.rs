@@ -52,7 +52,7 @@ pub fn new(package: PathBuf) -> Result<(), String> {
     } else {
         fs::write(
             package.join(SOURCE).join(ENTRYPOINT),
-            "println \"Hello, Passerine!\"\n",
+            "effect Write\n\nWrite \"Hello, Passerine!\\n\"\n",
         )
         .map_err(|_| "Could not create source entrypoint")?;
     }
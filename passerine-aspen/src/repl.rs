@@ -1,60 +1,72 @@
-// TODO: preserve values using module system.
+use std::io::{self, Write};
 
-// use std::io::{self, Read};
-// use std::io::Write;
-//
-// use passerine::{
-//     common::{closure::Closure, source::Source, lambda::Lambda},
-//     compiler::{lex, parse, desugar, gen, hoist, syntax::Syntax},
-//     vm::vm::VM,
-// };
+use passerine::{
+    compiler::{lex, Session},
+    kernel, Closure, Reader, Source,
+};
 
+/// Reads an entry, compiles it, runs it, and prints the result, forever.
+/// Each entry runs on the same `Fiber`,
+/// so variables from earlier entries can be used in later ones.
 pub fn repl() -> Result<(), String> {
-    // println!("Repl is WIP.");
-    Err("Interactive repl is WIP".into())
-
-    // println!("Hit ^C to quit.\n");
-
-    // loop {
-    //     let mut to_eval = String::new();
-    //
-    //     let lambda: Result<Lambda, Syntax> = 'read: loop {
-    //         print!("| ");
-    //         std::io::stdout().flush().unwrap();
-    //
-    //         let mut exit = false;
-    //         match io::stdin().read_line(&mut to_eval) {
-    //             Ok(l) if l <= 1 => { exit = true; },
-    //             _ => ()
-    //         }
-    //
-    //         let source = Source::source(&to_eval);
-    //         let compiled = lex(source)
-    //             .and_then(parse)
-    //             .and_then(desugar)
-    //             .and_then(hoist)
-    //             .and_then(gen);
-    //
-    //         match compiled {
-    //             Ok(lambda) => { break 'read Ok(lambda); },
-    //             Err(e)     => if exit { break 'read Err(e); },
-    //         };
-    //     };
-    //
-    //     let lambda = match lambda {
-    //         Ok(l)  => l,
-    //         Err(e) => { println!("\n{}\n", e); continue; },
-    //     };
-    //
-    //     let mut vm = VM::init(Closure::wrap(lambda));
-    //     match vm.run() {
-    //         Ok(()) => {
-    //             let data = vm.stack.pop_data();
-    //             println!("= {}\n", data)
-    //         },
-    //         Err(e) => {
-    //             println!("\n{}\n", e);
-    //         },
-    //     }
-    // }
+    println!("Hit ^D to quit.\n");
+
+    let mut session = Session::new();
+    let mut fiber = session.fiber();
+    kernel::register(&mut fiber);
+
+    while let Some(entry) = read_entry()? {
+        if entry.trim().is_empty() {
+            continue;
+        }
+
+        let lambda = match session.compile(Source::source(&entry)) {
+            Ok(lambda) => lambda,
+            Err(syntax) => {
+                println!("{}\n", syntax);
+                continue;
+            }
+        };
+
+        fiber.extend(Closure::wrap(lambda));
+        match fiber.run() {
+            Ok(()) => println!("= {}\n", fiber.stack.pop_data()),
+            Err(trace) => println!("{}\n", trace),
+        }
+    }
+
+    println!();
+    Ok(())
+}
+
+/// Reads lines until every delimiter opened in the entry is closed.
+/// An empty line ends the entry early, delimiters or not.
+/// Returns `None` once there is nothing left to read.
+fn read_entry() -> Result<Option<String>, String> {
+    let mut entry = String::new();
+
+    loop {
+        print!("{} ", if entry.is_empty() { ">" } else { "|" });
+        io::stdout().flush().map_err(|e| e.to_string())?;
+
+        let mut line = String::new();
+        let read = io::stdin()
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?;
+
+        if read == 0 {
+            return Ok(if entry.is_empty() { None } else { Some(entry) });
+        }
+
+        let blank = line.trim().is_empty();
+        entry.push_str(&line);
+
+        let unclosed = lex(Source::source(&entry))
+            .map(Reader::is_unclosed)
+            .unwrap_or(false);
+
+        if blank || !unclosed {
+            return Ok(Some(entry));
+        }
+    }
 }
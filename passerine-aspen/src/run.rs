@@ -1,28 +1,39 @@
 use std::path::PathBuf;
 
-use passerine::{compile, Source};
+use passerine::{compile_package_with_warnings, kernel, Fiber, Module};
 
-use crate::{manifest::Manifest, ENTRYPOINT, SOURCE};
+use crate::{manifest::Manifest, status::Status, SOURCE};
 
-pub fn run(path: PathBuf) -> Result<(), String> {
-    // just one file, for now
+/// Compiles and runs a package,
+/// optionally printing the compiled bytecode first.
+/// Every module in the source directory is compiled,
+/// and the entrypoint is run after the modules it uses.
+/// Warnings are printed, but only stop the package from running
+/// if `deny_warnings` is set.
+pub fn run(path: PathBuf, bytecode: bool, deny_warnings: bool) -> Result<(), String> {
     let (_manifest, path) = Manifest::package(&path)?;
-    let file = path.join(SOURCE).join(ENTRYPOINT);
-
-    let source = Source::path(&file).map_err(|_| {
-        format!(
-            "Could not find source entrypoint '{}/{}'",
-            SOURCE, ENTRYPOINT
-        )
-    })?;
-
-    let bytecode = compile(source).map_err(|e| e.to_string())?;
-
-    println!("{:#?}", bytecode.lambda);
-    println!("{}", bytecode.lambda);
-
-    // let mut vm = VM::init(Closure::wrap(bytecode));
-    // vm.run().map_err(|e| e.to_string())?;
-
-    Ok(())
+    let (module, warnings) = Module::new_from_dir(&path.join(SOURCE))?;
+    for warning in warnings {
+        Status::warn().log(&warning);
+    }
+
+    let (closure, lints) = compile_package_with_warnings(&module).map_err(|e| e.to_string())?;
+    for lint in lints.iter() {
+        Status::warn().log(&lint.to_string());
+    }
+    if deny_warnings && !lints.is_empty() {
+        return Err(format!(
+            "{} warning{} raised while compiling, and warnings are denied",
+            lints.len(),
+            if lints.len() == 1 { " was" } else { "s were" },
+        ));
+    }
+
+    if bytecode {
+        println!("{}", closure.lambda);
+    }
+
+    let mut fiber = Fiber::init(closure);
+    kernel::register(&mut fiber);
+    fiber.run().map_err(|e| e.to_string())
 }
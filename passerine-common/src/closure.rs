@@ -1,15 +1,23 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
 
 use crate::{data::Data, lambda::Lambda};
 
 /// Wraps a `Lambda` with some scope context.
 /// Each closure is unique when constructed,
 /// Because it depends on the surrounding environment it was constructed in.
-/// It holds a set of references to variables it captures.
-#[derive(Debug, Clone, PartialEq)]
+/// It holds a set of references to variables it captures,
+/// each of which holds nothing until the variable is assigned.
+/// Closures are compared by identity, i.e. by the lambda they wrap
+/// and the variables they capture, rather than the values of those variables.
+#[derive(Debug, Clone)]
 pub struct Closure {
     pub lambda: Rc<Lambda>,
-    pub captures: Vec<Rc<RefCell<Data>>>,
+    pub captures: Vec<Rc<RefCell<Option<Data>>>>,
 }
 
 impl Closure {
@@ -21,6 +29,38 @@ impl Closure {
             captures: vec![],
         }
     }
+
+    /// The addresses that identify this closure.
+    fn identity(&self) -> (*const Lambda, Vec<*const RefCell<Option<Data>>>) {
+        let captures = self.captures.iter().map(Rc::as_ptr).collect();
+        (Rc::as_ptr(&self.lambda), captures)
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Closure) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for Closure {}
+
+impl PartialOrd for Closure {
+    fn partial_cmp(&self, other: &Closure) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Closure {
+    fn cmp(&self, other: &Closure) -> Ordering {
+        self.identity().cmp(&other.identity())
+    }
+}
+
+impl Hash for Closure {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity().hash(state);
+    }
 }
 
 #[cfg(test)]
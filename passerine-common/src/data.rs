@@ -1,16 +1,28 @@
 use std::{
+    cmp::Ordering,
     collections::BTreeMap,
     f64,
     fmt::{Debug, Display, Formatter, Result},
+    hash::{Hash, Hasher},
     rc::Rc,
 };
 
-use crate::{closure::Closure, lambda::Lambda};
+use crate::{closure::Closure, effect::Continuation, label::Kind, lambda::Lambda};
 
 // TODO: separate VM data from parser data
 
 /// Built-in Passerine datatypes.
-#[derive(Clone, PartialEq)]
+///
+/// Data has a total ordering, so that any data can be used as the key of a map.
+/// Data of different variants is ordered by variant, in the order declared.
+/// Floats are ordered numerically, except that `-0.0` is equal to `0.0`,
+/// and `NaN` is equal to itself and greater than every other float.
+/// Functions and continuations are compared by identity:
+/// two closures are equal if they run the same lambda
+/// and capture the very same variables.
+/// Their order is consistent while a program runs,
+/// but may change from one run to the next.
+#[derive(Clone)]
 pub enum Data {
     // Passerine Data (Atomic)
     /// Float Numbers, represented as double-precision floating points.
@@ -30,12 +42,14 @@ pub enum Data {
     /// Some bytecode with a context that can be run.
     Closure(Box<Closure>),
 
+    /// A computation suspended by an effect, which can be resumed once.
+    Continuation(Continuation),
+
     // TODO: just remove Kind
     /// `Kind` is the base component of an unconstructed label
-    Kind(usize),
+    Kind(Kind),
     /// A Label is similar to a type, and wraps some data.
-    /// in the future labels will have associated namespaces.
-    Label(usize, Box<Data>),
+    Label(Kind, Box<Data>),
 
     // TODO: equivalence between Unit and Tuple(vec![])?
 
@@ -44,19 +58,137 @@ pub enum Data {
     Unit, // an empty typle
     /// A non-empty Tuple.
     Tuple(Vec<Data>),
-    // // TODO: Hashmap?
-    // // I mean, it's overkill for small things
-    // // yet if people have very big records, yk.
-    Record(BTreeMap<usize, Data>),
+    /// A record, mapping the name of each field to its value.
+    Record(BTreeMap<String, Data>),
+    /// A list of any length, whose items are shared between copies.
+    List(Rc<Vec<Data>>),
+    /// A map from keys to values, ordered by key.
     Map(BTreeMap<Data, Data>),
     // ArbInt(ArbInt),
 }
 
-// TODO: manually implement the equality trait
-// NOTE: might have to implement partial equality as well
+impl Data {
+    /// Compares two numbers of the same type, or two strings,
+    /// as Passerine's comparison operators do.
+    /// Floats follow the ordering described above.
+    /// An integer and a float are never equal,
+    /// so they can not be compared with one another either.
+    /// Returns `None` for data that can not be compared this way.
+    pub fn compare(&self, other: &Data) -> Option<Ordering> {
+        match (self, other) {
+            (Data::Integer(l), Data::Integer(r)) => Some(l.cmp(r)),
+            (Data::Float(l), Data::Float(r)) => Some(float_cmp(*l, *r)),
+            (Data::String(l), Data::String(r)) => Some(l.cmp(r)),
+            _ => None,
+        }
+    }
+
+    /// The position of each variant in the ordering of data.
+    fn rank(&self) -> u8 {
+        match self {
+            Data::Float(_) => 0,
+            Data::Integer(_) => 1,
+            Data::Boolean(_) => 2,
+            Data::String(_) => 3,
+            Data::Lambda(_) => 4,
+            Data::Closure(_) => 5,
+            Data::Continuation(_) => 6,
+            Data::Kind(_) => 7,
+            Data::Label(_, _) => 8,
+            Data::Unit => 9,
+            Data::Tuple(_) => 10,
+            Data::Record(_) => 11,
+            Data::List(_) => 12,
+            Data::Map(_) => 13,
+        }
+    }
+}
+
+/// Orders floats numerically, treating `-0.0` as `0.0`
+/// and every `NaN` as the same value, greater than all others.
+fn float_cmp(left: f64, right: f64) -> Ordering {
+    match (left.is_nan(), right.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => left.partial_cmp(&right).unwrap(),
+    }
+}
+
+/// Hashes a float so that floats that are equal have the same hash.
+fn float_hash<H: Hasher>(float: f64, state: &mut H) {
+    let float = if float.is_nan() {
+        f64::NAN
+    } else if float == 0.0 {
+        0.0
+    } else {
+        float
+    };
+    float.to_bits().hash(state);
+}
+
+impl PartialEq for Data {
+    fn eq(&self, other: &Data) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
 // NOTE: equality represents passerine equality, not rust equality
 impl Eq for Data {}
 
+impl PartialOrd for Data {
+    fn partial_cmp(&self, other: &Data) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Data {
+    fn cmp(&self, other: &Data) -> Ordering {
+        match (self, other) {
+            (Data::Float(l), Data::Float(r)) => float_cmp(*l, *r),
+            (Data::Integer(l), Data::Integer(r)) => l.cmp(r),
+            (Data::Boolean(l), Data::Boolean(r)) => l.cmp(r),
+            (Data::String(l), Data::String(r)) => l.cmp(r),
+            (Data::Lambda(l), Data::Lambda(r)) => Rc::as_ptr(l).cmp(&Rc::as_ptr(r)),
+            (Data::Closure(l), Data::Closure(r)) => l.cmp(r),
+            (Data::Continuation(l), Data::Continuation(r)) => l.cmp(r),
+            (Data::Kind(l), Data::Kind(r)) => l.cmp(r),
+            (Data::Label(l, v), Data::Label(r, w)) => l.cmp(r).then_with(|| v.cmp(w)),
+            (Data::Unit, Data::Unit) => Ordering::Equal,
+            (Data::Tuple(l), Data::Tuple(r)) => l.cmp(r),
+            (Data::Record(l), Data::Record(r)) => l.cmp(r),
+            (Data::List(l), Data::List(r)) => l.cmp(r),
+            (Data::Map(l), Data::Map(r)) => l.cmp(r),
+            (l, r) => l.rank().cmp(&r.rank()),
+        }
+    }
+}
+
+impl Hash for Data {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Data::Float(f) => float_hash(*f, state),
+            Data::Integer(i) => i.hash(state),
+            Data::Boolean(b) => b.hash(state),
+            Data::String(s) => s.hash(state),
+            Data::Lambda(l) => Rc::as_ptr(l).hash(state),
+            Data::Closure(c) => c.hash(state),
+            Data::Continuation(c) => c.hash(state),
+            Data::Kind(k) => k.hash(state),
+            Data::Label(k, v) => {
+                k.hash(state);
+                v.hash(state);
+            }
+            Data::Unit => (),
+            Data::Tuple(t) => t.hash(state),
+            Data::Record(r) => r.hash(state),
+            Data::List(l) => l.hash(state),
+            Data::Map(m) => m.hash(state),
+        }
+    }
+}
+
 impl Display for Data {
     /// Displays some Passerine Data in a pretty manner, as if it were printed
     /// to console.
@@ -73,7 +205,9 @@ impl Display for Data {
             Data::String(s) => write!(f, "{}", s),
             Data::Lambda(_) => unreachable!("Can not display naked functions"),
             Data::Closure(_) => write!(f, "Function"),
+            Data::Continuation(_) => write!(f, "Continuation"),
             Data::Kind(_) => unreachable!("Can not display naked labels"),
+            Data::Label(n, v) if matches!(**v, Data::Label(..)) => write!(f, "{} ({})", n, v),
             Data::Label(n, v) => write!(f, "{} {}", n, v),
             Data::Unit => write!(f, "()"),
             Data::Tuple(t) => write!(
@@ -84,8 +218,31 @@ impl Display for Data {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
-            // TODO: create representation
-            _ => panic!("Representation is not yet implemented for this"),
+            Data::Record(r) => write!(
+                f,
+                "{{ {} }}",
+                r.iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Data::List(l) => write!(
+                f,
+                "[{}]",
+                l.iter()
+                    .map(|i| format!("{}", i))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Data::Map(m) if m.is_empty() => write!(f, "[=>]"),
+            Data::Map(m) => write!(
+                f,
+                "[{}]",
+                m.iter()
+                    .map(|(key, value)| format!("{} => {}", key, value))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
         }
     }
 }
@@ -104,12 +261,74 @@ impl Debug for Data {
             Data::Lambda(_) => write!(f, "Function(...)"),
             Data::Closure(_c) => write!(f, "Closure(...)"), /* TODO: how to */
             // differentiate?
-            Data::Kind(n) => write!(f, "Kind({})", n),
-            Data::Label(n, v) => write!(f, "Label({}, {:?})", n, v),
+            Data::Continuation(_) => write!(f, "Continuation(...)"),
+            Data::Kind(n) => write!(f, "Kind({})", n.label().qualified()),
+            Data::Label(n, v) => write!(f, "Label({}, {:?})", n.label().qualified(), v),
             Data::Unit => write!(f, "Unit"),
             Data::Tuple(t) => write!(f, "Tuple({:?})", t),
-            // TODO: create representation
-            _ => panic!("Representation is not yet implemented for this"),
+            Data::Record(r) => write!(f, "Record({:?})", r),
+            Data::List(l) => write!(f, "List({:?})", l),
+            Data::Map(m) => write!(f, "Map({:?})", m),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use super::*;
+
+    fn hash(data: &Data) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn floats() {
+        let nan = Data::Float(f64::NAN);
+        assert_eq!(nan, Data::Float(-f64::NAN));
+        assert!(nan > Data::Float(f64::INFINITY));
+        assert_eq!(Data::Float(-0.0), Data::Float(0.0));
+        assert_eq!(hash(&Data::Float(-0.0)), hash(&Data::Float(0.0)));
+        assert_eq!(hash(&nan), hash(&Data::Float(-f64::NAN)));
+        assert!(Data::Float(-1.5) < Data::Float(2.0));
+    }
+
+    #[test]
+    fn compare() {
+        assert_eq!(
+            Data::Float(1.0).compare(&Data::Float(1.5)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(Data::Integer(1).compare(&Data::Float(1.0)), None);
+        assert_eq!(Data::Float(1.0).compare(&Data::Integer(1)), None);
+        assert_eq!(
+            Data::String("b".into()).compare(&Data::String("a".into())),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(Data::Integer(1).compare(&Data::String("1".into())), None);
+    }
+
+    #[test]
+    fn variants() {
+        assert_ne!(Data::Integer(1), Data::Float(1.0));
+        assert!(Data::Float(100.0) < Data::Integer(0));
+        assert!(Data::Unit < Data::Tuple(vec![]));
+        assert!(
+            Data::List(Rc::new(vec![Data::Integer(1)]))
+                < Data::List(Rc::new(vec![Data::Integer(1), Data::Integer(0)]))
+        );
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn map_display() {
+        let mut map = BTreeMap::new();
+        assert_eq!(Data::Map(map.clone()).to_string(), "[=>]");
+        map.insert(Data::Integer(2), Data::String("two".into()));
+        map.insert(Data::Integer(1), Data::String("one".into()));
+        assert_eq!(Data::Map(map).to_string(), "[1 => one, 2 => two]");
+    }
+}
@@ -1,33 +1,111 @@
-use std::marker::PhantomData;
+use std::{
+    any::Any,
+    cell::RefCell,
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
 
-use crate::{data::Data, inject::Inject};
-
-// TODO: switch from using From to TryFrom.
+use crate::inject::Inject;
 
+/// Identifies an effect across the boundary between Passerine and Rust.
+/// Effects are identified by name, so the id of an effect declared as
+/// `effect Write` is the same as the id of a Rust type named `Write`.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub struct EffectId(usize);
+pub struct EffectId(u64);
 
-pub struct Handler<T: Inject> {
-    id: EffectId,
-    _into: PhantomData<T>,
+impl EffectId {
+    /// Hashes the name of an effect to produce its id.
+    /// Uses FNV-1a, so ids are stable across runs and platforms.
+    pub fn new(name: &str) -> EffectId {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in name.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        EffectId(hash)
+    }
 }
 
-pub struct Effect {
-    pub id: EffectId,
-    unmatched_data: Option<Data>,
+/// A Rust type that represents the payload of an effect,
+/// usually implemented through `#[derive(Effect)]`.
+/// Host handlers for an effect receive its payload deserialized as this type.
+pub trait Effect: Inject {
+    /// The name of the effect, as it is declared in Passerine.
+    const NAME: &'static str;
+
+    /// The id of the effect.
+    fn id() -> EffectId {
+        EffectId::new(Self::NAME)
+    }
 }
 
-impl Effect {
-    #[inline(always)]
-    pub fn matches<T>(&mut self, handler: Handler<T>) -> Option<Option<T>>
-    where
-        T: Inject,
-    {
-        if self.id == handler.id {
-            let data = std::mem::replace(&mut self.unmatched_data, None);
-            data.map(Inject::deserialize)
-        } else {
-            None
-        }
+/// A computation that was suspended when it raised an effect,
+/// which can be resumed at most once.
+/// What is captured is up to the VM, so it is stored opaquely;
+/// clones share the same underlying computation.
+#[derive(Clone)]
+pub struct Continuation(Rc<RefCell<Option<Box<dyn Any>>>>);
+
+impl Continuation {
+    /// Wraps a suspended computation.
+    pub fn new<T: Any>(suspended: T) -> Continuation {
+        Continuation(Rc::new(RefCell::new(Some(Box::new(suspended)))))
+    }
+
+    /// Takes the suspended computation so it can be resumed.
+    /// Returns `None` if it has already been resumed.
+    pub fn take<T: Any>(&self) -> Option<T> {
+        let suspended = self.0.borrow_mut().take()?;
+        Some(*suspended.downcast::<T>().ok()?)
+    }
+}
+
+impl PartialEq for Continuation {
+    /// Continuations are only equal if they are the same continuation.
+    fn eq(&self, other: &Continuation) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Continuation {}
+
+impl PartialOrd for Continuation {
+    fn partial_cmp(&self, other: &Continuation) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Continuation {
+    /// Continuations are ordered by address.
+    fn cmp(&self, other: &Continuation) -> Ordering {
+        Rc::as_ptr(&self.0).cmp(&Rc::as_ptr(&other.0))
+    }
+}
+
+impl Hash for Continuation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_ids() {
+        assert_eq!(EffectId::new("Write"), EffectId::new("Write"));
+        assert_ne!(EffectId::new("Write"), EffectId::new("Show"));
+        // FNV-1a of the empty string is the offset basis
+        assert_eq!(EffectId::new(""), EffectId(0xcbf2_9ce4_8422_2325));
+    }
+
+    #[test]
+    fn resume_once() {
+        let continuation = Continuation::new(7_usize);
+        let clone = continuation.clone();
+        assert_eq!(clone.take::<usize>(), Some(7));
+        assert_eq!(continuation.take::<usize>(), None);
     }
 }
@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, rc::Rc};
+
 use crate::data::Data;
 
 /// Indicates that a Rust data structure can be serialized to Passerine data,
@@ -90,6 +92,40 @@ impl_inject! {
     into => Data::String(into),
 }
 
+// Lists
+// A list shared with other data is copied when deserialized as a `Vec`.
+// Deserializing a list as an `Rc` never copies it,
+// so it can be modified with `Rc::make_mut`, which copies only if it is shared.
+
+impl_inject! {
+    Vec<Data> where
+    from => match from {
+        Data::List(l) => Some(Rc::try_unwrap(l).unwrap_or_else(|l| (*l).clone())),
+        _ => None,
+    },
+    into => Data::List(Rc::new(into)),
+}
+
+impl_inject! {
+    Rc<Vec<Data>> where
+    from => match from {
+        Data::List(l) => Some(l),
+        _ => None,
+    },
+    into => Data::List(into),
+}
+
+// Maps
+
+impl_inject! {
+    BTreeMap<Data, Data> where
+    from => match from {
+        Data::Map(m) => Some(m),
+        _ => None,
+    },
+    into => Data::Map(into),
+}
+
 // Tuples
 
 // impl_inject! {
@@ -0,0 +1,221 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter, Result},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+/// The path of the root module of a program.
+pub const ROOT: &str = "";
+
+/// The name of a label, along with the module it was declared in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+    /// The path of the module that declares the label,
+    /// like `net.http`, or empty for the root module.
+    pub module: String,
+    /// The name of the label itself, like `Some`.
+    pub name: String,
+}
+
+impl Label {
+    /// The name of the label, prefixed by its module.
+    pub fn qualified(&self) -> String {
+        if self.module.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}.{}", self.module, self.name)
+        }
+    }
+}
+
+/// Identifies a label at runtime.
+/// Kinds are handed out by a `Labels` registry,
+/// and two kinds are the same if the same registry gave them the same id.
+/// Kinds from different registries are never the same.
+/// Each kind keeps the `Label` it stands for, so it can be displayed by name.
+#[derive(Clone)]
+pub struct Kind {
+    id: usize,
+    label: Rc<Label>,
+}
+
+impl Kind {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn label(&self) -> &Label {
+        &self.label
+    }
+
+    /// The id of the kind, along with the address of its label,
+    /// which is unique to the registry that handed the kind out.
+    fn identity(&self) -> (usize, *const Label) {
+        (self.id, Rc::as_ptr(&self.label))
+    }
+}
+
+impl PartialEq for Kind {
+    fn eq(&self, other: &Kind) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for Kind {}
+
+impl PartialOrd for Kind {
+    fn partial_cmp(&self, other: &Kind) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Kind {
+    fn cmp(&self, other: &Kind) -> Ordering {
+        self.identity().cmp(&other.identity())
+    }
+}
+
+impl Hash for Kind {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.label.name)
+    }
+}
+
+impl Debug for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Kind({}, {})", self.id, self.label.qualified())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    kinds: Vec<Kind>,
+    ids: HashMap<Rc<Label>, usize>,
+}
+
+/// Interns labels, so that the same label in the same module
+/// is always represented by the same `Kind`.
+/// Cloning a registry produces a handle to the same registry,
+/// which is how the compiler shares it with the `Fiber`
+/// that runs what it compiled.
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    registry: Rc<RefCell<Registry>>,
+}
+
+/// Two handles are equal if they are handles to the same registry.
+impl PartialEq for Labels {
+    fn eq(&self, other: &Labels) -> bool {
+        Rc::ptr_eq(&self.registry, &other.registry)
+    }
+}
+
+impl Labels {
+    pub fn new() -> Labels {
+        Labels::default()
+    }
+
+    /// Returns the kind of a label declared in a module,
+    /// registering the label if it has not been seen before.
+    pub fn intern(&self, module: &str, name: &str) -> Kind {
+        let label = Label {
+            module: module.to_string(),
+            name: name.to_string(),
+        };
+
+        let mut registry = self.registry.borrow_mut();
+        if let Some(id) = registry.ids.get(&label) {
+            return registry.kinds[*id].clone();
+        }
+
+        let kind = Kind {
+            id: registry.kinds.len(),
+            label: Rc::new(label),
+        };
+        registry.ids.insert(kind.label.clone(), kind.id);
+        registry.kinds.push(kind.clone());
+        kind
+    }
+
+    /// Returns the kind with a given id, if it has been registered.
+    pub fn get(&self, id: usize) -> Option<Kind> {
+        self.registry.borrow().kinds.get(id).cloned()
+    }
+
+    /// The number of labels registered so far.
+    pub fn len(&self) -> usize {
+        self.registry.borrow().kinds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Data;
+
+    #[test]
+    fn interned() {
+        let labels = Labels::new();
+        let some = labels.intern(ROOT, "Some");
+        let none = labels.intern(ROOT, "None");
+
+        assert_eq!(labels.intern(ROOT, "Some"), some);
+        assert_ne!(some, none);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels.get(some.id()), Some(some));
+    }
+
+    #[test]
+    fn modules() {
+        let labels = Labels::new();
+        let root = labels.intern(ROOT, "Error");
+        let shared = labels.clone();
+        let nested = shared.intern("net.http", "Error");
+
+        assert_ne!(root, nested);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(root.to_string(), "Error");
+        assert_eq!(nested.to_string(), "Error");
+        assert_eq!(nested.label().qualified(), "net.http.Error");
+    }
+
+    #[test]
+    fn registries() {
+        let program = Labels::new();
+        let host = Labels::new();
+        let some = program.intern(ROOT, "Some");
+        let none = host.intern(ROOT, "None");
+        let other = host.intern(ROOT, "Some");
+
+        assert_eq!(some.id(), none.id());
+        assert_ne!(some, none);
+        assert_ne!(some, other);
+        assert_ne!(program, host);
+        assert_eq!(program, program.clone());
+    }
+
+    #[test]
+    fn display() {
+        let labels = Labels::new();
+        let some = labels.intern(ROOT, "Some");
+        let inner = Data::Label(some.clone(), Box::new(Data::Integer(5)));
+        let outer = Data::Label(some, Box::new(inner.clone()));
+
+        assert_eq!(inner.to_string(), "Some 5");
+        assert_eq!(outer.to_string(), "Some (Some 5)");
+        assert_eq!(format!("{:?}", inner), "Label(Some, Integer(5))");
+    }
+}
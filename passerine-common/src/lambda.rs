@@ -1,6 +1,17 @@
 use std::fmt;
 
-use crate::{data::Data, number::build_number, opcode::Opcode, span::Span};
+use crate::{
+    data::Data,
+    label::Labels,
+    number::{build_number, split_number_padded},
+    opcode::Opcode,
+    span::Span,
+};
+
+/// The number of bytes used to encode the target of a jump.
+/// Jump targets have a fixed width so that they can be
+/// patched once the position they jump to is known.
+const JUMP_WIDTH: usize = 4;
 
 /// Represents a variable visible in the current scope.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -16,8 +27,11 @@ pub enum Captured {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Lambda {
     // TODO: make this a list of variable names
-    // So structs can be made, and state preserved in the repl.
+    // So structs can be made.
     /// Number of variables declared in this scope.
+    /// When a program is compiled one entry at a time, as in the REPL,
+    /// this includes the variables declared by the root of earlier entries,
+    /// which come first.
     pub decls: usize,
     /// Each byte is an opcode or a number-stream.
     pub code: Vec<u8>,
@@ -28,10 +42,10 @@ pub struct Lambda {
     /// List of positions of locals in the scope where this lambda is defined,
     /// indexes must be gauranteed to be data on the heap.
     pub captures: Vec<Captured>,
-    // TODO: delete FFI
-    // / List of FFI functions (i.e. Rust functions)
-    // / that can be called from this function.
-    // pub ffi: Vec<FFIFunction>,
+    /// The registry the labels in this lambda were interned in.
+    /// A `Fiber` running the lambda uses it too,
+    /// so the host can construct labels that match the program's.
+    pub labels: Labels,
     // TODO: add effects
     // pub effects: Vec<usize>,
 }
@@ -45,7 +59,7 @@ impl Lambda {
             spans: vec![],
             constants: vec![],
             captures: vec![],
-            // ffi:       vec![],
+            labels: Labels::new(),
         }
     }
 
@@ -73,9 +87,8 @@ impl Lambda {
             Opcode::Con => vec![self.constants.len()],
             Opcode::NotInit => vec![],
             Opcode::Del => vec![],
-            Opcode::FFICall => panic!(),
             Opcode::Copy => vec![],
-            // Opcode::Capture => vec![self.decls],
+            Opcode::Capture => vec![self.decls],
 
             // TODO: correct bounds check?
             Opcode::Save => vec![self.decls],
@@ -87,13 +100,46 @@ impl Lambda {
             // TODO: correct bounds check?
             Opcode::Closure => vec![self.constants.len()],
             Opcode::Print => vec![],
+            Opcode::Handler => vec![self.constants.len()],
+            Opcode::Effect => vec![self.constants.len()],
             Opcode::Label => vec![],
             Opcode::Tuple => vec![usize::MAX], // TODO: stricter bounds
+            Opcode::Record => vec![self.constants.len()],
             Opcode::UnData => vec![],
             Opcode::UnLabel => vec![],
             Opcode::UnTuple => vec![usize::MAX], // TODO: stricter bounds
+            Opcode::Add => vec![],
+            Opcode::Sub => vec![],
+            Opcode::Neg => vec![],
+            Opcode::Mul => vec![],
+            Opcode::Div => vec![],
+            Opcode::Rem => vec![],
+            Opcode::Pow => vec![],
+            // jumping to the very end of the bytecode is allowed
+            Opcode::Jump => vec![self.code.len() + 1],
+            Opcode::JumpFalse => vec![self.code.len() + 1],
+            Opcode::Index => vec![usize::MAX], // TODO: stricter bounds
+            Opcode::Equal => vec![],
+            Opcode::IsLabel => vec![],
+            Opcode::IsTuple => vec![usize::MAX],
+            Opcode::NoMatch => vec![self.constants.len()],
+            Opcode::Field => vec![self.constants.len()],
+            Opcode::Update => vec![self.constants.len()],
+            Opcode::UnRecord => vec![self.constants.len()],
+            Opcode::IsRecord => vec![self.constants.len()],
+            Opcode::List => vec![usize::MAX],
+            Opcode::Spread => vec![usize::MAX],
+            Opcode::UnList => vec![usize::MAX, 2],
+            Opcode::Rest => vec![usize::MAX],
+            Opcode::IsList => vec![usize::MAX, 2],
+            Opcode::Map => vec![usize::MAX],
+            Opcode::NotEqual => vec![],
+            Opcode::Less => vec![],
+            Opcode::Greater => vec![],
+            Opcode::LessEqual => vec![],
+            Opcode::GreaterEqual => vec![],
+            Opcode::Not => vec![],
             Opcode::Noop => vec![],
-            e => panic!("not implemented {:?}", e),
         }
     }
 
@@ -101,14 +147,19 @@ impl Lambda {
     /// Statically verifies some bytecode safely,
     /// By ensuring bytecode ops are within bounds,
     /// As well as the arguments those ops take.
+    /// Jumps must land on the start of an op, or the end of the bytecode.
     /// Returns `false` if the bytecode is invalid.
     pub fn verify(&self) -> bool {
         // go through each opcode
         // check the number of arguments
         // check that the arguments are valid
         let mut index = 0;
+        let mut starts = vec![];
+        let mut targets = vec![];
 
         while index < self.code.len() {
+            starts.push(index);
+
             // safely decode an opcode
             let opcode = match Opcode::from_byte_safe(self.code[index]) {
                 Some(o) => o,
@@ -118,18 +169,25 @@ impl Lambda {
             };
 
             index += 1;
+            let is_jump = matches!(opcode, Opcode::Jump | Opcode::JumpFalse);
             let bounds = self.bounds(opcode);
             let args_result = self.args_safe(index, &bounds);
 
             index += match args_result {
-                Some((_args, consumed)) => consumed,
+                Some((args, consumed)) => {
+                    if is_jump {
+                        targets.push(args[0]);
+                    }
+                    consumed
+                }
                 None => {
                     return false;
                 }
             }
         }
 
-        return true;
+        starts.push(self.code.len());
+        targets.iter().all(|target| starts.contains(target))
     }
 
     /// Emits an opcode as a byte.
@@ -149,6 +207,23 @@ impl Lambda {
         self.spans.push((self.code.len(), span.clone()))
     }
 
+    /// Emits a jump whose target is not yet known.
+    /// Returns the position of the target,
+    /// which should be filled in later with `patch_jump`.
+    pub fn emit_jump(&mut self, op: Opcode) -> usize {
+        self.emit(op);
+        let target = self.code.len();
+        self.emit_bytes(&mut split_number_padded(0, JUMP_WIDTH));
+        target
+    }
+
+    /// Sets the target of a jump emitted with `emit_jump`
+    /// to the position of the next op to be emitted.
+    pub fn patch_jump(&mut self, target: usize) {
+        let position = split_number_padded(self.code.len(), JUMP_WIDTH);
+        self.code[target..target + JUMP_WIDTH].copy_from_slice(&position);
+    }
+
     /// Removes the last emitted byte.
     pub fn demit(&mut self) {
         self.code.pop();
@@ -9,6 +9,7 @@ pub mod closure;
 pub mod data;
 pub mod effect;
 pub mod inject;
+pub mod label;
 pub mod lambda;
 pub mod lit;
 pub mod module;
@@ -21,6 +22,7 @@ pub mod ty;
 pub use closure::Closure;
 pub use data::Data;
 pub use inject::Inject;
+pub use label::{Kind, Labels};
 pub use module::Module;
 pub use source::Source;
 pub use span::{Span, Spanned};
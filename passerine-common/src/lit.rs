@@ -3,7 +3,7 @@ use std::{
     fmt::{Debug, Display, Formatter, Result},
 };
 
-use crate::data::Data;
+use crate::{data::Data, label::Kind};
 
 pub enum ArbInt {
     Small(u128),
@@ -27,9 +27,8 @@ pub enum Lit {
     String(String),
 
     /// A Label is similar to a type, and wraps some data.
-    /// in the future labels will have associated namespaces.
     #[proptest(skip)]
-    Label(usize, Box<Lit>),
+    Label(Kind, Box<Lit>),
 
     // Compound Datatypes
     /// The empty Tuple
@@ -43,7 +42,7 @@ impl Lit {
             Lit::Float(f) => Data::Float(f),
             Lit::Integer(i) => Data::Integer(i),
             Lit::String(s) => Data::String(s),
-            Lit::Label(_, _) => todo!(),
+            Lit::Label(n, v) => Data::Label(n, Box::new(v.to_data())),
             Lit::Unit => Data::Unit,
             Lit::Boolean(b) => Data::Boolean(b),
         }
@@ -58,8 +57,7 @@ impl Display for Lit {
             Lit::Float(n) => write!(f, "{}", n),
             Lit::Integer(n) => write!(f, "{}", n),
             Lit::String(s) => write!(f, "{}", s),
-            // TODO: better representation for Labels
-            Lit::Label(n, v) => write!(f, "#{}({})", n, v),
+            Lit::Label(n, v) => write!(f, "{} {}", n, v),
             Lit::Unit => write!(f, "()"),
             Lit::Boolean(b) => {
                 write!(f, "{}", if *b { "True" } else { "False" })
@@ -1,91 +1,347 @@
-use std::{fs, path::Path, rc::Rc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use crate::source::Source;
 
+/// A module is a source file, along with the modules nested in it.
+/// A module in a directory is defined by the entry point of the directory,
+/// and its children are the other source files and directories in the directory.
+#[derive(Debug)]
 pub struct Module {
+    name: String,
     source: Rc<Source>,
     children: Vec<Module>,
 }
 
 pub const ENTRY_POINT: &str = "main";
 pub const EXTENSION: &str = "pn";
-
-// TODO: handle symlinks, ugh
+/// The file listing the paths module discovery should skip.
+pub const IGNORE_FILE: &str = ".pnignore";
 
 impl Module {
-    pub fn new_from_dir(entry_path: &Path) -> Result<Module, String> {
-        // grab the entries in the directory
-        let paths = fs::read_dir(entry_path).map_err(|_| {
+    /// Builds a module out of a source and its child modules.
+    /// The module is named after its source file,
+    /// or after its directory if the source is an entry point.
+    pub fn new(source: Rc<Source>, children: Vec<Module>) -> Module {
+        let path = &source.path;
+        let stem = path.file_stem();
+        let name = if stem.map(|x| x == ENTRY_POINT).unwrap_or(false) {
+            path.parent().and_then(|parent| parent.file_name()).or(stem)
+        } else {
+            stem
+        };
+        let name = name
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| ENTRY_POINT.to_string());
+
+        Module {
+            name,
+            source,
+            children,
+        }
+    }
+
+    pub fn source(&self) -> &Rc<Source> {
+        &self.source
+    }
+
+    pub fn children(&self) -> &[Module] {
+        &self.children
+    }
+
+    /// The name the module is used by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Discovers the modules in a directory, which must have an entry point.
+    /// Children are sorted by name, so the same tree is always discovered
+    /// in the same order, no matter the order the OS lists directories in.
+    ///
+    /// Paths are canonicalised, so symlinks are followed,
+    /// but a symlink to a directory that contains it is skipped.
+    /// So are directories without an entry point,
+    /// along with any paths listed in the ignore file (`.pnignore`)
+    /// at the root of the directory.
+    /// Returns a warning for each entry that was skipped,
+    /// other than ignored entries and files that are not source files.
+    pub fn new_from_dir(entry_path: &Path) -> Result<(Module, Vec<String>), String> {
+        let unreadable = || {
             format!(
                 "The path `{}` could not be read as a directory",
                 entry_path.display()
             )
+        };
+        let root = fs::canonicalize(entry_path).map_err(|_| unreadable())?;
+        if !root.is_dir() {
+            return Err(unreadable());
+        }
+
+        let ignored = match fs::read_to_string(root.join(IGNORE_FILE)) {
+            Ok(contents) => Discovery::ignored(&contents),
+            Err(_) => vec![],
+        };
+
+        let mut discovery = Discovery {
+            ignored,
+            ancestors: vec![],
+            warnings: vec![],
+        };
+        let module = discovery.dir(&root, &PathBuf::new(), entry_path)?;
+        Ok((module, discovery.warnings))
+    }
+}
+
+/// Keeps track of where we are while discovering modules.
+struct Discovery {
+    /// Paths relative to the root, or names that can be anywhere.
+    ignored: Vec<String>,
+    /// The canonical paths of the directories being discovered,
+    /// each the parent of the next.
+    ancestors: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
+impl Discovery {
+    /// Reads an ignore file, which has one path on each line.
+    /// Blank lines and lines starting with `#` are skipped.
+    fn ignored(contents: &str) -> Vec<String> {
+        contents
+            .lines()
+            .map(|line| line.trim().trim_end_matches('/'))
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    /// Whether an entry is ignored.
+    /// A path with a `/` is relative to the root,
+    /// otherwise it matches any entry with that name.
+    fn is_ignored(&self, relative: &Path) -> bool {
+        self.ignored.iter().any(|ignored| {
+            if ignored.contains('/') {
+                relative == Path::new(ignored)
+            } else {
+                relative.file_name().map(|name| name == ignored.as_str()) == Some(true)
+            }
+        })
+    }
+
+    /// Discovers the module in a directory.
+    /// `path` is canonical, `relative` is the path from the root as written,
+    /// and `shown` is how the directory is shown in errors.
+    fn dir(&mut self, path: &Path, relative: &Path, shown: &Path) -> Result<Module, String> {
+        let entries = fs::read_dir(path).map_err(|_| {
+            format!(
+                "The path `{}` could not be read as a directory",
+                shown.display()
+            )
         })?;
 
-        // incrementally build up the struct
+        let mut names = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|_| {
+                format!(
+                    "Err while scanning the module located at `{}`",
+                    shown.display()
+                )
+            })?;
+            names.push(entry.file_name());
+        }
+        names.sort();
+
+        self.ancestors.push(path.to_owned());
         let entry_point_name = format!("{}.{}", ENTRY_POINT, EXTENSION);
         let mut entry: Option<Rc<Source>> = None;
-        let mut children = vec![];
-
-        for path in paths {
-            // get an actual entry
-            let path = path
-                .map_err(|_| {
-                    format!(
-                        "Err while scanning the module located at `{}`",
-                        entry_path.display()
-                    )
-                })?
-                .path();
-
-            // classify the entry
-            let is_source_file = path.extension().map(|x| x == EXTENSION).unwrap_or(false);
-            let is_entry_point = path.file_stem().map(|x| x == ENTRY_POINT).unwrap_or(false);
-
-            // grab the module at the given path
-            let module = if path.is_dir() {
-                if path.join(&entry_point_name).exists() {
-                    Module::new_from_dir(&path)?
-                } else {
+        let mut children: Vec<Module> = vec![];
+
+        for file_name in names {
+            let relative = relative.join(&file_name);
+            let shown = shown.join(&file_name);
+            if self.is_ignored(&relative) {
+                continue;
+            }
+
+            let logical = Path::new(&file_name);
+            let name = logical.file_stem().unwrap_or_default().to_string_lossy();
+            let is_source_file = logical.extension().map(|x| x == EXTENSION).unwrap_or(false);
+            let is_entry_point = is_source_file && name == ENTRY_POINT;
+
+            // follow symlinks
+            let resolved = match fs::canonicalize(path.join(&file_name)) {
+                Ok(resolved) => resolved,
+                Err(_) => {
+                    self.warn(&shown, "as it could not be resolved");
                     continue;
                 }
-            } else if path.is_file() && is_source_file {
-                let source = Source::path(&path)
-                    .map_err(|_| format!("Could not read source file `{}`", path.display()))?;
+            };
+
+            let module = if resolved.is_dir() {
+                if self.ancestors.contains(&resolved) {
+                    self.warn(&shown, "as it links to a directory that contains it");
+                    continue;
+                }
+                if !resolved.join(&entry_point_name).is_file() {
+                    let reason = format!("as it has no entry point (`{}`)", entry_point_name);
+                    self.warn(&shown, &reason);
+                    continue;
+                }
+                self.dir(&resolved, &relative, &shown)?
+            } else if resolved.is_file() && is_source_file {
+                let source = Source::path(&resolved)
+                    .map_err(|_| format!("Could not read source file `{}`", shown.display()))?;
 
                 if is_entry_point {
                     if let Some(other_path) = entry {
                         return Err(format!(
                             "Two potential entry points (`{}` and `{}`) for a single module",
                             other_path.path.display(),
-                            path.display(),
+                            resolved.display(),
                         ));
-                    } else {
-                        entry = Some(source);
                     }
+                    entry = Some(source);
                     continue;
-                } else {
-                    Module {
-                        source,
-                        children: Vec::new(),
-                    }
+                }
+
+                Module {
+                    name: name.to_string(),
+                    source,
+                    children: Vec::new(),
                 }
             } else {
                 continue;
             };
 
+            if children.iter().any(|child| child.name == module.name) {
+                let reason = format!("as there is already a module named `{}`", module.name);
+                self.warn(&shown, &reason);
+                continue;
+            }
+
             // append the module to the list of child modules
             children.push(module);
         }
+        self.ancestors.pop();
 
         let source = entry.ok_or_else(|| {
             format!(
                 "No entry point (e.g. `{}`) in the directory for the module `{}`",
                 entry_point_name,
-                entry_path.display()
+                shown.display()
             )
         })?;
 
-        Ok(Module { source, children })
+        let name = shown
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| ENTRY_POINT.to_string());
+        Ok(Module {
+            name,
+            source,
+            children,
+        })
+    }
+
+    fn warn(&mut self, shown: &Path, reason: &str) {
+        self.warnings
+            .push(format!("Skipped `{}`, {}", shown.display(), reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a fresh directory to discover modules in.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("passerine-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, path: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "()").unwrap();
+    }
+
+    fn names(module: &Module) -> Vec<&str> {
+        module.children().iter().map(|child| child.name()).collect()
+    }
+
+    #[test]
+    fn sorted() {
+        let dir = scratch("sorted");
+        for path in [
+            "main.pn",
+            "zeta.pn",
+            "alpha.pn",
+            "mid/main.pn",
+            "mid/inner.pn",
+        ] {
+            write(&dir, path);
+        }
+        fs::create_dir(dir.join("empty")).unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let (module, warnings) = Module::new_from_dir(&dir).unwrap();
+        assert_eq!(names(&module), vec!["alpha", "mid", "zeta"]);
+        assert_eq!(names(&module.children()[1]), vec!["inner"]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("empty"));
+
+        let canonical = fs::canonicalize(&dir).unwrap();
+        assert!(module.source().path.starts_with(canonical));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ignored() {
+        let dir = scratch("ignored");
+        for path in [
+            "main.pn",
+            "kept.pn",
+            "fixtures/main.pn",
+            "deep/main.pn",
+            "deep/skip.pn",
+        ] {
+            write(&dir, path);
+        }
+        fs::write(
+            dir.join(IGNORE_FILE),
+            "# test data\nfixtures/\n\ndeep/skip.pn\n",
+        )
+        .unwrap();
+
+        let (module, warnings) = Module::new_from_dir(&dir).unwrap();
+        assert_eq!(names(&module), vec!["deep", "kept"]);
+        assert!(module.children()[0].children().is_empty());
+        assert!(warnings.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = scratch("symlinks");
+        for path in ["main.pn", "shared.pn", "inner/main.pn"] {
+            write(&dir, path);
+        }
+        symlink(dir.join("shared.pn"), dir.join("alias.pn")).unwrap();
+        symlink(&dir, dir.join("inner").join("loop")).unwrap();
+        symlink(dir.join("missing"), dir.join("broken")).unwrap();
+
+        let (module, warnings) = Module::new_from_dir(&dir).unwrap();
+        assert_eq!(names(&module), vec!["alias", "inner", "shared"]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().any(|warning| warning.contains("loop")));
+        assert!(warnings.iter().any(|warning| warning.contains("broken")));
+        fs::remove_dir_all(dir).unwrap();
     }
 }
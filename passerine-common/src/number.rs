@@ -33,6 +33,22 @@ pub fn split_number(n: usize) -> Vec<u8> {
     return bytes;
 }
 
+/// Splits a number into a vector of exactly `width` bytes,
+/// padding the front of the chain with empty bytes.
+/// Because padded numbers always take up the same amount of space,
+/// they can be overwritten in place once the actual value is known.
+/// Panics if the number does not fit in the given width.
+pub fn split_number_padded(n: usize, width: usize) -> Vec<u8> {
+    let mut bytes = split_number(n);
+    if bytes.len() > width {
+        panic!("The number {} does not fit in {} bytes", n, width);
+    }
+
+    let mut padded = vec![0; width - bytes.len()];
+    padded.append(&mut bytes);
+    padded
+}
+
 /// This takes a stream of bytes, and builds the next number in it.
 /// Note that this function tries to build a number no matter what,
 /// even if the byte stream does not have a number, is empty, or ends after a
@@ -71,6 +87,14 @@ mod test {
         assert_eq!(build_number(&split_number(x)), (x, 9));
     }
 
+    #[test]
+    fn padded() {
+        let x = 300;
+        let padded = split_number_padded(x, 4);
+        assert_eq!(padded.len(), 4);
+        assert_eq!(build_number(&padded), (x, 4));
+    }
+
     #[test]
     fn rollover() {
         let x = 256;
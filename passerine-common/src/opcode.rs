@@ -1,6 +1,12 @@
 /// This enum represents a single opcode.
 /// Under the hood, it's just a byte.
 /// This allows non opcode bytes to be inserted in bytecode streams.
+///
+/// Opcodes are numbered by their position, so removing one renumbers
+/// every opcode after it. `FFICall` used to be `3`, and was removed
+/// in favor of host handlers, so bytecode produced before then,
+/// like that printed by `aspen run --bytecode`, no longer means the same thing
+/// and must be compiled again.
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq)]
 pub enum Opcode {
@@ -10,61 +16,114 @@ pub enum Opcode {
     NotInit = 1,
     /// Delete a value off the stack.
     Del = 2,
-    /// Calls out to a Rust function via FFI
-    FFICall = 3,
     /// Copies topmost value on the stack.
-    Copy = 4,
+    Copy = 3,
     /// Moves a variable onto the heap.
-    Capture = 5,
+    Capture = 4,
     /// Save a constant into a variable.
-    Save = 6,
+    Save = 5,
     /// Save a value to a captured variable.
-    SaveCap = 7,
+    SaveCap = 6,
     /// Push a copy of a variable onto the stack.
-    Load = 8,
+    Load = 7,
     /// Load a copy of a captured variable.
-    LoadCap = 9,
+    LoadCap = 8,
     /// Call a function.
-    Call = 10,
+    Call = 9,
     /// Return from a function.
-    Return = 11,
+    Return = 10,
     /// Creates a closure over the current local environment.
-    Closure = 12,
+    Closure = 11,
     /// Prints a value.
-    Print = 13,
-    ///
-    Handler = 14,
-    ///
-    Effect = 15,
+    Print = 12,
+    /// Calls a function with a handler installed for some effects.
+    Handler = 13,
+    /// Raises an effect, suspending the computation up to the nearest handler.
+    /// If nothing in Passerine handles it, a handler registered by the host
+    /// is called instead.
+    Effect = 14,
     /// Constructs a label.
-    Label = 16,
+    Label = 15,
     /// Constructs a tuple.
-    Tuple = 17,
-    ///
-    Record = 18,
+    Tuple = 16,
+    /// Constructs a record, given a constant tuple of the names of its fields.
+    Record = 17,
     /// Destructures atomic data by asserting it matches exactly.
-    UnData = 19,
+    UnData = 18,
     // TODO: make unlabel take the label index as an arg.
     /// Destructures a label.
-    UnLabel = 20,
+    UnLabel = 19,
     /// Destructures a tuple.
-    UnTuple = 21,
+    UnTuple = 20,
     /// Add two numbers on the stack.
-    Add = 22,
+    Add = 21,
     /// Subtract two numbers on the stack.
-    Sub = 23,
+    Sub = 22,
     /// Negate a number.
-    Neg = 24,
+    Neg = 23,
     /// Multiple two numbers on the stack.
-    Mul = 25,
-    /// Divide two numbers, raising ZeroDiv side effect
-    Div = 26,
-    /// Take the remainder of two numbers, raising ZeroDiv side effect
-    Rem = 27,
+    Mul = 24,
+    /// Divide two numbers, raising the `ZeroDiv` effect
+    /// with the dividend if the divisor is zero and it is handled.
+    Div = 25,
+    /// Take the remainder of two numbers, raising the `ZeroDiv` effect
+    /// with the dividend if the divisor is zero and it is handled.
+    Rem = 26,
     /// Take a number to a power.
-    Pow = 28,
+    Pow = 27,
+    /// Jumps unconditionally to a position in the current lambda's bytecode.
+    Jump = 28,
+    /// Pops a boolean, jumping to a position in the bytecode if it is false.
+    JumpFalse = 29,
+    /// Replaces a tuple or list with one of its items.
+    /// Raises an indexing error if the index is out of bounds.
+    Index = 30,
+    /// Pops two values, pushing whether they are equal.
+    Equal = 31,
+    /// Pops a kind and some data, pushing whether the data is a label of that kind.
+    IsLabel = 32,
+    /// Pops some data, pushing whether it is a tuple of a specific length.
+    IsTuple = 33,
+    /// Raises an error because no arm of a match matched some data.
+    NoMatch = 34,
+    /// Replaces a record with the value of one of its fields.
+    Field = 35,
+    /// Copies a record, replacing the values of some of its fields.
+    Update = 36,
+    /// Destructures a record, pushing the value of one of its fields.
+    UnRecord = 37,
+    /// Pops some data, pushing whether it is a record with specific fields.
+    IsRecord = 38,
+    /// Constructs a list.
+    List = 39,
+    /// Adds some items to the front of a list.
+    Spread = 40,
+    /// Destructures a list by asserting it has a specific length,
+    /// or at least that length if the rest of the list is also matched.
+    UnList = 41,
+    /// Replaces a list with the items that come after the first few.
+    Rest = 42,
+    /// Pops some data, pushing whether it is a list of a specific length,
+    /// or at least that length.
+    IsList = 43,
+    /// Constructs a map from some pairs of keys and values.
+    Map = 44,
+    /// Pops two values, pushing whether they are not equal.
+    NotEqual = 45,
+    /// Pops two numbers or strings, pushing whether the first is less.
+    Less = 46,
+    /// Pops two numbers or strings, pushing whether the first is greater.
+    Greater = 47,
+    /// Pops two numbers or strings,
+    /// pushing whether the first is less than or equal to the second.
+    LessEqual = 48,
+    /// Pops two numbers or strings,
+    /// pushing whether the first is greater than or equal to the second.
+    GreaterEqual = 49,
+    /// Pops a boolean, pushing its negation.
+    Not = 50,
     /// Does nothing. Must always be last.
-    Noop = 29,
+    Noop = 51,
 }
 
 impl Opcode {
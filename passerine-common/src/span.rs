@@ -17,6 +17,15 @@ pub struct Span {
     source: Rc<Source>,
     offset: usize,
     length: usize,
+    /// Set if the code was produced by expanding a macro.
+    expansion: Option<Rc<Expansion>>,
+}
+
+/// Where a macro that produced some code was used and defined.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Expansion {
+    pub call: Span,
+    pub definition: Span,
 }
 
 impl Span {
@@ -29,6 +38,7 @@ impl Span {
             source: Rc::clone(source),
             offset,
             length,
+            expansion: None,
         }
     }
 
@@ -39,9 +49,34 @@ impl Span {
             source: Rc::clone(source),
             offset,
             length: 0,
+            expansion: None,
+        }
+    }
+
+    /// A `Span` for code produced by expanding a macro.
+    /// It covers the use of the macro,
+    /// and also remembers where the macro was defined.
+    pub fn expanded(call: &Span, definition: &Span) -> Span {
+        Span {
+            expansion: Some(Rc::new(Expansion {
+                call: call.clone(),
+                definition: definition.clone(),
+            })),
+            ..call.clone()
         }
     }
 
+    /// Where the macro that produced this code was used and defined,
+    /// if this code was produced by a macro.
+    pub fn expansion(&self) -> Option<&Expansion> {
+        self.expansion.as_deref()
+    }
+
+    /// The source this `Span` is a section of.
+    pub fn source(&self) -> &Rc<Source> {
+        &self.source
+    }
+
     /// Return the index of the end of the `Span`.
     pub fn end(&self) -> usize {
         self.offset + self.length
@@ -68,7 +103,12 @@ impl Span {
         let end = a.end().max(b.end());
         let length = end - offset;
 
-        return Span::new(&a.source, offset, length);
+        // code is only part of an expansion if both sides are
+        let mut combined = Span::new(&a.source, offset, length);
+        if a.expansion == b.expansion {
+            combined.expansion = a.expansion.clone();
+        }
+        return combined;
     }
 
     /// Combines a set of `Span`s (think fold-left over
@@ -132,6 +172,18 @@ impl Span {
             lines: self.lines(),
             start_col: self.col(self.offset),
             end_col: self.col(self.end()),
+            expansion: self.expansion().map(|expansion| {
+                let (call, definition) = (&expansion.call, &expansion.definition);
+                format!(
+                    "expanded from the macro used at {}:{}:{}, which is defined at {}:{}:{}",
+                    call.path(),
+                    call.line(call.offset) + 1,
+                    call.col(call.offset) + 1,
+                    definition.path(),
+                    definition.line(definition.offset) + 1,
+                    definition.col(definition.offset) + 1,
+                )
+            }),
         }
     }
 }
@@ -178,6 +230,8 @@ pub struct FormattedSpan {
     pub lines: Vec<String>,
     pub start_col: usize,
     pub end_col: usize,
+    /// Describes the macro this span was expanded from, if any.
+    pub expansion: Option<String>,
 }
 
 impl FormattedSpan {
@@ -185,12 +239,14 @@ impl FormattedSpan {
         self.lines.len() != 1
     }
 
+    /// The index of the last line of the span.
     pub fn end(&self) -> usize {
-        (self.start - 1) + self.lines.len()
+        self.start + self.lines.len() - 1
     }
 
+    /// The width of the widest line number shown.
     pub fn gutter_padding(&self) -> usize {
-        self.start.add(1).to_string().len()
+        self.end().add(1).to_string().len()
     }
 
     /// If a single line span, returns the number of carrots
@@ -226,12 +282,21 @@ impl Display for FormattedSpan {
             )?;
         } else {
             for (index, line) in self.lines.iter().enumerate() {
-                let line_no = (self.start + index).to_string();
+                let line_no = (self.start + index + 1).to_string();
                 let padding = " ".repeat(self.gutter_padding() - line_no.len());
                 writeln!(f, "{}{} > {}", line_no, padding, line)?;
             }
         }
 
+        if let Some(expansion) = &self.expansion {
+            writeln!(
+                f,
+                "{} = note: {}",
+                " ".repeat(self.gutter_padding()),
+                expansion
+            )?;
+        }
+
         Ok(())
     }
 }
@@ -319,6 +384,32 @@ mod test {
         assert_eq!(Span::join(spans).unwrap().contents(), result.contents());
     }
 
+    #[test]
+    fn expansion() {
+        let source = Source::source("macro m = x -> x\nm 1");
+        let definition = Span::new(&source, 0, 16);
+        let call = Span::new(&source, 17, 3);
+        let expanded = Span::expanded(&call, &definition);
+
+        assert_eq!(expanded.contents(), "m 1");
+        assert_eq!(expanded.expansion().unwrap().definition, definition);
+        assert!(format!("{}", expanded).contains("defined at ./source:1:1"));
+        // expansion is only kept when both spans share it
+        assert!(Span::combine(&expanded, &call).expansion().is_none());
+    }
+
+    #[test]
+    fn multiline() {
+        let source = Source::source(&"x\n".repeat(12));
+        let span = Span::new(&source, 14, 6);
+        let formatted = format!("{}", span);
+
+        assert_eq!(span.format().gutter_padding(), 2);
+        assert!(formatted.contains("\n8  > x\n"));
+        assert!(formatted.contains("10 > x\n"));
+        assert!(!formatted.contains("7  > "));
+    }
+
     #[test]
     fn empty() {
         let source = Source::source("");
@@ -1,17 +1,23 @@
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Identifies a type stored in a `TyPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TySymbol(usize);
 
 /// Built-in Passerine datatypes.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Ty {
+    /// Any type at all, checked at runtime rather than at compile time.
+    /// Compatible with every other type.
+    Any,
+
     // Passerine Data (Atomic)
     Natural,
     Integer,
     Float,
-    // Boolean, // TODO: should be just standard library enum?
+    Boolean, // TODO: should be just standard library enum?
     String,
+    Unit,
 
     // Function
     // <arg> -> <body> / <ty>
@@ -29,11 +35,222 @@ pub enum Ty {
     // Compound
     Tuple(Vec<TySymbol>),
     List(TySymbol),
-    // TODO: hashmap?
-    Record(Vec<TySymbol>), // TODO: names for records and enums
-    Enum(Vec<TySymbol>),
+    /// A map with keys of one type and values of another.
+    Map {
+        key: TySymbol,
+        value: TySymbol,
+    },
+    /// The type of each field, sorted by name.
+    Record(Vec<(String, TySymbol)>),
+    Enum(Vec<TySymbol>), // TODO: names for enums
+}
+
+impl Ty {
+    /// Returns the symbols of the types this type is built from.
+    pub fn children(&self) -> Vec<TySymbol> {
+        match self {
+            Ty::Function { arg, body, effect } => vec![*arg, *body, *effect],
+            Ty::Fiber { takes, yields } => vec![*takes, *yields],
+            Ty::Tuple(items) | Ty::Enum(items) => items.clone(),
+            Ty::Record(fields) => fields.iter().map(|(_, field)| *field).collect(),
+            Ty::List(item) => vec![*item],
+            Ty::Map { key, value } => vec![*key, *value],
+            _ => vec![],
+        }
+    }
 }
 
+/// Stores types, which refer to one another through `TySymbol`s.
+/// A symbol that does not stand for a type is a type variable,
+/// i.e. a type that is not known yet.
+/// Type variables are resolved by linking them to other types.
+#[derive(Debug, Clone, Default)]
 pub struct TyPool {
     tys: HashMap<TySymbol, Ty>,
+    /// Type variables that have been resolved,
+    /// mapped to the type they stand for.
+    links: HashMap<TySymbol, TySymbol>,
+    next: usize,
+}
+
+impl TyPool {
+    pub fn new() -> TyPool {
+        TyPool::default()
+    }
+
+    /// Creates a new type variable.
+    pub fn var(&mut self) -> TySymbol {
+        self.next += 1;
+        TySymbol(self.next - 1)
+    }
+
+    /// Adds a type to the pool, returning its symbol.
+    pub fn insert(&mut self, ty: Ty) -> TySymbol {
+        let symbol = self.var();
+        self.tys.insert(symbol, ty);
+        symbol
+    }
+
+    /// Follows links to find the symbol that
+    /// represents the same type as a given symbol.
+    pub fn find(&self, mut symbol: TySymbol) -> TySymbol {
+        while let Some(linked) = self.links.get(&symbol) {
+            symbol = *linked;
+        }
+        symbol
+    }
+
+    /// Returns the type a symbol stands for,
+    /// or `None` if the symbol is an unresolved type variable.
+    pub fn get(&self, symbol: TySymbol) -> Option<&Ty> {
+        self.tys.get(&self.find(symbol))
+    }
+
+    /// Resolves a type variable to the type of another symbol.
+    pub fn link(&mut self, var: TySymbol, symbol: TySymbol) {
+        let var = self.find(var);
+        assert!(
+            !self.tys.contains_key(&var),
+            "Only type variables can be linked"
+        );
+        self.links.insert(var, symbol);
+    }
+
+    /// Collects the unresolved type variables a type contains,
+    /// in the order they first appear.
+    pub fn vars(&self, symbol: TySymbol, vars: &mut Vec<TySymbol>) {
+        let symbol = self.find(symbol);
+        match self.get(symbol) {
+            Some(ty) => {
+                for child in ty.children() {
+                    self.vars(child, vars);
+                }
+            }
+            None if !vars.contains(&symbol) => vars.push(symbol),
+            None => (),
+        }
+    }
+
+    /// Displays a type, naming its type variables `a`, `b`, and so on.
+    pub fn display(&self, symbol: TySymbol) -> String {
+        let mut vars = vec![];
+        self.shown(symbol, &mut vars);
+        self.name(symbol, &vars, false)
+    }
+
+    /// Like `vars`, but leaves out effects, which are not displayed.
+    fn shown(&self, symbol: TySymbol, vars: &mut Vec<TySymbol>) {
+        let symbol = self.find(symbol);
+        match self.get(symbol) {
+            Some(Ty::Function { arg, body, .. }) => {
+                self.shown(*arg, vars);
+                self.shown(*body, vars);
+            }
+            Some(ty) => {
+                for child in ty.children() {
+                    self.shown(child, vars);
+                }
+            }
+            None if !vars.contains(&symbol) => vars.push(symbol),
+            None => (),
+        }
+    }
+
+    fn name(&self, symbol: TySymbol, vars: &[TySymbol], nested: bool) -> String {
+        let symbol = self.find(symbol);
+        let ty = match self.get(symbol) {
+            Some(ty) => ty,
+            None => {
+                let index = vars.iter().position(|var| *var == symbol).unwrap();
+                let letter = ((b'a' + (index % 26) as u8) as char).to_string();
+                return match index / 26 {
+                    0 => letter,
+                    n => format!("{}{}", letter, n),
+                };
+            }
+        };
+
+        let join = |items: &[TySymbol], separator| {
+            items
+                .iter()
+                .map(|item| self.name(*item, vars, false))
+                .collect::<Vec<String>>()
+                .join(separator)
+        };
+
+        match ty {
+            Ty::Any => "Any".to_string(),
+            Ty::Natural => "Natural".to_string(),
+            Ty::Integer => "Integer".to_string(),
+            Ty::Float => "Float".to_string(),
+            Ty::Boolean => "Boolean".to_string(),
+            Ty::String => "String".to_string(),
+            Ty::Unit => "()".to_string(),
+            Ty::Function { arg, body, .. } => {
+                let function = format!(
+                    "{} -> {}",
+                    self.name(*arg, vars, true),
+                    self.name(*body, vars, false)
+                );
+                if nested {
+                    format!("({})", function)
+                } else {
+                    function
+                }
+            }
+            Ty::Fiber { takes, yields } => format!("Fiber {}", join(&[*takes, *yields], " ")),
+            Ty::Tuple(items) if items.len() == 1 => format!("({},)", join(items, "")),
+            Ty::Tuple(items) => format!("({})", join(items, ", ")),
+            Ty::List(item) => format!("[{}]", self.name(*item, vars, false)),
+            Ty::Map { key, value } => format!(
+                "[{} => {}]",
+                self.name(*key, vars, false),
+                self.name(*value, vars, false)
+            ),
+            Ty::Record(fields) => format!(
+                "{{ {} }}",
+                fields
+                    .iter()
+                    .map(|(name, field)| format!("{}: {}", name, self.name(*field, vars, false)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Ty::Enum(items) => join(items, " | "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links() {
+        let mut pool = TyPool::new();
+        let var = pool.var();
+        let integer = pool.insert(Ty::Integer);
+        assert_eq!(pool.get(var), None);
+
+        pool.link(var, integer);
+        assert_eq!(pool.find(var), integer);
+        assert_eq!(pool.get(var), Some(&Ty::Integer));
+    }
+
+    #[test]
+    fn display() {
+        let mut pool = TyPool::new();
+        let (a, b, effect) = (pool.var(), pool.var(), pool.var());
+        let inner = pool.insert(Ty::Function {
+            arg: a,
+            body: b,
+            effect,
+        });
+        let tuple = pool.insert(Ty::Tuple(vec![b, a]));
+        let outer = pool.insert(Ty::Function {
+            arg: inner,
+            body: tuple,
+            effect,
+        });
+        assert_eq!(pool.display(outer), "(a -> b) -> (b, a)");
+    }
 }
@@ -6,11 +6,17 @@ use syn::{parse_macro_input, spanned::Spanned, DeriveInput, Ident, Index};
 /// which allows a Rust type to be converted to Passerine data and back again.
 /// This type is very important for building interfaces between Rust and
 /// Passerine using system injection.
+///
+/// Also implements the `Effect` trait, so that the type can be used as the
+/// payload of the effect with the same name.
+/// A struct with a single field is represented by the data in that field,
+/// and a struct with more than one field by a tuple.
 #[proc_macro_derive(Effect)]
 pub fn derive_effect(input: TokenStream) -> TokenStream {
     // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
     let type_name = input.ident;
+    let effect_name = type_name.to_string();
 
     let (from, into) = match input.data {
         syn::Data::Struct(ref data) => match data.fields {
@@ -41,6 +47,10 @@ pub fn derive_effect(input: TokenStream) -> TokenStream {
             fn deserialize(param: passerine_common::Data) -> Option<Self> { #from }
         }
 
+        impl passerine_common::effect::Effect for #type_name {
+            const NAME: &'static str = #effect_name;
+        }
+
         // // Data -> Item conversion
         // impl TryFrom<passerine_common::Data> for #type_name {
         //     type Error = ();
@@ -66,6 +76,15 @@ fn derive_struct_named(
     fields: &syn::FieldsNamed,
 ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
     let num_fields = fields.named.len();
+    if num_fields == 1 {
+        let name = &fields.named[0].ident;
+        let from = quote! {
+            Some(#type_name { #name: passerine_common::Inject::deserialize(param)? })
+        };
+        let into = quote! { passerine_common::Inject::serialize(param.#name) };
+        return (from, into);
+    }
+
     let from = fields.named.iter().rev().map(|f| {
         let name = &f.ident;
         quote_spanned! { f.span() =>
@@ -99,9 +118,18 @@ fn derive_struct_unnamed(
     fields: &syn::FieldsUnnamed,
 ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
     let num_fields = fields.unnamed.len();
-    let from = fields.unnamed.iter().rev().map(|f| {
+    if num_fields == 1 {
+        let from = quote! {
+            Some(#type_name(passerine_common::Inject::deserialize(param)?))
+        };
+        let into = quote! { passerine_common::Inject::serialize(param.0) };
+        return (from, into);
+    }
+
+    // fields are positional, so they are taken in order
+    let from = fields.unnamed.iter().map(|f| {
         quote_spanned! { f.span() =>
-            passerine_common::Inject::deserialize(param.pop()?)?
+            passerine_common::Inject::deserialize(param.next()?)?
         }
     });
     let into = fields.unnamed.iter().enumerate().map(|(index, f)| {
@@ -112,8 +140,9 @@ fn derive_struct_unnamed(
     });
 
     let from = quote! {
-        if let passerine_common::Data::Tuple(mut param) = param {
+        if let passerine_common::Data::Tuple(param) = param {
             if param.len() != #num_fields { return None; }
+            let mut param = param.into_iter();
             Some(#type_name (#(#from,)*))
         } else {
             None
@@ -1,8 +1,9 @@
-use std::{mem, rc::Rc};
+use std::{collections::HashSet, mem, rc::Rc};
 
 // TODO: hoist and resolve types
 use crate::{
     common::{
+        label::{Labels, ROOT},
         lambda::{Captured, Lambda},
         lit::Lit,
         number::split_number,
@@ -10,12 +11,17 @@ use crate::{
         span::{Span, Spanned},
         Data,
     },
-    compiler::syntax::Syntax,
+    compiler::{
+        decision::{Check, Decision, Step},
+        link::Paths,
+        syntax::Syntax,
+    },
     construct::{
         scope::Scope,
         symbol::UniqueSymbol,
-        tree::{Base, Pattern, ScopedLambda, SST},
+        tree::{Base, BinOp, Fields, Pattern, ScopedLambda, UnOp, SST},
     },
+    explain::Code,
 };
 
 /// Compiler is a bytecode generator that walks an SST and produces
@@ -29,55 +35,84 @@ pub struct Compiler {
     lambda: Lambda,
     /// Names of symbols,
     // symbol_table: Vec<String>,
-    /// The foreign functional interface used to bind values
-    // ffi:       FFI,
-    /// The FFI functions that have been bound in this scope.
-    // ffi_names: Vec<String>,
     // determined in hoisting
     scope: Scope,
+    /// The registry labels are interned in.
+    /// Shared between all nested compilers.
+    labels: Labels,
+    /// The module each part of the program is in,
+    /// which labels are interned under.
+    paths: Paths,
+    /// The names of the effects declared in the program.
+    effects: Rc<HashSet<String>>,
 }
 
 impl Compiler {
     pub fn compile(tree: Spanned<SST>, scope: Scope) -> Result<Rc<Lambda>, Syntax> {
-        dbg!(&tree);
-        // let ffi = ffi_core();
-        let mut compiler = Compiler::base(scope);
+        return Compiler::compile_entry(tree, scope, &Labels::new());
+    }
+
+    /// Compiles one entry of a `Session`.
+    /// Labels are interned in the registry that earlier entries used,
+    /// so that the same label always has the same kind.
+    pub fn compile_entry(
+        tree: Spanned<SST>,
+        scope: Scope,
+        labels: &Labels,
+    ) -> Result<Rc<Lambda>, Syntax> {
+        let mut compiler = Compiler::base(scope, labels.clone());
+        compiler.walk(&tree)?;
+        return Ok(Rc::new(compiler.lambda));
+    }
+
+    /// Compiles a program produced by the `Linker`,
+    /// interning each label under the module it is written in
+    /// in a new registry, which the returned lambda carries.
+    pub fn compile_linked(
+        tree: Spanned<SST>,
+        scope: Scope,
+        paths: &Paths,
+        effects: HashSet<String>,
+    ) -> Result<Rc<Lambda>, Syntax> {
+        let mut compiler = Compiler::base(scope, Labels::new());
+        compiler.paths = paths.clone();
+        compiler.effects = Rc::new(effects);
         compiler.walk(&tree)?;
         return Ok(Rc::new(compiler.lambda));
     }
 
-    /// Construct a new `Compiler`.
-    fn base(scope: Scope) -> Compiler {
+    /// Construct a new `Compiler` that interns labels in a registry.
+    fn base(scope: Scope, labels: Labels) -> Compiler {
+        let mut lambda = Lambda::empty();
+        lambda.labels = labels.clone();
         Compiler {
             enclosing: None,
-            lambda: Lambda::empty(),
-            // ffi,
-            // ffi_names: vec![],
+            lambda,
             scope,
+            labels,
+            paths: Paths::default(),
+            effects: Rc::new(HashSet::new()),
         }
     }
 
     /// Replace the current compiler with a fresh one,
-    /// keeping a reference to the old one in `self.enclosing`,
-    /// and moving the FFI into the current compiler.
+    /// keeping a reference to the old one in `self.enclosing`.
     fn enter_scope(&mut self, scope: Scope) {
-        // let ffi = mem::replace(&mut self.ffi, FFI::new());
-        let nested = Compiler::base(scope);
+        let mut nested = Compiler::base(scope, self.labels.clone());
+        nested.paths = self.paths.clone();
+        nested.effects = self.effects.clone();
         let enclosing = mem::replace(self, nested);
         self.enclosing = Some(Box::new(enclosing));
     }
 
     /// Restore the enclosing compiler,
-    /// returning the nested one for data (Lambda) extraction,
-    /// and moving the FFI mappings back into the enclosing compiler.
+    /// returning the nested one for data (Lambda) extraction.
     fn exit_scope(&mut self) -> Compiler {
-        // let ffi = mem::replace(&mut self.ffi, FFI::new());
         let enclosing = mem::replace(&mut self.enclosing, None);
         let nested = match enclosing {
             Some(compiler) => mem::replace(self, *compiler),
             None => unreachable!("Can not go back past root copiler"),
         };
-        // self.ffi = ffi;
         return nested;
     }
 
@@ -98,16 +133,37 @@ impl Compiler {
             SST::Base(Base::Lit(lit)) => Ok(self.lit(lit)),
             SST::Base(Base::Symbol(unique)) => Ok(self.symbol(unique)),
             SST::Base(Base::Block(block)) => self.block(block),
-            // SST::Base(Base::Label(name, expression)) => {
-            //     self.label(name, *expression)
-            // },
-            SST::Base(Base::Label(_)) => todo!(),
+            SST::Base(Base::Label(name)) => Err(Syntax::error(
+                Code::EmptyLabel,
+                &format!(
+                    "This Label does not wrap any data.\n\
+                    To wrap nothing, use `{} ()`",
+                    name,
+                ),
+                &sst.span,
+            )),
             SST::Base(Base::Tuple(tuple)) => self.tuple(tuple),
+            SST::Base(Base::Record(fields)) => self.record(fields),
+            SST::Base(Base::List(items, rest)) => self.list(items, rest.map(|rest| *rest)),
+            SST::Base(Base::Map(entries)) => self.map(entries),
+            SST::Base(Base::Field(record, name)) => self.field(*record, name),
+            SST::Base(Base::Update(record, fields)) => self.update(*record, fields),
             SST::Base(Base::Assign(pattern, expression)) => self.assign(pattern, *expression),
-            SST::ScopedLambda(ScopedLambda { arg, body, scope }) => self.lambda(arg, *body, scope),
+            SST::ScopedLambda(ScopedLambda { arg, body, scope }) => {
+                self.lambda(arg, *body, scope, false)
+            }
             SST::Base(Base::Call(fun, arg)) => self.call(*fun, *arg),
-            SST::Base(Base::Module(_)) => todo!("need to handle modules"),
-            SST::Base(Base::Effect(_)) => todo!("need to handle effects"),
+            SST::Base(Base::BinOp(op, left, right)) => self.bin_op(op, *left, *right),
+            SST::Base(Base::UnOp(op, operand)) => self.un_op(op, *operand),
+            SST::Base(Base::If(cond, then, else_)) => self.if_(*cond, *then, *else_),
+            SST::Base(Base::Match(value, arms)) => self.match_(*value, arms, sst.span.clone()),
+            SST::Base(Base::Module(module)) => self.module(*module),
+            SST::Base(Base::Effect(name, payload)) => self.effect(&name.item, *payload, &sst.span),
+            SST::Base(Base::Handle(body, handler, effects)) => {
+                self.handle(*body, *handler, effects, &sst.span)
+            }
+            // types are checked before the program is run
+            SST::Base(Base::Is(expression, _)) => self.walk(&expression),
         };
     }
 
@@ -157,22 +213,34 @@ impl Compiler {
 
     /// Generates a print expression
     /// Note that currently printing is a baked-in language feature,
-    /// but the second the FFI becomes a thing
-    /// it'll no longer be one.
+    /// but it should become an effect handled by the host.
     fn print(&mut self, expression: Spanned<SST>) -> Result<(), Syntax> {
         self.walk(&expression)?;
         self.lambda.emit(Opcode::Print);
         Ok(())
     }
 
+    /// Loads the kind that represents a label with a given name,
+    /// as written in the module of the code at a span.
+    /// Effects are shared by all modules, so they belong to the root.
+    fn kind(&mut self, name: &str, span: &Span) {
+        let module = match self.effects.contains(name) {
+            true => ROOT,
+            false => self.paths.module(span),
+        };
+        let kind = self.labels.intern(module, name);
+        self.lambda.emit(Opcode::Con);
+        let mut split = split_number(self.lambda.index_data(Data::Kind(kind)));
+        self.lambda.emit_bytes(&mut split);
+    }
+
     /// Generates a Label construction
     /// that loads the variant, then wraps some data
-    fn label(&mut self, name: UniqueSymbol, expression: Spanned<SST>) -> Result<(), Syntax> {
-        todo!()
-        // self.walk(&expression)?;
-        // self.lit(Lit::Kind(name.0));
-        // self.lambda.emit(Opcode::Label);
-        // Ok(())
+    fn label(&mut self, name: &str, expression: Spanned<SST>, span: &Span) -> Result<(), Syntax> {
+        self.walk(&expression)?;
+        self.kind(name, span);
+        self.lambda.emit(Opcode::Label);
+        Ok(())
     }
 
     /// Generates a Tuple construction
@@ -190,45 +258,87 @@ impl Compiler {
         Ok(())
     }
 
-    // TODO: remove FFI!
-
-    // // TODO: make a macro to map Passerine's data model to Rust's
-    // /// Makes a Rust function callable from Passerine,
-    // /// by keeping a reference to that function.
-    // fn ffi(
-    //     &mut self,
-    //     name: String,
-    //     expression: Spanned<SST>,
-    //     span: Span,
-    // ) -> Result<(), Syntax> {
-    //     self.walk(&expression)?;
-
-    //     let function =
-    //         self.ffi.get(&name).map_err(|s| Syntax::error(&s, &span))?;
-
-    //     let index = match self.ffi_names.iter().position(|n| n == &name) {
-    //         Some(p) => p,
-    //         None => {
-    //             // TODO: switch ffi to symbol, just use unique symbol?
-    //             // TODO: keeping track of state
-    //             // in two different places is a code smell imo
-    //             // Reason: don't want to include strings in lambda
-    //             // optimal solutions:
-    //             // have an earlier step that normalizes AST,
-    //             // determines scope of all names/symbols,
-    //             // and replaces all names/symbols with indicies
-    //             // before codgen.
-    //             self.ffi_names.push(name);
-    //             todo!("add FFI function")
-    //             // self.lambda.add_ffi(function)
-    //         },
-    //     };
-
-    //     self.lambda.emit_span(&span);
-    //     self.lambda.emit(Opcode::FFICall);
-    //     self.lambda.emit_bytes(&mut split_number(index));
-    //     Ok(())
-    // }
+    /// Loads each item in a list, in order, then builds a list out of them.
+    /// If another list is spread at the end,
+    /// the items are added to the front of it instead.
+    fn list(&mut self, items: Vec<Spanned<SST>>, rest: Option<Spanned<SST>>) -> Result<(), Syntax> {
+        let length = items.len();
+
+        for item in items.into_iter() {
+            self.walk(&item)?;
+        }
+
+        match rest {
+            Some(rest) => {
+                self.walk(&rest)?;
+                self.lambda.emit(Opcode::Spread);
+            }
+            None => self.lambda.emit(Opcode::List),
+        }
+        self.lambda.emit_bytes(&mut split_number(length));
+        Ok(())
+    }
+
+    /// Loads each key in a map followed by its value, in order,
+    /// then builds a map out of them.
+    fn map(&mut self, entries: Vec<(Spanned<SST>, Spanned<SST>)>) -> Result<(), Syntax> {
+        let length = entries.len();
+
+        for (key, value) in entries.into_iter() {
+            self.walk(&key)?;
+            self.walk(&value)?;
+        }
+
+        self.lambda.emit(Opcode::Map);
+        self.lambda.emit_bytes(&mut split_number(length));
+        Ok(())
+    }
+
+    /// Loads the value of each field, in order,
+    /// then builds a record out of them.
+    fn record(&mut self, fields: Fields<Spanned<SST>>) -> Result<(), Syntax> {
+        let names = self.field_names(&fields);
+        for (_, value) in fields.into_iter() {
+            self.walk(&value)?;
+        }
+
+        self.lambda.emit(Opcode::Record);
+        self.lambda.emit_bytes(&mut split_number(names));
+        Ok(())
+    }
+
+    /// Generates a field access, like `point.x`.
+    fn field(&mut self, record: Spanned<SST>, name: Spanned<String>) -> Result<(), Syntax> {
+        self.walk(&record)?;
+        self.lambda.emit_span(&name.span);
+        self.lambda.emit(Opcode::Field);
+        let index = self.lambda.index_data(Data::String(name.item));
+        self.lambda.emit_bytes(&mut split_number(index));
+        Ok(())
+    }
+
+    /// Loads a record, then the new value of each field being updated.
+    fn update(&mut self, record: Spanned<SST>, fields: Fields<Spanned<SST>>) -> Result<(), Syntax> {
+        self.walk(&record)?;
+        let names = self.field_names(&fields);
+        for (_, value) in fields.into_iter() {
+            self.walk(&value)?;
+        }
+
+        self.lambda.emit(Opcode::Update);
+        self.lambda.emit_bytes(&mut split_number(names));
+        Ok(())
+    }
+
+    /// Stores the names of some fields as a constant tuple,
+    /// returning the index of the constant.
+    fn field_names<T>(&mut self, fields: &[(Spanned<String>, T)]) -> usize {
+        let names = fields
+            .iter()
+            .map(|(name, _)| Data::String(name.item.clone()))
+            .collect();
+        self.lambda.index_data(Data::Tuple(names))
+    }
 
     /// Resolves the assignment of a variable
     /// returns true if the variable was declared.
@@ -263,10 +373,9 @@ impl Compiler {
                 self.lambda.emit(Opcode::UnData);
             }
             Pattern::Label(name, pattern) => {
-                todo!()
-                // self.lit(Lit::Kind(name.0));
-                // self.lambda.emit(Opcode::UnLabel);
-                // self.destructure(*pattern, redeclare);
+                self.kind(&name.item, &name.span);
+                self.lambda.emit(Opcode::UnLabel);
+                self.destructure(*pattern, redeclare);
             }
             Pattern::Tuple(tuple) => {
                 for (index, sub_pattern) in tuple.into_iter().enumerate() {
@@ -277,7 +386,43 @@ impl Compiler {
                 // Delete the tuple moved to the top of the stack.
                 self.lambda.emit(Opcode::Del);
             }
+            Pattern::Record(fields) => {
+                for (name, sub_pattern) in fields.into_iter() {
+                    self.lambda.emit(Opcode::UnRecord);
+                    let index = self.lambda.index_data(Data::String(name.item));
+                    self.lambda.emit_bytes(&mut split_number(index));
+                    self.destructure(sub_pattern, redeclare);
+                }
+                // Delete the record moved to the top of the stack.
+                self.lambda.emit(Opcode::Del);
+            }
+            Pattern::List(items, rest) => {
+                let length = items.len();
+                self.lambda.emit(Opcode::UnList);
+                self.lambda.emit_bytes(&mut split_number(length));
+                self.lambda
+                    .emit_bytes(&mut split_number(rest.is_some() as usize));
+
+                for (index, sub_pattern) in items.into_iter().enumerate() {
+                    self.lambda.emit(Opcode::Copy);
+                    self.lambda.emit(Opcode::Index);
+                    self.lambda.emit_bytes(&mut split_number(index));
+                    self.destructure(sub_pattern, redeclare);
+                }
+
+                match rest {
+                    Some(rest) => {
+                        self.lambda.emit(Opcode::Rest);
+                        self.lambda.emit_bytes(&mut split_number(length));
+                        self.destructure(*rest, redeclare);
+                    }
+                    // Delete the list moved to the top of the stack.
+                    None => self.lambda.emit(Opcode::Del),
+                }
+            }
             Pattern::Chain(_) => todo!("handle pattern chains"),
+            // types are checked before the program is run
+            Pattern::Is(pattern, _) => self.destructure(*pattern, redeclare),
         }
     }
 
@@ -295,11 +440,14 @@ impl Compiler {
     }
 
     /// Recursively compiles a lambda declaration in a new scope.
+    /// If `keep_frame` is set, a call at the end of the body is not a tail call,
+    /// so the frame of the function stays around for tracebacks.
     fn lambda(
         &mut self,
         pattern: Spanned<Pattern<UniqueSymbol>>,
         expression: Spanned<SST>,
         scope: Scope,
+        keep_frame: bool,
     ) -> Result<(), Syntax> {
         // build a list of captures at the boundary
         let mut captures = vec![];
@@ -328,6 +476,9 @@ impl Compiler {
             self.walk(&expression)?;
 
             // return the result
+            if keep_frame {
+                self.lambda.emit(Opcode::Noop);
+            }
             self.lambda.emit(Opcode::Return);
             self.lambda
                 .emit_bytes(&mut split_number(self.scope.locals.len()));
@@ -346,13 +497,381 @@ impl Compiler {
     /// When a function is called, the top two items are taken off the stack,
     /// The topmost item is expected to be a function.
     fn call(&mut self, fun: Spanned<SST>, arg: Spanned<SST>) -> Result<(), Syntax> {
+        // applying a label wraps the argument, as in `Some x`
+        if let SST::Base(Base::Label(name)) = &fun.item {
+            return self.label(name, arg, &fun.span);
+        }
+
         self.walk(&arg)?;
-        self.walk(&fun)?;
+        let span = Span::combine(&fun.span, &arg.span);
+        let fun_span = fun.span.clone();
+
+        // a function of `()` that is called right away runs once, like a block,
+        // so it keeps its frame; the root module of a package is wrapped in one
+        match (fun.item, &arg.item) {
+            (
+                SST::ScopedLambda(ScopedLambda { arg, body, scope }),
+                SST::Base(Base::Lit(Lit::Unit)),
+            ) if arg.item == Pattern::Lit(Lit::Unit) => self.lambda(arg, *body, scope, true)?,
+            (fun, _) => self.walk(&Spanned::new(fun, fun_span))?,
+        }
 
-        self.lambda.emit_span(&Span::combine(&fun.span, &arg.span));
+        self.lambda.emit_span(&span);
         self.lambda.emit(Opcode::Call);
         Ok(())
     }
+
+    /// Runs the body of a module, which is hoisted to a function that takes `()`,
+    /// leaving the record of its top-level variables on the stack.
+    fn module(&mut self, module: Spanned<SST>) -> Result<(), Syntax> {
+        self.lit(Lit::Unit);
+        self.walk(&module)?;
+
+        self.lambda.emit_span(&module.span);
+        self.lambda.emit(Opcode::Call);
+        Ok(())
+    }
+
+    /// Raises an effect.
+    /// The payload is wrapped in a label named after the effect,
+    /// which is what handlers match against.
+    /// The name of the effect is passed along as well,
+    /// so that the host can handle it if nothing else does.
+    fn effect(&mut self, name: &str, payload: Spanned<SST>, span: &Span) -> Result<(), Syntax> {
+        self.walk(&payload)?;
+        self.kind(name, span);
+        self.lambda.emit(Opcode::Label);
+
+        self.lambda.emit_span(span);
+        self.lambda.emit(Opcode::Effect);
+        let index = self.lambda.index_data(Data::String(name.to_string()));
+        self.lambda.emit_bytes(&mut split_number(index));
+        Ok(())
+    }
+
+    /// Calls the body of a handle expression with a handler installed.
+    /// The handler is passed every effect raised in the body
+    /// whose kind is listed in a constant tuple.
+    fn handle(
+        &mut self,
+        body: Spanned<SST>,
+        handler: Spanned<SST>,
+        effects: Vec<String>,
+        span: &Span,
+    ) -> Result<(), Syntax> {
+        self.walk(&body)?;
+        self.walk(&handler)?;
+
+        let kinds = effects
+            .iter()
+            .map(|name| Data::Kind(self.labels.intern(ROOT, name)))
+            .collect();
+
+        self.lambda.emit_span(span);
+        self.lambda.emit(Opcode::Handler);
+        let mut split = split_number(self.lambda.index_data(Data::Tuple(kinds)));
+        self.lambda.emit_bytes(&mut split);
+        Ok(())
+    }
+
+    /// Evaluates the left operand, then the right operand,
+    /// and then applies the builtin operator to the both of them.
+    fn bin_op(&mut self, op: BinOp, left: Spanned<SST>, right: Spanned<SST>) -> Result<(), Syntax> {
+        if op.is_logic() {
+            return self.logic(op, left, right);
+        }
+
+        self.walk(&left)?;
+        self.walk(&right)?;
+
+        self.lambda
+            .emit_span(&Span::combine(&left.span, &right.span));
+        self.lambda.emit(match op {
+            BinOp::Add => Opcode::Add,
+            BinOp::Sub => Opcode::Sub,
+            BinOp::Mul => Opcode::Mul,
+            BinOp::Div => Opcode::Div,
+            BinOp::Rem => Opcode::Rem,
+            BinOp::Pow => Opcode::Pow,
+            BinOp::Equal => Opcode::Equal,
+            BinOp::NotEqual => Opcode::NotEqual,
+            BinOp::Less => Opcode::Less,
+            BinOp::Greater => Opcode::Greater,
+            BinOp::LessEqual => Opcode::LessEqual,
+            BinOp::GreaterEqual => Opcode::GreaterEqual,
+            BinOp::And | BinOp::Or => unreachable!("Logic operators are compiled to jumps"),
+        });
+        Ok(())
+    }
+
+    /// Compiles `&&` and `||` so that they short-circuit:
+    /// `a && b` is `if a { b } else { False }`,
+    /// and `a || b` is `if a { True } else { b }`.
+    /// The right operand is only evaluated if the left does not decide the result.
+    fn logic(&mut self, op: BinOp, left: Spanned<SST>, right: Spanned<SST>) -> Result<(), Syntax> {
+        self.walk(&left)?;
+        self.lambda.emit_span(&left.span);
+        let to_else = self.lambda.emit_jump(Opcode::JumpFalse);
+
+        match op {
+            BinOp::And => self.walk(&right)?,
+            _ => self.lit(Lit::Boolean(true)),
+        }
+        let to_end = self.lambda.emit_jump(Opcode::Jump);
+
+        self.lambda.patch_jump(to_else);
+        match op {
+            BinOp::And => self.lit(Lit::Boolean(false)),
+            _ => self.walk(&right)?,
+        }
+        self.lambda.patch_jump(to_end);
+        Ok(())
+    }
+
+    /// Evaluates the operand, then applies the builtin operator to it.
+    fn un_op(&mut self, op: UnOp, operand: Spanned<SST>) -> Result<(), Syntax> {
+        self.walk(&operand)?;
+        self.lambda.emit_span(&operand.span);
+        self.lambda.emit(match op {
+            UnOp::Neg => Opcode::Neg,
+            UnOp::Not => Opcode::Not,
+        });
+        Ok(())
+    }
+
+    /// Compiles an if expression.
+    /// If the condition is false, we jump over the first branch;
+    /// otherwise, we run the first branch then jump over the second.
+    fn if_(
+        &mut self,
+        cond: Spanned<SST>,
+        then: Spanned<SST>,
+        else_: Spanned<SST>,
+    ) -> Result<(), Syntax> {
+        self.walk(&cond)?;
+        self.lambda.emit_span(&cond.span);
+        let to_else = self.lambda.emit_jump(Opcode::JumpFalse);
+
+        self.walk(&then)?;
+        let to_end = self.lambda.emit_jump(Opcode::Jump);
+
+        self.lambda.patch_jump(to_else);
+        self.walk(&else_)?;
+        self.lambda.patch_jump(to_end);
+        Ok(())
+    }
+
+    /// Compiles a match expression to a decision tree.
+    /// The value being matched is kept on the top of the stack,
+    /// and each check in the tree is performed against a copy of it.
+    /// Once an arm has been selected, it is called with the value.
+    fn match_(
+        &mut self,
+        value: Spanned<SST>,
+        arms: Vec<Spanned<SST>>,
+        span: Span,
+    ) -> Result<(), Syntax> {
+        let patterns = arms
+            .iter()
+            .map(|arm| match &arm.item {
+                SST::ScopedLambda(lambda) => lambda.arg.clone(),
+                _ => unreachable!("Match arms are always functions"),
+            })
+            .collect::<Vec<_>>();
+
+        let tried = patterns
+            .iter()
+            .map(|pattern| format!("    {}", pattern.span.contents()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let tried = self.lambda.index_data(Data::String(tried));
+
+        let decision = Decision::build(
+            &patterns
+                .into_iter()
+                .map(|pattern| pattern.item)
+                .collect::<Vec<_>>(),
+        );
+
+        self.walk(&value)?;
+        let mut ends = vec![];
+        self.decide(&decision, &arms, tried, &span, &mut ends)?;
+
+        for end in ends {
+            self.lambda.patch_jump(end);
+        }
+        Ok(())
+    }
+
+    /// Emits the bytecode for a node in a decision tree,
+    /// collecting the jumps to be patched at the end of the match.
+    fn decide(
+        &mut self,
+        decision: &Decision,
+        arms: &[Spanned<SST>],
+        tried: usize,
+        span: &Span,
+        ends: &mut Vec<usize>,
+    ) -> Result<(), Syntax> {
+        match decision {
+            Decision::Arm(index) => {
+                let arm = &arms[*index];
+                self.walk(arm)?;
+                self.lambda.emit_span(&arm.span);
+                self.lambda.emit(Opcode::Call);
+                ends.push(self.lambda.emit_jump(Opcode::Jump));
+            }
+            Decision::Fail => {
+                self.lambda.emit_span(span);
+                self.lambda.emit(Opcode::NoMatch);
+                self.lambda.emit_bytes(&mut split_number(tried));
+            }
+            Decision::Check(path, check, success, failure) => {
+                // the length of the rest of a list
+                // can be checked without building the rest of the list
+                let (path, skipped) = match (path.split_last(), check) {
+                    (Some((Step::Rest(skipped), path)), Check::Empty | Check::NonEmpty) => {
+                        (path, *skipped)
+                    }
+                    _ => (&path[..], 0),
+                };
+
+                self.lambda.emit(Opcode::Copy);
+                for step in path.iter() {
+                    match step {
+                        Step::Index(index) => {
+                            self.lambda.emit(Opcode::Index);
+                            self.lambda.emit_bytes(&mut split_number(*index));
+                        }
+                        Step::Rest(skipped) => {
+                            self.lambda.emit(Opcode::Rest);
+                            self.lambda.emit_bytes(&mut split_number(*skipped));
+                        }
+                        Step::Unwrap(name) => {
+                            self.kind(name, span);
+                            self.lambda.emit(Opcode::UnLabel);
+                        }
+                        Step::Field(name) => {
+                            self.lambda.emit(Opcode::Field);
+                            let index = self.lambda.index_data(Data::String(name.clone()));
+                            self.lambda.emit_bytes(&mut split_number(index));
+                        }
+                    }
+                }
+
+                match check {
+                    Check::Lit(lit) => {
+                        self.lit(lit.clone());
+                        self.lambda.emit(Opcode::Equal);
+                    }
+                    Check::Label(name) => {
+                        self.kind(name, span);
+                        self.lambda.emit(Opcode::IsLabel);
+                    }
+                    Check::Tuple(length) => {
+                        self.lambda.emit(Opcode::IsTuple);
+                        self.lambda.emit_bytes(&mut split_number(*length));
+                    }
+                    Check::Record(names) => {
+                        let names = names
+                            .iter()
+                            .map(|name| Data::String(name.clone()))
+                            .collect();
+                        let index = self.lambda.index_data(Data::Tuple(names));
+                        self.lambda.emit(Opcode::IsRecord);
+                        self.lambda.emit_bytes(&mut split_number(index));
+                    }
+                    Check::Empty => {
+                        self.lambda.emit(Opcode::IsList);
+                        self.lambda.emit_bytes(&mut split_number(skipped));
+                        self.lambda.emit_bytes(&mut split_number(0));
+                    }
+                    Check::NonEmpty => {
+                        self.lambda.emit(Opcode::IsList);
+                        self.lambda.emit_bytes(&mut split_number(skipped + 1));
+                        self.lambda.emit_bytes(&mut split_number(1));
+                    }
+                }
+
+                let to_failure = self.lambda.emit_jump(Opcode::JumpFalse);
+                self.decide(success, arms, tried, span, ends)?;
+                self.lambda.patch_jump(to_failure);
+                self.decide(failure, arms, tried, span, ends)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
-// TODO: tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::Source, compiler::hoist};
+
+    fn gen(source: &str) -> Rc<Lambda> {
+        let (sst, scope) = hoist(Source::source(source)).unwrap();
+        Compiler::compile(sst, scope).unwrap()
+    }
+
+    #[test]
+    fn jumps_verify() {
+        let lambda = gen("x = if True { 1 } else if False { 2 } else { 3 }\nif x { 4 }");
+        assert!(lambda.verify());
+    }
+
+    #[test]
+    fn match_verifies() {
+        let lambda = gen("match (1, 2) { (0, _) -> 0, (_, Some x) -> x, _ -> 3 }");
+        assert!(lambda.verify());
+    }
+
+    #[test]
+    fn records_verify() {
+        let lambda = gen("p = { x: 1, y: 2 }\n{ x: a, y: _ } = { p with y = 3 }\n\
+            match p { { x: 0, y: b } -> b, _ -> p.x }");
+        assert!(lambda.verify());
+    }
+
+    #[test]
+    fn lists_verify() {
+        let lambda = gen("xs = [1, ..[2, 3]]\n[a, ..rest] = xs\n\
+            match xs { [] -> 0, [x] -> x, [_, y, ..[z]] -> y + z, _ -> a }");
+        assert!(lambda.verify());
+    }
+
+    #[test]
+    fn logic_verify() {
+        let lambda = gen("a = True && !False\nb = a || 1 < 2");
+        assert!(lambda.verify());
+    }
+
+    #[test]
+    fn negation_verify() {
+        let lambda = gen("x = 2\ny = -x * -1.5");
+        assert!(lambda.verify());
+    }
+
+    #[test]
+    fn maps_verify() {
+        let lambda = gen("m = [1 => \"one\", 2 => \"two\"]\n[=>]");
+        assert!(lambda.verify());
+    }
+
+    #[test]
+    fn labels_interned() {
+        let lambda = gen("a = Some 1\nb = None ()\nSome x = a\nc = Some 2");
+        let kinds = lambda
+            .constants
+            .iter()
+            .filter_map(|data| match data {
+                Data::Kind(kind) => Some((kind.id(), kind.to_string())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![(0, "Some".to_string()), (1, "None".to_string())]
+        );
+    }
+}
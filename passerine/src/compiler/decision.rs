@@ -0,0 +1,310 @@
+use crate::{
+    common::lit::Lit,
+    construct::{symbol::UniqueSymbol, tree::Pattern},
+};
+
+// TODO: compile more than one arm in a row into a jump table
+// when they all check the same path against different literals.
+
+/// A single step taken to reach some part of the data being matched.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Take an item out of a tuple or list.
+    Index(usize),
+    /// Take the items of a list after the first few.
+    Rest(usize),
+    /// Take the data wrapped by a label with a specific name.
+    Unwrap(String),
+    /// Take the value of a field out of a record.
+    Field(String),
+}
+
+/// The path to some part of the data being matched,
+/// i.e. a series of steps starting from the matched data itself.
+pub type Path = Vec<Step>;
+
+/// A check performed against some part of the data being matched.
+/// Two different checks against the same path are mutually exclusive,
+/// i.e. if one succeeds, the other is guaranteed to fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    /// The data is equal to a literal.
+    Lit(Lit),
+    /// The data is a label with a specific name.
+    Label(String),
+    /// The data is a tuple with a specific length.
+    Tuple(usize),
+    /// The data is a record with exactly these fields, sorted by name.
+    Record(Vec<String>),
+    /// The data is a list with no items.
+    Empty,
+    /// The data is a list with at least one item.
+    NonEmpty,
+}
+
+/// A decision tree built from the arms of a match expression.
+/// Each check is performed at most once along any path through the tree,
+/// and arms are always selected in the order they are written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// Runs the arm with the given index.
+    Arm(usize),
+    /// No arm matched the data.
+    Fail,
+    /// Performs a check at a path,
+    /// continuing with the first tree if it succeeds,
+    /// or the second if it does not.
+    Check(Path, Check, Box<Decision>, Box<Decision>),
+}
+
+/// An arm still being considered,
+/// alongside the checks it needs to succeed before it can be selected.
+#[derive(Debug, Clone)]
+struct Row {
+    checks: Vec<(Path, Check)>,
+    arm: usize,
+}
+
+impl Row {
+    fn without(mut self, path: &Path) -> Row {
+        self.checks.retain(|(p, _)| p != path);
+        self
+    }
+
+    fn check_at(&self, path: &Path) -> Option<&Check> {
+        self.checks.iter().find(|(p, _)| p == path).map(|(_, c)| c)
+    }
+}
+
+impl Decision {
+    /// Builds a decision tree from the patterns of each arm, in order.
+    pub fn build(patterns: &[Pattern<UniqueSymbol>]) -> Decision {
+        let rows = patterns
+            .iter()
+            .enumerate()
+            .map(|(arm, pattern)| {
+                let mut checks = vec![];
+                Decision::checks(pattern, vec![], &mut checks);
+                Row { checks, arm }
+            })
+            .collect();
+
+        Decision::decide(rows)
+    }
+
+    /// Flattens a pattern into the checks that must succeed for it to match.
+    /// Checks are listed parent first, so a check on a path
+    /// is always performed after the checks required to reach that path.
+    fn checks(pattern: &Pattern<UniqueSymbol>, path: Path, checks: &mut Vec<(Path, Check)>) {
+        match pattern {
+            Pattern::Symbol(_) => (),
+            Pattern::Lit(lit) => checks.push((path, Check::Lit(lit.clone()))),
+            Pattern::Label(name, inner) => {
+                checks.push((path.clone(), Check::Label(name.item.clone())));
+                let mut inner_path = path;
+                inner_path.push(Step::Unwrap(name.item.clone()));
+                Decision::checks(&inner.item, inner_path, checks);
+            }
+            Pattern::Tuple(items) => {
+                checks.push((path.clone(), Check::Tuple(items.len())));
+                for (index, item) in items.iter().enumerate() {
+                    let mut item_path = path.clone();
+                    item_path.push(Step::Index(index));
+                    Decision::checks(&item.item, item_path, checks);
+                }
+            }
+            Pattern::Record(fields) => {
+                let mut names = fields
+                    .iter()
+                    .map(|(name, _)| name.item.clone())
+                    .collect::<Vec<_>>();
+                names.sort();
+                checks.push((path.clone(), Check::Record(names)));
+                for (name, field) in fields.iter() {
+                    let mut field_path = path.clone();
+                    field_path.push(Step::Field(name.item.clone()));
+                    Decision::checks(&field.item, field_path, checks);
+                }
+            }
+            // a list pattern checks, item by item, that there is another item,
+            // so that `[]` and `[x, ..xs]` are mutually exclusive
+            Pattern::List(items, rest) => {
+                for (index, item) in items.iter().enumerate() {
+                    let mut rest_path = path.clone();
+                    rest_path.push(Step::Rest(index));
+                    checks.push((rest_path, Check::NonEmpty));
+
+                    let mut item_path = path.clone();
+                    item_path.push(Step::Index(index));
+                    Decision::checks(&item.item, item_path, checks);
+                }
+
+                let mut rest_path = path;
+                rest_path.push(Step::Rest(items.len()));
+                match rest {
+                    Some(rest) => Decision::checks(&rest.item, rest_path, checks),
+                    None => checks.push((rest_path, Check::Empty)),
+                }
+            }
+            Pattern::Chain(_) => unreachable!("Chained patterns are rejected before matching"),
+            // types are checked before the program is run
+            Pattern::Is(inner, _) => Decision::checks(&inner.item, path, checks),
+        }
+    }
+
+    /// Selects the first arm if it needs no more checks,
+    /// otherwise performs its next check and splits the arms
+    /// based on whether that check succeeds.
+    fn decide(rows: Vec<Row>) -> Decision {
+        let first = match rows.first() {
+            Some(row) => row,
+            None => return Decision::Fail,
+        };
+
+        let (path, check) = match first.checks.first() {
+            Some(next) => next.clone(),
+            None => return Decision::Arm(first.arm),
+        };
+
+        let mut success = vec![];
+        let mut failure = vec![];
+
+        for row in rows {
+            match row.check_at(&path) {
+                // the same check; it either succeeded or failed
+                Some(other) if other == &check => success.push(row.without(&path)),
+                // a different check at the same path can only succeed
+                // if this check fails
+                Some(_) => failure.push(row),
+                // this row does not care about this path
+                None => {
+                    success.push(row.clone());
+                    failure.push(row);
+                }
+            }
+        }
+
+        Decision::Check(
+            path,
+            check,
+            Box::new(Decision::decide(success)),
+            Box::new(Decision::decide(failure)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{
+        span::{Span, Spanned},
+        Source,
+    };
+
+    fn spanned<T>(item: T) -> Spanned<T> {
+        Spanned::new(item, Span::point(&Source::source(""), 0))
+    }
+
+    #[test]
+    fn wildcard_after_literal() {
+        let patterns = vec![
+            Pattern::Lit(Lit::Integer(0)),
+            Pattern::Symbol(UniqueSymbol(0)),
+        ];
+
+        assert_eq!(
+            Decision::build(&patterns),
+            Decision::Check(
+                vec![],
+                Check::Lit(Lit::Integer(0)),
+                Box::new(Decision::Arm(0)),
+                Box::new(Decision::Arm(1)),
+            )
+        );
+    }
+
+    #[test]
+    fn shared_checks() {
+        // (0, x), (y, 1), (_, _)
+        let patterns = vec![
+            Pattern::Tuple(vec![
+                spanned(Pattern::Lit(Lit::Integer(0))),
+                spanned(Pattern::Symbol(UniqueSymbol(0))),
+            ]),
+            Pattern::Tuple(vec![
+                spanned(Pattern::Symbol(UniqueSymbol(1))),
+                spanned(Pattern::Lit(Lit::Integer(1))),
+            ]),
+            Pattern::Symbol(UniqueSymbol(2)),
+        ];
+
+        let tuple = |success, failure| {
+            Decision::Check(
+                vec![],
+                Check::Tuple(2),
+                Box::new(success),
+                Box::new(failure),
+            )
+        };
+        let second = Decision::Check(
+            vec![Step::Index(1)],
+            Check::Lit(Lit::Integer(1)),
+            Box::new(Decision::Arm(1)),
+            Box::new(Decision::Arm(2)),
+        );
+        let first = Decision::Check(
+            vec![Step::Index(0)],
+            Check::Lit(Lit::Integer(0)),
+            Box::new(Decision::Arm(0)),
+            Box::new(second),
+        );
+
+        // the tuple check is only performed once
+        assert_eq!(Decision::build(&patterns), tuple(first, Decision::Arm(2)));
+    }
+
+    #[test]
+    fn lists() {
+        // [], [x, ..xs]
+        let patterns = vec![
+            Pattern::List(vec![], None),
+            Pattern::List(
+                vec![spanned(Pattern::Symbol(UniqueSymbol(0)))],
+                Some(Box::new(spanned(Pattern::Symbol(UniqueSymbol(1))))),
+            ),
+        ];
+
+        assert_eq!(
+            Decision::build(&patterns),
+            Decision::Check(
+                vec![Step::Rest(0)],
+                Check::Empty,
+                Box::new(Decision::Arm(0)),
+                Box::new(Decision::Check(
+                    vec![Step::Rest(0)],
+                    Check::NonEmpty,
+                    Box::new(Decision::Arm(1)),
+                    Box::new(Decision::Fail),
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn exhausted() {
+        let patterns = vec![Pattern::Label(
+            spanned("Some".to_string()),
+            Box::new(spanned(Pattern::Symbol(UniqueSymbol(0)))),
+        )];
+
+        assert_eq!(
+            Decision::build(&patterns),
+            Decision::Check(
+                vec![],
+                Check::Label("Some".to_string()),
+                Box::new(Decision::Arm(0)),
+                Box::new(Decision::Fail),
+            )
+        );
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     common::{Span, Spanned},
     construct::{
         symbol::SharedSymbol,
-        tree::{Base, Lambda, Sugar, AST, CST},
+        tree::{Base, Fields, Lambda, Sugar, AST, CST},
     },
 };
 
@@ -35,14 +35,46 @@ impl Desugarer {
             Base::Label(l) => Base::Label(l),
             Base::Lit(l) => Base::Lit(l),
             Base::Tuple(t) => Base::Tuple(t.into_iter().map(Desugarer::walk).collect()),
+            Base::Record(r) => Base::Record(Desugarer::walk_fields(r)),
+            Base::Map(m) => Base::Map(
+                m.into_iter()
+                    .map(|(k, v)| (Desugarer::walk(k), Desugarer::walk(v)))
+                    .collect(),
+            ),
+            Base::List(l, r) => Base::list(
+                l.into_iter().map(Desugarer::walk).collect(),
+                r.map(|r| Desugarer::walk(*r)),
+            ),
             Base::Module(m) => Base::module(Desugarer::walk(*m)),
             Base::Block(b) => Base::Block(b.into_iter().map(Desugarer::walk).collect()),
             Base::Call(f, a) => Base::call(Desugarer::walk(*f), Desugarer::walk(*a)),
+            Base::BinOp(o, l, r) => Base::bin_op(o, Desugarer::walk(*l), Desugarer::walk(*r)),
+            Base::UnOp(o, e) => Base::un_op(o, Desugarer::walk(*e)),
+            Base::Match(v, a) => Base::match_(
+                Desugarer::walk(*v),
+                a.into_iter().map(Desugarer::walk).collect(),
+            ),
+            Base::If(c, t, e) => Base::if_(
+                Desugarer::walk(*c),
+                Desugarer::walk(*t),
+                Desugarer::walk(*e),
+            ),
             Base::Assign(p, e) => Base::assign(p, Desugarer::walk(*e)),
-            Base::Effect(_) => todo!("need to handle effects"),
+            Base::Effect(n, p) => Base::effect(n, Desugarer::walk(*p)),
+            Base::Handle(b, h, e) => Base::handle(Desugarer::walk(*b), Desugarer::walk(*h), e),
+            Base::Is(e, t) => Base::is(Desugarer::walk(*e), t),
+            Base::Field(r, f) => Base::field(Desugarer::walk(*r), f),
+            Base::Update(r, f) => Base::update(Desugarer::walk(*r), Desugarer::walk_fields(f)),
         }
     }
 
+    fn walk_fields(fields: Fields<Spanned<AST>>) -> Fields<Spanned<CST>> {
+        fields
+            .into_iter()
+            .map(|(name, value)| (name, Desugarer::walk(value)))
+            .collect()
+    }
+
     fn walk_lambda(l: Lambda<Spanned<AST>>) -> Lambda<Spanned<CST>> {
         let Lambda { arg, body } = l;
         let body = Desugarer::walk(*body);
@@ -70,17 +102,15 @@ impl Desugarer {
 
                 fun.item
             }
-            // TODO: don't ignore type annotations!
-            Sugar::Is(e, _) => {
-                unimplemented!(
-                    "type annotations will be implemented when the type checker is implemented"
-                )
-            }
             Sugar::Comp(arg, fun) => {
                 CST::Base(Base::call(Desugarer::walk(*fun), Desugarer::walk(*arg)))
             }
-            Sugar::Field(_, _) => {
-                unimplemented!("field access will be implemented when structs are implemented")
+            // `a ++ b` calls the function bound to `++` with `(a, b)`
+            Sugar::Infix(fun, left, right) => {
+                let (left, right) = (Desugarer::walk(*left), Desugarer::walk(*right));
+                let span = Span::combine(&left.span, &right.span);
+                let arg = Spanned::new(CST::Base(Base::Tuple(vec![left, right])), span);
+                CST::Base(Base::call(Desugarer::walk(*fun), arg))
             }
             Sugar::Keyword(_) => todo!(),
         }
@@ -1,98 +1,918 @@
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-use crate::common::{lambda::Lambda, Spanned};
-use crate::compiler::{compile_token_tree, Syntax};
-use crate::construct::token::{Token, TokenTree};
+use crate::{
+    common::{
+        closure::Closure,
+        label::{Kind, ROOT},
+        lambda::Lambda,
+        lit::Lit,
+        number::split_number,
+        opcode::Opcode,
+        span::{Span, Spanned},
+        Data, Labels,
+    },
+    compiler::{
+        syntax::{Diagnostics, Note, Syntax},
+        Compiler, Desugarer, Hoister, Parser, Unifier,
+    },
+    construct::token::{ResIden, TokenTree, TokenTrees},
+    explain::Code,
+    kernel,
+    vm::fiber::Fiber,
+};
 
+/// How many times a macro may expand into a use of another macro
+/// before the expansion is assumed to never finish.
+const MAX_DEPTH: usize = 128;
+
+/// A macro defined with `macro`.
+#[derive(Debug, Clone)]
+struct Macro {
+    /// The function run to expand each use of the macro.
+    fun: Data,
+    /// Where the macro was defined.
+    definition: Span,
+}
+
+/// A part of the template of a syntax rule.
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    /// Matches only this identifier, written like `'then`.
+    Keyword(String),
+    /// Matches any token tree, which replaces the variable in the body of the rule.
+    Variable(String),
+}
+
+/// A syntax rule defined with `syntax`.
+#[derive(Debug, Clone)]
+struct Rule {
+    template: Vec<Part>,
+    /// The lines of the block the rule expands to.
+    body: Vec<Spanned<TokenTrees>>,
+    /// Where the rule was defined.
+    definition: Span,
+}
+
+impl Rule {
+    fn keywords(&self) -> usize {
+        self.template
+            .iter()
+            .filter(|part| matches!(part, Part::Keyword(_)))
+            .count()
+    }
+
+    /// Whether a line or a group is a use of this rule.
+    fn matches(&self, trees: &[Spanned<TokenTree>]) -> bool {
+        self.template.len() == trees.len()
+            && self
+                .template
+                .iter()
+                .zip(trees)
+                .all(|(part, tree)| match (part, &tree.item) {
+                    (Part::Keyword(keyword), TokenTree::Iden(iden)) => keyword == iden,
+                    (Part::Keyword(_), _) => false,
+                    (Part::Variable(_), _) => true,
+                })
+    }
+
+    /// Whether some line or group could be a use of both rules.
+    fn overlaps(&self, other: &Rule) -> bool {
+        self.template.len() == other.template.len()
+            && self
+                .template
+                .iter()
+                .zip(&other.template)
+                .all(|parts| match parts {
+                    (Part::Keyword(a), Part::Keyword(b)) => a == b,
+                    _ => true,
+                })
+    }
+
+    /// Whether this rule matches only a subset of what an overlapping rule matches,
+    /// which is the case if it has a keyword wherever the other does, and more.
+    fn is_more_specific(&self, other: &Rule) -> bool {
+        self.keywords() > other.keywords()
+            && self
+                .template
+                .iter()
+                .zip(&other.template)
+                .all(|parts| !matches!(parts, (Part::Variable(_), Part::Keyword(_))))
+    }
+
+    fn describe(&self) -> String {
+        self.template
+            .iter()
+            .map(|part| match part {
+                Part::Keyword(keyword) => format!("'{}", keyword),
+                Part::Variable(variable) => variable.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Expands macros, after a program is read and before it is parsed.
+///
+/// A macro is defined on its own line, like `macro name = function`,
+/// and can be used after its definition by starting a line or a group with its name,
+/// like `name a (b + c)`.
+/// The function is run at compile time on a `Fiber`,
+/// and is passed a list of the token trees following the name.
+/// It must return a list of token trees, which replaces the use of the macro.
+///
+/// Token trees are represented as labelled data:
+/// `Iden "x"`, `Label "Some"`, `Op "+"`, and `Lit 1` for single tokens,
+/// `Form [..]` and `List [..]` for groups in parentheses and square brackets,
+/// and `Block [[..], ..]` for a list of the lines in curly brackets.
+///
+/// Identifiers the macro introduces are renamed when they are bound in the expansion,
+/// so they can not clash with the identifiers used where the macro is.
+/// To tell the two apart, identifiers are passed to the macro under new names,
+/// like `x#3`, and get their names back once the macro has run.
+/// Macros and syntax rules defined in a block can only be used inside that block.
+/// Each token produced by a macro is spanned by the use of the macro,
+/// and its span remembers where the macro was defined.
+///
+/// Syntax rules are a simpler kind of macro, defined by a template and a block,
+/// like `syntax 'unless cond body { if !cond { body } else { () } }`.
+/// A line or a group that matches the template,
+/// with an identifier wherever the template has a keyword, like `'unless`,
+/// and any token tree wherever it has a pattern variable, like `cond`,
+/// is replaced by the block, with each variable replaced by what it matched.
+/// If a line matches more than one rule, the most specific rule is used;
+/// rules where neither is more specific can not both be defined.
+#[derive(Debug, Clone, Default)]
 pub struct Expander {
-    rules: HashMap<String, Rc<Lambda>>,
+    macros: HashMap<String, Macro>,
+    /// Syntax rules, in the order they were defined.
+    rules: Vec<Rule>,
+    /// The registry of the labels token trees are represented with.
+    labels: Labels,
+    /// The number of identifiers renamed so far, used to keep new names unique.
+    renamed: usize,
+    /// How many macros are currently being expanded, one inside the other.
+    depth: usize,
 }
 
 impl Expander {
-    pub fn expand(
+    /// Expands all macros in a token tree.
+    pub fn expand(token_tree: Spanned<TokenTree>) -> Result<Spanned<TokenTree>, Diagnostics> {
+        Expander::new().expand_entry(token_tree)
+    }
+
+    /// Builds an expander with no macros.
+    pub fn new() -> Expander {
+        Expander::default()
+    }
+
+    /// Expands one entry of a `Session`.
+    /// Macros defined in earlier entries can be used in later ones.
+    pub fn expand_entry(
+        &mut self,
         token_tree: Spanned<TokenTree>,
-        mut rules: HashMap<String, Spanned<TokenTree>>,
-    ) -> Result<Spanned<TokenTree>, Syntax> {
-        let mut expander = Expander {
-            rules: HashMap::new(),
+    ) -> Result<Spanned<TokenTree>, Diagnostics> {
+        // the entry itself is not a scope, so its macros outlive it
+        match token_tree.item {
+            TokenTree::Block(lines) => Ok(Spanned::new(
+                TokenTree::Block(self.lines(lines)?),
+                token_tree.span,
+            )),
+            _ => self.walk(token_tree),
+        }
+    }
+
+    fn walk(&mut self, token_tree: Spanned<TokenTree>) -> Result<Spanned<TokenTree>, Diagnostics> {
+        let Spanned { item, span } = token_tree;
+
+        let item = match item {
+            TokenTree::Block(lines) => TokenTree::Block(self.scoped(lines)?),
+            TokenTree::Form(form) => TokenTree::Form(self.sequence(form, &span)?),
+            TokenTree::List(items) => TokenTree::List(self.trees(items)?),
+            leaf => leaf,
         };
 
-        for (name, rule) in rules.drain() {
-            let bytecode = compile_token_tree(rule)?;
-            expander.rules.insert(name, bytecode);
+        Ok(Spanned::new(item, span))
+    }
+
+    fn trees(&mut self, trees: TokenTrees) -> Result<TokenTrees, Diagnostics> {
+        trees.into_iter().map(|tree| self.walk(tree)).collect()
+    }
+
+    /// Expands the lines of a nested block,
+    /// forgetting the macros and syntax rules defined in it afterwards.
+    fn scoped(
+        &mut self,
+        lines: Vec<Spanned<TokenTrees>>,
+    ) -> Result<Vec<Spanned<TokenTrees>>, Diagnostics> {
+        let macros = self.macros.clone();
+        let rules = self.rules.clone();
+        let expanded = self.lines(lines);
+        self.macros = macros;
+        self.rules = rules;
+        expanded
+    }
+
+    /// Expands each line of a block.
+    /// Macro definitions and syntax rules are removed, leaving `()` in their place.
+    fn lines(
+        &mut self,
+        lines: Vec<Spanned<TokenTrees>>,
+    ) -> Result<Vec<Spanned<TokenTrees>>, Diagnostics> {
+        let mut expanded = vec![];
+
+        for Spanned { item, span } in lines {
+            let keyword = match item.first().map(|tree| &tree.item) {
+                Some(TokenTree::Iden(iden)) => ResIden::try_new(iden),
+                _ => None,
+            };
+            let line = match keyword {
+                Some(ResIden::Macro) => self.define(item, &span)?,
+                Some(ResIden::Syntax) => self.define_rule(item, &span)?,
+                _ => self.sequence(item, &span)?,
+            };
+            expanded.push(Spanned::new(line, span));
         }
 
-        expander.walk(token_tree)
+        Ok(expanded)
     }
 
-    pub fn walk(
-        &self,
-        token_tree: Spanned<TokenTree>,
-    ) -> Result<Spanned<TokenTree>, Syntax> {
-        let Spanned {
-            item: token_tree,
-            span,
-        } = token_tree;
-
-        let result = match token_tree {
-            TokenTree::Form(form) => self.expand_form(form)?,
-
-            // trivial conversion
-            TokenTree::Block(block) => {
-                let mut new_block = Vec::new();
-                for trees in block {
-                    let mut new_trees = Vec::new();
-                    let Spanned { item, span } = trees;
-                    for tree in item.into_iter() {
-                        new_trees.push(self.walk(tree)?)
-                    }
-                    new_block.push(Spanned::new(new_trees, span));
+    /// Expands a line or a group,
+    /// which is a use of a macro if it starts with the name of one,
+    /// or a use of a syntax rule if it matches the template of one.
+    fn sequence(&mut self, trees: TokenTrees, span: &Span) -> Result<TokenTrees, Diagnostics> {
+        let expanded = match trees.first().map(|tree| &tree.item) {
+            Some(TokenTree::Iden(name)) if self.macros.contains_key(name) => {
+                let name = name.to_string();
+                self.nested(&format!("macro `{}`", name), span)?;
+                self.invoke(&name, &trees[1..], span)?
+            }
+            _ => match self.matching(&trees) {
+                Some(rule) => {
+                    self.nested(&format!("syntax rule `{}`", rule.describe()), span)?;
+                    self.instantiate(rule, trees, span)
                 }
-                TokenTree::Block(new_block)
+                None => return self.trees(trees),
             },
-            TokenTree::List(trees) => TokenTree::List(
-                trees
-                    .into_iter()
-                    .map(|tree| self.walk(tree))
-                    .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        self.depth += 1;
+        let result = self.sequence(expanded, span);
+        self.depth -= 1;
+        result
+    }
+
+    /// Raises an error if a macro is about to be expanded
+    /// inside of too many other macros.
+    fn nested(&self, name: &str, span: &Span) -> Result<(), Diagnostics> {
+        if self.depth < MAX_DEPTH {
+            return Ok(());
+        }
+
+        Err(Syntax::error(
+            Code::RecursiveMacro,
+            &format!(
+                "The {} is used inside of {} other macros, so its expansion probably never ends",
+                name, MAX_DEPTH
             ),
-            // trivial conversion
-            TokenTree::Iden(iden) => TokenTree::Iden(iden),
-            TokenTree::Label(label) => TokenTree::Label(label),
-            TokenTree::Op(op) => TokenTree::Op(op),
-            TokenTree::Lit(lit) => TokenTree::Lit(lit),
+            span,
+        )
+        .into())
+    }
+
+    /// Parses a macro definition, like `macro name = function`,
+    /// then compiles and runs the function so it can be used to expand the macro.
+    fn define(&mut self, line: TokenTrees, span: &Span) -> Result<TokenTrees, Diagnostics> {
+        let usage = "Expected a macro definition, like `macro name = trees -> trees`";
+        let name = match line.get(1).map(|tree| &tree.item) {
+            Some(TokenTree::Iden(name)) if ResIden::try_new(name).is_none() => name.to_string(),
+            _ => return Err(Syntax::error(Code::InvalidMacro, usage, span).into()),
         };
+        match line.get(2).map(|tree| &tree.item) {
+            Some(TokenTree::Op(op)) if op == "=" && line.len() > 3 => (),
+            _ => return Err(Syntax::error(Code::InvalidMacro, usage, span).into()),
+        }
 
-        Ok(Spanned::new(result, span))
+        // the function may use macros defined before it
+        let body = self.sequence(line[3..].to_vec(), span)?;
+        let program = Spanned::new(
+            TokenTree::Block(vec![Spanned::new(body, span.clone())]),
+            span.clone(),
+        );
+
+        let (ast, symbols) = Parser::parse(program)?;
+        let cst = Desugarer::desugar(ast);
+        let (sst, scope) = Hoister::hoist(cst, symbols)?;
+        Unifier::unify(&sst)?;
+        let lambda = Compiler::compile_entry(sst, scope, &self.labels)?;
+
+        let fun = self.run(lambda, &name, span)?;
+        let definition = span.clone();
+        self.macros.insert(name, Macro { fun, definition });
+        Ok(vec![Spanned::new(TokenTree::Lit(Lit::Unit), span.clone())])
+    }
+
+    /// Parses a syntax rule, like `syntax 'unless cond body { ... }`.
+    /// A rule must have at least one keyword,
+    /// and must not be ambiguous with any rule defined before it.
+    fn define_rule(&mut self, line: TokenTrees, span: &Span) -> Result<TokenTrees, Diagnostics> {
+        let (body, template) = match line[1..].split_last() {
+            Some((
+                Spanned {
+                    item: TokenTree::Block(body),
+                    ..
+                },
+                template,
+            )) if !template.is_empty() => (body.clone(), template),
+            _ => {
+                return Err(Syntax::error(
+                    Code::InvalidSyntaxRule,
+                    "Expected a syntax rule, like `syntax 'unless cond body { ... }`",
+                    span,
+                )
+                .into())
+            }
+        };
+
+        let mut parts = vec![];
+        let mut trees = template.iter();
+        while let Some(tree) = trees.next() {
+            let part = match &tree.item {
+                TokenTree::Op(op) if op == "'" => match trees.next().map(|tree| &tree.item) {
+                    Some(TokenTree::Iden(keyword)) => Part::Keyword(keyword.to_string()),
+                    _ => {
+                        return Err(Syntax::error(
+                            Code::InvalidSyntaxRule,
+                            "Expected a keyword after `'`, like `'then`",
+                            &tree.span,
+                        ).into())
+                    }
+                },
+                TokenTree::Iden(variable) if ResIden::try_new(variable).is_none() => {
+                    let variable = Part::Variable(variable.to_string());
+                    if parts.contains(&variable) {
+                        return Err(Syntax::error(
+                            Code::InvalidSyntaxRule,
+                            "This pattern variable is already used in this syntax rule",
+                            &tree.span,
+                        ).into());
+                    }
+                    variable
+                }
+                other => {
+                    return Err(Syntax::error(
+                        Code::InvalidSyntaxRule,
+                        &format!(
+                            "Expected a keyword, like `'then`, or a pattern variable in the template of a syntax rule, but found {}",
+                            other
+                        ),
+                        &tree.span,
+                    ).into())
+                }
+            };
+            parts.push(part);
+        }
+
+        let rule = Rule {
+            template: parts,
+            body,
+            definition: span.clone(),
+        };
+
+        if rule.keywords() == 0 {
+            return Err(Syntax::error(
+                Code::InvalidSyntaxRule,
+                "A syntax rule needs at least one keyword, like `'then`, so it can be told apart from a function call",
+                span,
+            ).into());
+        }
+
+        for other in self.rules.iter() {
+            if rule.overlaps(other)
+                && !rule.is_more_specific(other)
+                && !other.is_more_specific(&rule)
+            {
+                return Err(Syntax::error_with_note(
+                    Code::AmbiguousSyntaxRule,
+                    &format!(
+                        "The syntax rule `{}` is ambiguous with the syntax rule `{}`, as some lines match both and neither is more specific",
+                        rule.describe(),
+                        other.describe()
+                    ),
+                    Note::new_with_hint("this rule is ambiguous", span),
+                )
+                .add_note(Note::new_with_hint(
+                    "with this rule, defined earlier",
+                    &other.definition,
+                )).into());
+            }
+        }
+
+        self.rules.push(rule);
+        Ok(vec![Spanned::new(TokenTree::Lit(Lit::Unit), span.clone())])
+    }
+
+    /// Finds the most specific syntax rule a line or a group is a use of.
+    fn matching(&self, trees: &[Spanned<TokenTree>]) -> Option<Rule> {
+        // no two matching rules have the same number of keywords,
+        // because otherwise they would be ambiguous
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(trees))
+            .max_by_key(|rule| rule.keywords())
+            .cloned()
     }
 
-    pub fn walk_form(
+    /// Expands a use of a syntax rule to the block of the rule,
+    /// replacing each pattern variable with the token tree it matched.
+    /// Identifiers the block binds, other than the variables,
+    /// are renamed first, so they can not clash with the token trees that replace the variables.
+    fn instantiate(&mut self, rule: Rule, trees: TokenTrees, span: &Span) -> TokenTrees {
+        let expanded = Span::expanded(span, &rule.definition);
+        let mut bindings = HashMap::new();
+        for (part, tree) in rule.template.into_iter().zip(trees) {
+            if let Part::Variable(variable) = part {
+                bindings.insert(variable, tree);
+            }
+        }
+
+        let mut block = vec![Spanned::new(TokenTree::Block(rule.body), expanded.clone())];
+        let mut renames = HashMap::new();
+        for binder in bound(&block) {
+            if !bindings.contains_key(&binder) {
+                let fresh = self.fresh(&binder);
+                renames.insert(binder, fresh);
+            }
+        }
+
+        rename(&mut block, &renames, &HashMap::new(), false);
+        substitute(&mut block, &bindings, &expanded, false);
+        block
+    }
+
+    /// Runs some code on a new `Fiber` with the kernel,
+    /// returning the value it evaluates to.
+    fn run(&self, lambda: Rc<Lambda>, name: &str, span: &Span) -> Result<Data, Diagnostics> {
+        let mut fiber = Fiber::init(Closure::wrap(lambda));
+        kernel::register(&mut fiber);
+
+        fiber.run().map_err(|trace| {
+            Syntax::error(
+                Code::MacroFailed,
+                &format!("The macro `{}` raised an error:\n{}", name, trace),
+                span,
+            )
+        })?;
+        Ok(fiber.stack.pop_data())
+    }
+
+    /// Calls the function of a macro with some data.
+    fn apply(&self, name: &str, arg: Data, span: &Span) -> Result<Data, Diagnostics> {
+        let mut lambda = Lambda::empty();
+        lambda.labels = self.labels.clone();
+        lambda.emit_span(span);
+        for data in [arg, self.macros[name].fun.clone()] {
+            lambda.emit(Opcode::Con);
+            let mut split = split_number(lambda.index_data(data));
+            lambda.emit_bytes(&mut split);
+        }
+        lambda.emit(Opcode::Call);
+
+        self.run(Rc::new(lambda), name, span)
+    }
+
+    /// Expands a single use of a macro, returning the token trees it produced.
+    ///
+    /// Each identifier is passed to the macro under a new name,
+    /// so that the identifiers it passes through can be told apart
+    /// from the ones it introduces, which are renamed if they are bound.
+    /// Identifiers passed through then get their names back.
+    fn invoke(
+        &mut self,
+        name: &str,
+        args: &[Spanned<TokenTree>],
+        span: &Span,
+    ) -> Result<TokenTrees, Diagnostics> {
+        let expanded = Span::expanded(span, &self.macros[name].definition);
+
+        let mut passed = HashSet::new();
+        for arg in args {
+            identifiers(arg, &mut passed);
+        }
+        let marked = passed
+            .into_iter()
+            .map(|iden| {
+                let mark = self.fresh(&iden);
+                (iden, mark)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut trees = self.call(name, args, &marked, span, &expanded)?;
+
+        let restores = marked
+            .into_iter()
+            .map(|(original, mark)| (mark, original))
+            .collect::<HashMap<_, _>>();
+        let mut renames = HashMap::new();
+        for binder in bound(&trees) {
+            if !restores.contains_key(&binder) {
+                let fresh = self.fresh(&binder);
+                renames.insert(binder, fresh);
+            }
+        }
+
+        rename(&mut trees, &renames, &restores, false);
+        Ok(trees)
+    }
+
+    /// Passes token trees to a macro,
+    /// renaming the identifiers in `marked` on the way in,
+    /// and converts the token trees it returns,
+    /// which are spanned by `expanded`.
+    fn call(
         &self,
-        form: Vec<Spanned<TokenTree>>,
-    ) -> Result<TokenTree, Syntax> {
-        Ok(TokenTree::Form(
-            form.into_iter()
-                .map(|tree| self.walk(tree))
-                .collect::<Result<Vec<_>, _>>()?,
-        ))
+        name: &str,
+        args: &[Spanned<TokenTree>],
+        marked: &HashMap<String, String>,
+        span: &Span,
+        expanded: &Span,
+    ) -> Result<TokenTrees, Diagnostics> {
+        let args = args
+            .iter()
+            .map(|arg| self.to_data(&arg.item, marked))
+            .collect();
+        match self.apply(name, Data::List(Rc::new(args)), span)? {
+            Data::List(trees) => trees
+                .iter()
+                .map(|tree| self.to_tree(tree, name, expanded))
+                .collect(),
+            other => Err(self.not_token_tree(&other, name, span).into()),
+        }
     }
 
-    pub fn expand_form(
+    /// Produces a new name for an identifier,
+    /// which can not clash with any name in the source.
+    fn fresh(&mut self, name: &str) -> String {
+        self.renamed += 1;
+        format!("{}#{}", name, self.renamed)
+    }
+
+    fn kind(&self, name: &str) -> Kind {
+        self.labels.intern(ROOT, name)
+    }
+
+    /// Converts a token tree to data, as passed to a macro.
+    fn to_data(&self, tree: &TokenTree, marked: &HashMap<String, String>) -> Data {
+        let trees = |trees: &TokenTrees| {
+            let items = trees
+                .iter()
+                .map(|tree| self.to_data(&tree.item, marked))
+                .collect();
+            Data::List(Rc::new(items))
+        };
+
+        let (kind, data) = match tree {
+            TokenTree::Iden(iden) => (
+                "Iden",
+                Data::String(marked.get(iden).unwrap_or(iden).to_string()),
+            ),
+            TokenTree::Label(label) => ("Label", Data::String(label.to_string())),
+            TokenTree::Op(op) => ("Op", Data::String(op.to_string())),
+            TokenTree::Lit(lit) => ("Lit", lit.clone().to_data()),
+            TokenTree::Form(form) => ("Form", trees(form)),
+            TokenTree::List(items) => ("List", trees(items)),
+            TokenTree::Block(lines) => (
+                "Block",
+                Data::List(Rc::new(
+                    lines.iter().map(|line| trees(&line.item)).collect(),
+                )),
+            ),
+        };
+
+        Data::Label(self.kind(kind), Box::new(data))
+    }
+
+    /// Converts data returned by a macro back into a token tree.
+    fn to_tree(
         &self,
-        mut form: Vec<Spanned<TokenTree>>,
-    ) -> Result<TokenTree, Syntax> {
-        assert!(form.len() >= 2);
-        let first = form.remove(0);
+        data: &Data,
+        name: &str,
+        span: &Span,
+    ) -> Result<Spanned<TokenTree>, Diagnostics> {
+        let trees = |data: &Data| match data {
+            Data::List(trees) => trees
+                .iter()
+                .map(|tree| self.to_tree(tree, name, span))
+                .collect::<Result<TokenTrees, Diagnostics>>(),
+            other => Err(self.not_token_tree(other, name, span).into()),
+        };
+
+        let (kind, payload) = match data {
+            Data::Label(kind, payload) => (kind, payload.as_ref()),
+            other => return Err(self.not_token_tree(other, name, span).into()),
+        };
+
+        let tree = match (kind.label().name.as_str(), payload) {
+            _ if kind.label().module != ROOT => {
+                return Err(self.not_token_tree(data, name, span).into())
+            }
+            ("Iden", Data::String(iden)) => TokenTree::Iden(iden.to_string()),
+            ("Label", Data::String(label)) => TokenTree::Label(label.to_string()),
+            ("Op", Data::String(op)) => TokenTree::Op(op.to_string()),
+            ("Lit", Data::Integer(n)) => TokenTree::Lit(Lit::Integer(*n)),
+            ("Lit", Data::Float(n)) => TokenTree::Lit(Lit::Float(*n)),
+            ("Lit", Data::String(s)) => TokenTree::Lit(Lit::String(s.to_string())),
+            ("Lit", Data::Boolean(b)) => TokenTree::Lit(Lit::Boolean(*b)),
+            ("Lit", Data::Unit) => TokenTree::Lit(Lit::Unit),
+            ("Form", form) => TokenTree::Form(trees(form)?),
+            ("List", items) => TokenTree::List(trees(items)?),
+            ("Block", Data::List(lines)) => TokenTree::Block(
+                lines
+                    .iter()
+                    .map(|line| Ok(Spanned::new(trees(line)?, span.clone())))
+                    .collect::<Result<_, Diagnostics>>()?,
+            ),
+            _ => return Err(self.not_token_tree(data, name, span).into()),
+        };
+
+        Ok(Spanned::new(tree, span.clone()))
+    }
+
+    fn not_token_tree(&self, data: &Data, name: &str, span: &Span) -> Syntax {
+        Syntax::error_with_note(
+            Code::MacroFailed,
+            &format!(
+                "The macro `{}` produced '{}', which is not a token tree",
+                name, data
+            ),
+            Note::new_with_hint("the macro is used here", span),
+        )
+    }
+}
+
+/// Collects the names of all identifiers in a token tree.
+fn identifiers(tree: &Spanned<TokenTree>, found: &mut HashSet<String>) {
+    match &tree.item {
+        TokenTree::Iden(iden) => {
+            found.insert(iden.to_string());
+        }
+        TokenTree::Form(trees) | TokenTree::List(trees) => {
+            trees.iter().for_each(|tree| identifiers(tree, found))
+        }
+        TokenTree::Block(lines) => lines
+            .iter()
+            .flat_map(|line| line.item.iter())
+            .for_each(|tree| identifiers(tree, found)),
+        _ => (),
+    }
+}
+
+/// Whether the identifier at an index names a field rather than a variable,
+/// as in `point.x`, `{ x: 1 }`, or `{ point with x = 1 }`.
+fn is_field(trees: &[Spanned<TokenTree>], index: usize, record: bool) -> bool {
+    let is_op = |tree: Option<&Spanned<TokenTree>>, ops: &[&str]| matches!(tree.map(|tree| &tree.item), Some(TokenTree::Op(op)) if ops.contains(&op.as_str()));
+    let before = index.checked_sub(1).and_then(|before| trees.get(before));
+    let after_with =
+        matches!(before.map(|tree| &tree.item), Some(TokenTree::Iden(iden)) if iden == "with");
+
+    is_op(before, &["."])
+        || record
+            && (before.is_none() || is_op(before, &[","]) || after_with)
+            && is_op(trees.get(index + 1), &[":", "="])
+}
+
+/// Whether a block is a record or a record update, whose lines name fields.
+fn is_record(lines: &[Spanned<TokenTrees>]) -> bool {
+    Parser::is_record(lines) || Parser::is_update(lines)
+}
+
+/// Collects the names of the identifiers that are bound by
+/// the assignments, functions, and match arms in some token trees.
+fn bound(trees: &[Spanned<TokenTree>]) -> HashSet<String> {
+    let mut found = HashSet::new();
+    binders(trees, false, &mut found);
+    found
+}
+
+fn binders(trees: &[Spanned<TokenTree>], record: bool, found: &mut HashSet<String>) {
+    let mut start = 0;
 
-        if let TokenTree::Iden(iden) = first.item {
-            if let Some(rule) = self.rules.get(&iden) {
-                // TODO: call compiled function somehow
-                let expanded = todo!();
-                return expanded;
+    for (index, tree) in trees.iter().enumerate() {
+        match &tree.item {
+            TokenTree::Form(inner) | TokenTree::List(inner) => binders(inner, false, found),
+            TokenTree::Block(lines) => {
+                let record = is_record(lines);
+                for line in lines {
+                    binders(&line.item, record, found);
+                }
             }
+            // in a record update, `=` sets a field
+            TokenTree::Op(op) if (op == "=" && !record) || op == "->" => {
+                patterns(&trees[start..index], false, found);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Collects the names of the identifiers in a pattern.
+fn patterns(trees: &[Spanned<TokenTree>], record: bool, found: &mut HashSet<String>) {
+    for (index, tree) in trees.iter().enumerate() {
+        // the type after `:` is not part of the pattern
+        let before = index.checked_sub(1).and_then(|before| trees.get(before));
+        if !record && matches!(before.map(|tree| &tree.item), Some(TokenTree::Op(op)) if op == ":")
+        {
+            continue;
         }
 
-        self.walk_form(form)
+        match &tree.item {
+            TokenTree::Iden(iden)
+                if ResIden::try_new(iden).is_none() && !is_field(trees, index, record) =>
+            {
+                found.insert(iden.to_string());
+            }
+            TokenTree::Form(inner) | TokenTree::List(inner) => patterns(inner, false, found),
+            TokenTree::Block(lines) => {
+                let record = is_record(lines);
+                for line in lines {
+                    patterns(&line.item, record, found);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Renames identifiers, leaving field names as they are.
+/// Identifiers in `restores` are always renamed,
+/// as they were passed to the macro under a different name.
+fn rename(
+    trees: &mut [Spanned<TokenTree>],
+    renames: &HashMap<String, String>,
+    restores: &HashMap<String, String>,
+    record: bool,
+) {
+    for index in 0..trees.len() {
+        let field = is_field(trees, index, record);
+
+        match &mut trees[index].item {
+            TokenTree::Iden(iden) => {
+                if let Some(original) = restores.get(iden.as_str()) {
+                    *iden = original.to_string();
+                } else if let Some(renamed) = renames.get(iden.as_str()).filter(|_| !field) {
+                    *iden = renamed.to_string();
+                }
+            }
+            TokenTree::Form(inner) | TokenTree::List(inner) => {
+                rename(inner, renames, restores, false)
+            }
+            TokenTree::Block(lines) => {
+                let record = is_record(lines);
+                for line in lines.iter_mut() {
+                    rename(&mut line.item, renames, restores, record);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Replaces pattern variables with the token trees they matched,
+/// and respans everything else to where the syntax rule was used.
+fn substitute(
+    trees: &mut [Spanned<TokenTree>],
+    bindings: &HashMap<String, Spanned<TokenTree>>,
+    span: &Span,
+    record: bool,
+) {
+    for index in 0..trees.len() {
+        let field = is_field(trees, index, record);
+        let tree = &mut trees[index];
+
+        match &mut tree.item {
+            TokenTree::Iden(iden) if !field && bindings.contains_key(iden.as_str()) => {
+                *tree = bindings[iden.as_str()].clone();
+                continue;
+            }
+            TokenTree::Form(inner) | TokenTree::List(inner) => {
+                substitute(inner, bindings, span, false)
+            }
+            TokenTree::Block(lines) => {
+                let record = is_record(lines);
+                for line in lines.iter_mut() {
+                    substitute(&mut line.item, bindings, span, record);
+                    line.span = span.clone();
+                }
+            }
+            _ => (),
+        }
+        tree.span = span.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::Source, compiler::read};
+
+    fn expand(source: &str) -> Result<Vec<TokenTrees>, Diagnostics> {
+        let tree = Expander::expand(read(Source::source(source))?)?;
+        match tree.item {
+            TokenTree::Block(lines) => Ok(lines.into_iter().map(|line| line.item).collect()),
+            _ => unreachable!("Programs are always blocks"),
+        }
+    }
+
+    fn idens(trees: &TokenTrees) -> Vec<String> {
+        let mut found = vec![];
+        for tree in trees {
+            match &tree.item {
+                TokenTree::Iden(iden) => found.push(iden.to_string()),
+                TokenTree::Form(inner) | TokenTree::List(inner) => found.extend(idens(inner)),
+                TokenTree::Block(lines) => lines
+                    .iter()
+                    .for_each(|line| found.extend(idens(&line.item))),
+                _ => (),
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn expands() {
+        let lines = expand("macro flip = [a, op, b] -> [b, op, a]\nflip 1 - 2").unwrap();
+        assert_eq!(lines[0][0].item, TokenTree::Lit(Lit::Unit));
+        assert_eq!(lines[1][0].item, TokenTree::Lit(Lit::Integer(2)));
+        assert!(lines[1][1].span.expansion().is_some());
+    }
+
+    #[test]
+    fn hygiene() {
+        let source = "macro twice = [x] -> [Block [[Iden \"t\", Op \"=\", x], [Iden \"t\", Op \"+\", Iden \"t\"]]]\n\
+            twice t\n\
+            twice (n.t)";
+        let lines = expand(source).unwrap();
+
+        // the `t` passed in keeps its name, the one introduced does not
+        let first = idens(&lines[1]);
+        assert_eq!(first.iter().filter(|iden| *iden == "t").count(), 1);
+        assert!(first
+            .iter()
+            .all(|iden| iden == "t" || iden.starts_with("t#")));
+
+        // field names are not renamed
+        assert!(idens(&lines[2]).contains(&"t".to_string()));
+    }
+
+    #[test]
+    fn scoped() {
+        let source = "{ macro one = _ -> [Lit 1]\none () }\n\
+            one ()\n\
+            { syntax 'zero { 0 }\nzero }\n\
+            zero";
+        let lines = expand(source).unwrap();
+
+        // macros and rules are used inside the block they are defined in
+        for line in [&lines[0], &lines[2]] {
+            assert_eq!(idens(line), Vec::<String>::new());
+        }
+
+        // but are not visible after it
+        assert_eq!(idens(&lines[1]), vec!["one".to_string()]);
+        assert_eq!(idens(&lines[3]), vec!["zero".to_string()]);
+    }
+
+    #[test]
+    fn syntax_rules() {
+        let source = "syntax 'double x { y = x; y + y }\n\
+            syntax 'double 'it { 0 }\n\
+            double y\n\
+            double it";
+        let lines = expand(source).unwrap();
+
+        // the `y` bound by the rule is renamed, the one passed in is not
+        let first = idens(&lines[2]);
+        assert_eq!(first.iter().filter(|iden| *iden == "y").count(), 1);
+        assert!(lines[2][0].span.expansion().is_some());
+
+        // the more specific rule is used
+        assert_eq!(idens(&lines[3]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(expand("syntax a b { a }").is_err());
+        assert!(expand("syntax 'a b b { b }").is_err());
+        assert!(expand("syntax 'a b { b }\nsyntax c 'd { c }").is_err());
+        assert!(expand("syntax 'a b { b }\nsyntax 'a 'b { b }").is_ok());
+    }
+
+    #[test]
+    fn not_token_tree() {
+        assert!(expand("macro bad = _ -> [1]\nbad").is_err());
+        assert!(expand("macro\nx").is_err());
     }
 }
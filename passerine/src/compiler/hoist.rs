@@ -1,13 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    common::span::{Span, Spanned},
-    compiler::syntax::{Note, Syntax},
+    common::{
+        lit::Lit,
+        span::{Span, Spanned},
+    },
+    compiler::{
+        lint::{Usage, Warning},
+        syntax::{Diagnostics, Syntax},
+    },
     construct::{
         scope::Scope,
         symbol::{SharedSymbol, SymbolTable, UniqueSymbol},
-        tree::{Base, Lambda, Pattern, ScopedLambda, CST, SST},
+        tree::{Base, BinOp, Fields, Lambda, Pattern, ScopedLambda, CST, SST},
     },
+    explain::Code,
 };
 
 // TODO: hoisting before expansion??
@@ -28,6 +35,7 @@ use crate::{
 /// 1. Local and nonlocal variables in each scope.
 /// 2. All variables declared.
 /// 3. Variables that have been used but not declared.
+#[derive(Clone)]
 pub struct Hoister {
     /// The unique local symbols in the current scope.
     scopes: Vec<Scope>,
@@ -37,16 +45,25 @@ pub struct Hoister {
     /// Keeps track of variables that were referenced before
     /// assignment.
     unresolved_hoists: HashMap<SharedSymbol, Spanned<UniqueSymbol>>,
+    /// Keeps track of how each variable is used, for lints.
+    usage: Usage,
+}
+
+impl Default for Hoister {
+    fn default() -> Hoister {
+        Hoister::new()
+    }
 }
 
 impl Hoister {
     /// Creates a new hoisted in a root scope.
     /// Note that the hoister will always have a root scope.
-    fn new() -> Hoister {
+    pub fn new() -> Hoister {
         Hoister {
             scopes: vec![Scope::new()],
             symbol_table: SymbolTable::new(),
             unresolved_hoists: HashMap::new(),
+            usage: Usage::default(),
         }
     }
 
@@ -58,32 +75,85 @@ impl Hoister {
     pub fn hoist(
         tree: Spanned<CST>,
         symbols: HashMap<String, SharedSymbol>,
-    ) -> Result<(Spanned<SST>, Scope), Syntax> {
+    ) -> Result<(Spanned<SST>, Scope), Diagnostics> {
+        let (sst, scope, _) = Hoister::hoist_with_warnings(tree, symbols)?;
+        Ok((sst, scope))
+    }
+
+    /// Like `hoist`, but also returns the warnings
+    /// found by checking how each variable is used.
+    /// Variables named with a leading underscore are not checked.
+    pub fn hoist_with_warnings(
+        tree: Spanned<CST>,
+        symbols: HashMap<String, SharedSymbol>,
+    ) -> Result<(Spanned<SST>, Scope, Vec<Warning>), Diagnostics> {
         let mut hoister = Hoister::new();
 
         let sst = hoister.walk(tree)?;
-        let scope = hoister.scopes.pop().unwrap();
+        hoister.resolved()?;
+
+        let names = symbols
+            .into_iter()
+            .map(|(name, symbol)| (symbol, name))
+            .collect::<HashMap<_, _>>();
+        let warnings = hoister.usage.warnings(|unique_symbol| {
+            let name = names.get(&hoister.symbol_table.name(&unique_symbol))?;
+            // names with a `#` are made up by the compiler
+            match name.starts_with('_') || name.contains('#') {
+                true => None,
+                false => Some(name.clone()),
+            }
+        });
 
-        if !hoister.unresolved_hoists.is_empty() {
-            let num_unresolved = hoister.unresolved_hoists.len();
+        let scope = hoister.scopes.pop().unwrap();
+        Ok((sst, scope, warnings))
+    }
 
-            let mut error = Syntax::error_no_note(&format!(
-                "{} variable{} referenced before assignment",
-                num_unresolved,
-                if num_unresolved == 1 { "" } else { "s" }
-            ));
+    /// Hoists one entry of a `Session`.
+    /// Variables declared at the root of earlier entries stay in scope,
+    /// so the returned root scope extends the root scope of the last entry.
+    pub fn hoist_entry(
+        &mut self,
+        tree: Spanned<CST>,
+    ) -> Result<(Spanned<SST>, Scope), Diagnostics> {
+        let sst = self.walk(tree)?;
+        self.resolved()?;
+        Ok((sst, self.borrow_local_scope().clone()))
+    }
 
-            // TODO: sort by occurence, earliest first?
-            for (_symbol, spanned) in hoister.unresolved_hoists.iter() {
-                // TODO: hints to correct to similar names, etc.
-                // dbg!(&spanned.span);
-                error = error.add_note(Note::new(spanned.span.clone()));
-            }
+    /// Raises an error if any variables were used but never declared.
+    fn resolved(&self) -> Result<(), Diagnostics> {
+        self.resolved_since(&HashSet::new())
+    }
 
-            Err(error)
-        } else {
-            Ok((sst, scope))
-        }
+    /// Raises an error for each variable that was used but never declared,
+    /// ignoring those that were already unresolved beforehand.
+    /// Errors are ordered by where the variable was first used.
+    fn resolved_since(&self, before: &HashSet<SharedSymbol>) -> Result<(), Diagnostics> {
+        let mut unresolved = self
+            .unresolved_hoists
+            .iter()
+            .filter(|(symbol, _)| !before.contains(symbol))
+            .map(|(_, spanned)| &spanned.span)
+            .collect::<Vec<_>>();
+        unresolved.sort_by_key(|span| (span.path(), span.end()));
+
+        // TODO: hints to correct to similar names, etc.
+        Diagnostics::check(
+            unresolved
+                .into_iter()
+                .map(|span| {
+                    Syntax::error(
+                        Code::UnassignedVariable,
+                        &format!(
+                            "The variable `{}` is referenced before it is assigned",
+                            span.contents()
+                        ),
+                        span,
+                    )
+                })
+                .collect(),
+        )
     }
 
     /// Enters a new scope, called when entering a new
@@ -110,8 +180,6 @@ impl Hoister {
                     scope.locals.remove(&local);
                 }
             }
-            dbg!(&scope);
-            dbg!(&self.unresolved_hoists);
             Some(scope)
         } else {
             unreachable!("no scopes left on stack?");
@@ -134,25 +202,41 @@ impl Hoister {
     /// Walks a `CST` to produce an `SST`.
     /// This is fairly standard - hoisting happens in
     /// `self.assign`, `self.lambda`, and `self.symbol`.
-    fn walk(&mut self, tree: Spanned<CST>) -> Result<Spanned<SST>, Syntax> {
+    fn walk(&mut self, tree: Spanned<CST>) -> Result<Spanned<SST>, Diagnostics> {
         let sst: SST = match tree.item {
             CST::Base(Base::Lit(data)) => SST::Base(Base::Lit(data)),
             CST::Base(Base::Symbol(name)) => self.symbol(name, tree.span.clone()),
             CST::Base(Base::Block(block)) => self.block(block)?,
-            // TODO: hoist as well
-            CST::Base(Base::Label(name)) => {
-                todo!()
-                // SST::label(
-                //     // TODO: change this to the following lines after types:
-                //     self.resolve_symbol(name, cst.span.clone()),
-                // )
-            }
+            // labels are not scoped, they're identified by name.
+            CST::Base(Base::Label(name)) => SST::Base(Base::Label(name)),
             CST::Base(Base::Tuple(tuple)) => self.tuple(tuple)?,
+            CST::Base(Base::Record(fields)) => SST::Base(Base::Record(self.fields(fields)?)),
+            CST::Base(Base::List(items, rest)) => self.list(items, rest)?,
+            CST::Base(Base::Map(entries)) => self.map(entries)?,
+            CST::Base(Base::Field(record, name)) => {
+                SST::Base(Base::field(self.walk(*record)?, name))
+            }
+            CST::Base(Base::Update(record, fields)) => {
+                SST::Base(Base::update(self.walk(*record)?, self.fields(fields)?))
+            }
             CST::Base(Base::Assign(pattern, expression)) => self.assign(pattern, *expression)?,
             CST::Lambda(Lambda { arg, body }) => self.lambda(arg, *body)?,
             CST::Base(Base::Call(fun, arg)) => self.call(*fun, *arg)?,
-            CST::Base(Base::Module(_)) => todo!(),
-            CST::Base(Base::Effect(_)) => todo!(),
+            CST::Base(Base::BinOp(op, left, right)) => self.bin_op(op, *left, *right)?,
+            CST::Base(Base::UnOp(op, operand)) => SST::Base(Base::un_op(op, self.walk(*operand)?)),
+            CST::Base(Base::If(cond, then, else_)) => self.if_(*cond, *then, *else_)?,
+            CST::Base(Base::Match(value, arms)) => self.match_(*value, arms)?,
+            CST::Base(Base::Module(body)) => self.module(*body, &tree.span)?,
+            CST::Base(Base::Effect(name, payload)) => {
+                SST::Base(Base::effect(name, self.walk(*payload)?))
+            }
+            CST::Base(Base::Handle(body, handler, effects)) => {
+                self.handle(*body, *handler, effects)?
+            }
+            CST::Base(Base::Is(expression, ty)) => {
+                self.usage.annotate(&ty);
+                SST::Base(Base::is(self.walk(*expression)?, ty))
+            }
         };
 
         return Ok(Spanned::new(sst, tree.span));
@@ -167,22 +251,32 @@ impl Hoister {
         declare: bool,
     ) -> Spanned<Pattern<UniqueSymbol>> {
         let item = match pattern.item {
-            Pattern::Symbol(name) => Pattern::Symbol(self.resolve_assign(name, declare)),
+            Pattern::Symbol(name) => {
+                Pattern::Symbol(self.resolve_assign(name, &pattern.span, declare))
+            }
             Pattern::Lit(l) => Pattern::Lit(l),
-            Pattern::Label(n, p) => Pattern::Label(
-                // TODO: This is temoprary. Makes first use the definition.
-                // Once we have types, change the following line to:
-                // self.resolve_symbol(n, pattern.span.clone()),
-                // until then, this will do:
-                Spanned::new(self.resolve_assign(n.item, false), n.span),
-                Box::new(self.walk_pattern(*p, declare)),
-            ),
+            Pattern::Label(n, p) => Pattern::label(n, self.walk_pattern(*p, declare)),
             Pattern::Tuple(t) => Pattern::Tuple(
                 t.into_iter()
                     .map(|c| self.walk_pattern(c, declare))
                     .collect::<Vec<_>>(),
             ),
+            Pattern::Record(r) => Pattern::Record(
+                r.into_iter()
+                    .map(|(n, p)| (n, self.walk_pattern(p, declare)))
+                    .collect::<Vec<_>>(),
+            ),
+            Pattern::List(l, r) => Pattern::list(
+                l.into_iter()
+                    .map(|c| self.walk_pattern(c, declare))
+                    .collect::<Vec<_>>(),
+                r.map(|r| self.walk_pattern(*r, declare)),
+            ),
             Pattern::Chain(_) => todo!("Chained Patterns not yet implemented"),
+            Pattern::Is(p, ty) => {
+                self.usage.annotate(&ty);
+                Pattern::is(self.walk_pattern(*p, declare), ty)
+            }
         };
 
         return Spanned::new(item, pattern.span);
@@ -203,6 +297,22 @@ impl Hoister {
         return None;
     }
 
+    /// Looks to see whether a name is defined as a local
+    /// in any scope enclosing the current one.
+    fn enclosing_symbol(&self, name: SharedSymbol) -> Option<UniqueSymbol> {
+        let enclosing = &self.scopes[..self.scopes.len() - 1];
+        for scope in enclosing.iter().rev() {
+            for local in scope.locals.items().iter() {
+                let unresolved = self.unresolved_hoists.values().any(|u| u.item == *local);
+                if self.symbol_table.name(local) == name && !unresolved {
+                    return Some(*local);
+                }
+            }
+        }
+
+        return None;
+    }
+
     /// Looks to see whether a name is used as a nonlocal in
     /// the current scope.
     fn nonlocal_symbol(&self, name: SharedSymbol) -> Option<UniqueSymbol> {
@@ -239,7 +349,7 @@ impl Hoister {
     /// scope, use it. 2. If this variable is defined in
     /// an enclosing scope, capture it and use it. 3. If
     /// this variable is not defined, return `None`.
-    fn try_resolve(&mut self, name: SharedSymbol) -> Option<UniqueSymbol> {
+    fn try_resolve(&mut self, name: SharedSymbol, span: &Span) -> Option<UniqueSymbol> {
         if let Some(unique_symbol) = self.local_symbol(name) {
             return Some(unique_symbol);
         }
@@ -248,10 +358,11 @@ impl Hoister {
         }
 
         if let Some(scope) = self.exit_scope() {
-            let resolved = self.try_resolve(name);
+            let resolved = self.try_resolve(name, span);
             self.reenter_scope(scope);
             if let Some(unique_symbol) = resolved {
                 self.local_scope().nonlocals.push(unique_symbol);
+                self.usage.capture(unique_symbol, span);
                 return Some(unique_symbol);
             }
         }
@@ -265,7 +376,7 @@ impl Hoister {
     /// this function will define it in all lexical scopes
     /// once this variable is discovered, we remove the
     /// definitions in all scopes below this one.
-    fn resolve_assign(&mut self, name: SharedSymbol, redeclare: bool) -> UniqueSymbol {
+    fn resolve_assign(&mut self, name: SharedSymbol, span: &Span, redeclare: bool) -> UniqueSymbol {
         // if we've seen the symbol before but don't know where it's
         // defined
         if let Some(unique_symbol) = self.unresolved_hoists.get(&name) {
@@ -281,6 +392,8 @@ impl Hoister {
                 self.uncapture_all(unique_symbol);
                 self.unresolved_hoists.remove(&name);
                 self.local_scope().locals.push(unique_symbol);
+                self.usage.declare(unique_symbol, span, redeclare);
+                self.usage.unhoist(unique_symbol, self.scopes.len());
                 return unique_symbol;
             }
         }
@@ -289,7 +402,8 @@ impl Hoister {
         // we search backwards through scopes and build a hoisting
         // chain
         if !redeclare {
-            if let Some(unique_symbol) = self.try_resolve(name) {
+            if let Some(unique_symbol) = self.try_resolve(name, span) {
+                self.usage.reassign(unique_symbol, span);
                 return unique_symbol;
             }
         }
@@ -297,6 +411,12 @@ impl Hoister {
         // if we didn't find it by searching backwards, we declare
         // it in the current scope
         let unique_symbol = self.symbol_table.push(name);
+        self.usage.declare(unique_symbol, span, redeclare);
+        if redeclare {
+            if let Some(shadowed) = self.enclosing_symbol(name) {
+                self.usage.shadow(unique_symbol, shadowed);
+            }
+        }
         self.local_scope().locals.push(unique_symbol);
         return unique_symbol;
     }
@@ -313,13 +433,14 @@ impl Hoister {
         // if we haven't seen the symbol before,
         // we search backwards through scopes and build a hoisting
         // chain
-        if let Some(unique_symbol) = self.try_resolve(name) {
+        if let Some(unique_symbol) = self.try_resolve(name, &span) {
             return unique_symbol;
         }
 
         // if we didn't find it by searching backwards, we mark it
         // as unresolved
         let unique_symbol = self.symbol_table.push(name);
+        self.usage.hoist(unique_symbol, self.scopes.len(), &span);
         self.capture_all(unique_symbol);
         self.unresolved_hoists
             .insert(name, Spanned::new(unique_symbol, span));
@@ -335,11 +456,13 @@ impl Hoister {
         // if we are hoisting the variable,
         // mark the variable as being used before its lexical
         // definition
-        return SST::Base(Base::Symbol(self.resolve_symbol(name, span)));
+        let unique_symbol = self.resolve_symbol(name, span);
+        self.usage.read(unique_symbol);
+        return SST::Base(Base::Symbol(unique_symbol));
     }
 
     /// Walks a block, nothing fancy here.
-    fn block(&mut self, block: Vec<Spanned<CST>>) -> Result<SST, Syntax> {
+    fn block(&mut self, block: Vec<Spanned<CST>>) -> Result<SST, Diagnostics> {
         let mut expressions = vec![];
         for expression in block {
             expressions.push(self.walk(expression)?)
@@ -349,7 +472,7 @@ impl Hoister {
     }
 
     /// Walks a tuple, nothing fancy here.
-    fn tuple(&mut self, tuple: Vec<Spanned<CST>>) -> Result<SST, Syntax> {
+    fn tuple(&mut self, tuple: Vec<Spanned<CST>>) -> Result<SST, Diagnostics> {
         let mut expressions = vec![];
         for expression in tuple {
             expressions.push(self.walk(expression)?)
@@ -358,6 +481,45 @@ impl Hoister {
         Ok(SST::Base(Base::Tuple(expressions)))
     }
 
+    /// Walks each item in a list, then the list it is spread onto, if any.
+    fn list(
+        &mut self,
+        items: Vec<Spanned<CST>>,
+        rest: Option<Box<Spanned<CST>>>,
+    ) -> Result<SST, Diagnostics> {
+        let mut expressions = vec![];
+        for expression in items {
+            expressions.push(self.walk(expression)?)
+        }
+        let rest = match rest {
+            Some(rest) => Some(self.walk(*rest)?),
+            None => None,
+        };
+
+        Ok(SST::Base(Base::list(expressions, rest)))
+    }
+
+    /// Walks each key in a map, followed by its value.
+    fn map(&mut self, entries: Vec<(Spanned<CST>, Spanned<CST>)>) -> Result<SST, Diagnostics> {
+        let mut walked = vec![];
+        for (key, value) in entries {
+            walked.push((self.walk(key)?, self.walk(value)?));
+        }
+        Ok(SST::Base(Base::Map(walked)))
+    }
+
+    /// Walks the value of each field in a record.
+    fn fields(
+        &mut self,
+        fields: Fields<Spanned<CST>>,
+    ) -> Result<Fields<Spanned<SST>>, Diagnostics> {
+        let mut walked = vec![];
+        for (name, value) in fields {
+            walked.push((name, self.walk(value)?));
+        }
+        Ok(walked)
+    }
+
     /// Walks an assignment.
     /// Delegates to `walk_pattern` for capturing.
     /// Assignments can capture existing variables
@@ -365,10 +527,17 @@ impl Hoister {
         &mut self,
         pattern: Spanned<Pattern<SharedSymbol>>,
         expression: Spanned<CST>,
-    ) -> Result<SST, Syntax> {
+    ) -> Result<SST, Diagnostics> {
         let sst_pattern = self.walk_pattern(pattern, false);
         let sst_expression = self.walk(expression)?;
 
+        // the value is assigned once the expression is evaluated
+        let mut assigned = vec![];
+        spanned_symbols(&sst_pattern, &mut assigned);
+        for (unique_symbol, span) in assigned {
+            self.usage.write(unique_symbol, span);
+        }
+
         return Ok(SST::Base(Base::assign(sst_pattern, sst_expression)));
     }
 
@@ -380,7 +549,7 @@ impl Hoister {
         &mut self,
         pattern: Spanned<Pattern<SharedSymbol>>,
         expression: Spanned<CST>,
-    ) -> Result<SST, Syntax> {
+    ) -> Result<SST, Diagnostics> {
         self.enter_scope();
         let arg = self.walk_pattern(pattern, true);
         let body = Box::new(self.walk(expression)?);
@@ -389,10 +558,121 @@ impl Hoister {
         return Ok(SST::ScopedLambda(ScopedLambda { arg, body, scope }));
     }
 
+    /// Walks a module, whose body has its own scope,
+    /// as if it were the body of a function that takes `()`.
+    /// A module is evaluated as soon as it is defined,
+    /// so every variable it uses must already be declared.
+    fn module(&mut self, body: Spanned<CST>, span: &Span) -> Result<SST, Diagnostics> {
+        let before = self.unresolved_hoists.keys().copied().collect();
+        let arg = Spanned::new(Pattern::Lit(Lit::Unit), span.clone());
+        let lambda = Spanned::new(self.lambda(arg, body)?, span.clone());
+        self.resolved_since(&before)?;
+        Ok(SST::Base(Base::module(lambda)))
+    }
+
     /// Walks a function call.
-    fn call(&mut self, fun: Spanned<CST>, arg: Spanned<CST>) -> Result<SST, Syntax> {
+    fn call(&mut self, fun: Spanned<CST>, arg: Spanned<CST>) -> Result<SST, Diagnostics> {
         return Ok(SST::Base(Base::call(self.walk(fun)?, self.walk(arg)?)));
     }
+
+    /// Walks a builtin binary operation.
+    fn bin_op(
+        &mut self,
+        op: BinOp,
+        left: Spanned<CST>,
+        right: Spanned<CST>,
+    ) -> Result<SST, Diagnostics> {
+        let left = self.walk(left)?;
+        // the right side of a logical operator is not always evaluated
+        let is_logic = matches!(op, BinOp::And | BinOp::Or);
+        if is_logic {
+            self.usage.enter_branch();
+        }
+        let right = self.walk(right)?;
+        if is_logic {
+            self.usage.exit_branch();
+        }
+
+        return Ok(SST::Base(Base::bin_op(op, left, right)));
+    }
+
+    /// Walks an if expression.
+    /// Note that branches do not introduce a new scope.
+    fn if_(
+        &mut self,
+        cond: Spanned<CST>,
+        then: Spanned<CST>,
+        else_: Spanned<CST>,
+    ) -> Result<SST, Diagnostics> {
+        let cond = self.walk(cond)?;
+        self.usage.enter_branch();
+        let then = self.walk(then)?;
+        self.usage.exit_branch();
+        self.usage.enter_branch();
+        let else_ = self.walk(else_)?;
+        self.usage.exit_branch();
+
+        return Ok(SST::Base(Base::if_(cond, then, else_)));
+    }
+
+    /// Walks a match expression.
+    /// Each arm is a function, so it gets its own scope,
+    /// but the variables its pattern binds are not arguments.
+    fn match_(&mut self, value: Spanned<CST>, arms: Vec<Spanned<CST>>) -> Result<SST, Diagnostics> {
+        let value = self.walk(value)?;
+        let mut scoped_arms = vec![];
+        for arm in arms {
+            let arm = self.walk(arm)?;
+            if let SST::ScopedLambda(ScopedLambda { arg, .. }) = &arm.item {
+                let mut bound = vec![];
+                spanned_symbols(arg, &mut bound);
+                for (unique_symbol, _) in bound {
+                    self.usage.bind(unique_symbol);
+                }
+            }
+            scoped_arms.push(arm);
+        }
+
+        return Ok(SST::Base(Base::match_(value, scoped_arms)));
+    }
+
+    /// Walks a handle expression.
+    /// Both the body and the handler are functions,
+    /// so each gets its own scope.
+    fn handle(
+        &mut self,
+        body: Spanned<CST>,
+        handler: Spanned<CST>,
+        effects: Vec<String>,
+    ) -> Result<SST, Diagnostics> {
+        return Ok(SST::Base(Base::handle(
+            self.walk(body)?,
+            self.walk(handler)?,
+            effects,
+        )));
+    }
+}
+
+/// Collects the symbols a pattern binds, along with where they are bound.
+fn spanned_symbols<'a>(
+    pattern: &'a Spanned<Pattern<UniqueSymbol>>,
+    found: &mut Vec<(UniqueSymbol, &'a Span)>,
+) {
+    match &pattern.item {
+        Pattern::Symbol(unique_symbol) => found.push((*unique_symbol, &pattern.span)),
+        Pattern::Lit(_) => (),
+        Pattern::Label(_, inner) | Pattern::Is(inner, _) => spanned_symbols(inner, found),
+        Pattern::Tuple(items) | Pattern::Chain(items) => {
+            items.iter().for_each(|item| spanned_symbols(item, found))
+        }
+        Pattern::Record(fields) => fields
+            .iter()
+            .for_each(|(_, field)| spanned_symbols(field, found)),
+        Pattern::List(items, rest) => items
+            .iter()
+            .chain(rest.as_deref())
+            .for_each(|item| spanned_symbols(item, found)),
+    }
 }
 
 #[cfg(test)]
@@ -430,6 +710,26 @@ mod test_super {
         assert!(test_source("x; () -> x; x = 0").not());
     }
 
+    #[test]
+    fn every_unresolved() {
+        let tokens = Lexer::lex(Source::source("a = b\nc = d + a\nf = () -> e")).unwrap();
+        let (ast, symbols) = Parser::parse(Reader::read(tokens).unwrap()).unwrap();
+        let errors = Hoister::hoist(Desugarer::desugar(ast), symbols).unwrap_err();
+        let reasons = errors
+            .errors()
+            .iter()
+            .map(|error| error.reason.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                "The variable `b` is referenced before it is assigned",
+                "The variable `d` is referenced before it is assigned",
+                "The variable `e` is referenced before it is assigned",
+            ]
+        );
+    }
+
     #[test]
     fn nested_capture() {
         assert!(test_source("_ -> { x = _ -> pi; pi = 3 }; pi = 3.14"));
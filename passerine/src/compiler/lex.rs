@@ -13,10 +13,14 @@ use crate::{
     },
     compiler::syntax::{Note, Syntax},
     construct::token::{Delim, Token, Tokens},
+    explain::Code,
 };
 
 const OP_CHARS: &str = "!$%&*+,-./:<=>?@^|~";
 
+/// Operator characters that may also be used as prefix operators.
+const PREFIX_CHARS: &str = "-!";
+
 macro_rules! RemainingIter {
     () => { Peekable<impl Iterator<Item = char>> };
 }
@@ -157,6 +161,7 @@ impl Lexer {
                     o => {
                         return Err(
                             Syntax::error_with_note(
+                                Code::InvalidEscape,
                                 &format!("Unknown escape code `\\{}` in string literal", o),
                                 Note::new_with_hint(
                                     "To include a single backslash `\\`, escape it first: `\\\\`",
@@ -176,6 +181,7 @@ impl Lexer {
         }
 
         Err(Syntax::error(
+            Code::UnterminatedString,
             "Unexpected end of source while parsing string literal",
             &Span::point(&self.source, self.index + len),
         ))
@@ -194,6 +200,7 @@ impl Lexer {
 
         let integer = i64::from_str_radix(&self.grab_from_index(len)[2..], radix).map_err(|_| {
             Syntax::error(
+                Code::InvalidNumber,
                 "Integer literal too large to fit in a signed 64-bit integer",
                 // hate the + 2 hack
                 // + 2 chars to take the `0?` into account
@@ -241,6 +248,7 @@ impl Lexer {
                     .1;
                 let float = f64::from_str(self.grab_from_index(len)).map_err(|_| {
                     Syntax::error(
+                        Code::InvalidNumber,
                         "Float literal does not fit in a 64-bit floating-point number",
                         &Span::new(&self.source, self.index, len),
                     )
@@ -249,6 +257,7 @@ impl Lexer {
             }
             // There's an 'e', so we parse using scientific notation
             Some('e') => Err(Syntax::error(
+                Code::InvalidNumber,
                 "Scientific notation for floating-point is WIP!",
                 &Span::point(&self.source, self.index),
             )),
@@ -256,6 +265,7 @@ impl Lexer {
             _ => {
                 let integer = i64::from_str(self.grab_from_index(len)).map_err(|_| {
                     Syntax::error(
+                        Code::InvalidNumber,
                         "Decimal literal too large to fit in a signed 64-bit integer",
                         &Span::new(&self.source, self.index, len),
                     )
@@ -265,6 +275,27 @@ impl Lexer {
         }
     }
 
+    /// Operators are made of runs of operator characters,
+    /// so in `x*-1` the lexer would see a single operator, `*-`.
+    /// If a run of operator characters ends in a prefix operator,
+    /// like `-` or `!`, and is directly followed by an operand,
+    /// the prefix operator is split off to be lexed on its own.
+    /// Note that `a -1` is still lexed as `a`, `-`, `1`;
+    /// it is up to the parser to decide that this is a subtraction.
+    fn prefix_split(&self, op: String, len: usize) -> (Token, usize) {
+        let followed = self.source.contents[self.index + len..]
+            .chars()
+            .next()
+            .is_some_and(|n| !n.is_whitespace());
+
+        match op.chars().last() {
+            Some(last) if len > 1 && followed && PREFIX_CHARS.contains(last) => {
+                (Token::Op(op[..len - 1].to_string()), len - 1)
+            },
+            _ => (Token::Op(op), len),
+        }
+    }
+
     /// Parses the next token.
     /// Expects all whitespace and comments to be stripped.
     fn next_token(&mut self) -> Result<Spanned<Token>, Syntax> {
@@ -291,6 +322,9 @@ impl Lexer {
             '}' => (Token::Close(Delim::Curly), 1),
             ']' => (Token::Close(Delim::Square), 1),
 
+            // Marks a keyword in a syntax rule, like `'then`
+            '\'' => (Token::Op("'".to_string()), 1),
+
             // Label
             c if c.is_alphabetic() && c.is_uppercase() => {
                 self.take_while(
@@ -342,15 +376,17 @@ impl Lexer {
             // don't have both a list and `is_ascii_punctuation`
             // Op
             c if OP_CHARS.contains(c) => {
-                self.take_while(
+                let (op, len) = self.take_while(
                     &mut once(c).chain(remaining).peekable(),
-                    |s| Token::Op(s.to_string()),
+                    |s| s.to_string(),
                     |n| OP_CHARS.contains(n),
-                )
+                );
+                self.prefix_split(op, len)
             },
 
             // Unrecognized char
             unknown => return Err(Syntax::error(
+                Code::UnknownCharacter,
                 &format!(
                     "Hmm... The character `{}` is not recognized in this context - check for encoding issues or typos",
                     unknown,
@@ -440,6 +476,31 @@ mod test {
         assert_eq!(result[6].item, Token::Lit(Lit::Unit));
     }
 
+    #[test]
+    fn prefix_operators() {
+        let result = Lexer::lex(Source::source("2*-1 a,!b c - -d e-f")).unwrap().item;
+        let ops = result.iter()
+            .filter_map(|token| match &token.item {
+                Token::Op(op) => Some(op.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ops, vec!["*", "-", ",", "!", "-", "-", "-"]);
+    }
+
+    #[test]
+    fn keyword_marker() {
+        let result = Lexer::lex(Source::source("'then x '=")).unwrap().item;
+        let tokens = result.into_iter().map(|token| token.item).collect::<Vec<_>>();
+        assert_eq!(tokens, vec![
+            Token::Op("'".to_string()),
+            Token::Iden("then".to_string()),
+            Token::Iden("x".to_string()),
+            Token::Op("'".to_string()),
+            Token::Op("=".to_string()),
+        ]);
+    }
+
     #[test]
     fn unclosed_string() {
         let result = Lexer::lex(Source::source("\"asdf\"\"qwerty"));
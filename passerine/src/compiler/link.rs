@@ -0,0 +1,525 @@
+use std::rc::Rc;
+
+use crate::{
+    common::{
+        label::ROOT,
+        lit::Lit,
+        module::{Module, ENTRY_POINT},
+        span::{Span, Spanned},
+        Source,
+    },
+    compiler::{
+        expand,
+        syntax::{Diagnostics, Note, Syntax},
+    },
+    construct::token::{ResIden, TokenTree, TokenTrees},
+    explain::Code,
+};
+
+/// A module in the tree being linked.
+struct Unit {
+    /// The names of the modules leading from the root to this one,
+    /// which is empty for the root.
+    path: Vec<String>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    source: Rc<Source>,
+}
+
+/// A module after its macros are expanded,
+/// with each `use` replaced by an assignment.
+struct Linked {
+    lines: Vec<Spanned<TokenTrees>>,
+    span: Span,
+    /// The modules this module uses, and where they are used.
+    uses: Vec<(usize, Span)>,
+}
+
+/// The path of the module each source in a linked program belongs to,
+/// like `net.http`, or empty for the root module.
+/// Labels are interned under the module they are written in,
+/// so the same label in two modules is two different labels.
+/// Cloning produces a handle to the same paths.
+#[derive(Debug, Clone, Default)]
+pub struct Paths {
+    sources: Rc<Vec<(Rc<Source>, String)>>,
+}
+
+impl Paths {
+    /// The path of the module some code is in,
+    /// or the root module if the code is not in a linked source.
+    pub fn module(&self, span: &Span) -> &str {
+        self.sources
+            .iter()
+            .find(|(source, _)| Rc::ptr_eq(source, span.source()))
+            .map(|(_, path)| path.as_str())
+            .unwrap_or(ROOT)
+    }
+}
+
+/// Links a tree of modules into a single program.
+///
+/// A module can use its children and its siblings,
+/// by writing `use` and the path to the module on its own line,
+/// like `use math` or `use math.vector`.
+/// This assigns the module to a variable with the same name as the module,
+/// like `vector` for `use math.vector`.
+///
+/// Each module other than the root is compiled as a `mod`,
+/// which is a record of its top-level variables,
+/// and is evaluated once, before any module that uses it.
+/// Modules that are never used are still compiled and evaluated,
+/// before the root, which is evaluated last.
+/// Modules can not use each other in a cycle.
+///
+/// A label belongs to the module it is written in,
+/// so `Error` in one module does not match `Error` in another.
+/// Effects are shared by all modules.
+pub struct Linker {
+    units: Vec<Unit>,
+}
+
+impl Linker {
+    /// Links a module and all modules nested in it,
+    /// returning the program along with the path of each module in it.
+    pub fn link(module: &Module) -> Result<(Spanned<TokenTree>, Paths), Diagnostics> {
+        let mut linker = Linker { units: vec![] };
+        linker.flatten(module, None, vec![]);
+
+        let mut linked = vec![];
+        for index in 0..linker.units.len() {
+            linked.push(linker.expand(index)?);
+        }
+
+        let order = linker.order(&linked)?;
+        Ok((linker.program(linked, order), linker.paths()))
+    }
+
+    fn paths(&self) -> Paths {
+        let sources = self
+            .units
+            .iter()
+            .map(|unit| (unit.source.clone(), unit.path.join(".")))
+            .collect();
+        Paths {
+            sources: Rc::new(sources),
+        }
+    }
+
+    /// Numbers each module in the tree, parents before children.
+    fn flatten(&mut self, module: &Module, parent: Option<usize>, path: Vec<String>) -> usize {
+        let index = self.units.len();
+        self.units.push(Unit {
+            path: path.clone(),
+            parent,
+            children: vec![],
+            source: module.source().clone(),
+        });
+
+        for child in module.children() {
+            let mut child_path = path.clone();
+            child_path.push(child.name().to_string());
+            let child = self.flatten(child, Some(index), child_path);
+            self.units[index].children.push(child);
+        }
+
+        index
+    }
+
+    /// The name of a module, as shown in errors.
+    fn name(&self, index: usize) -> String {
+        match self.units[index].path.is_empty() {
+            true => ENTRY_POINT.to_string(),
+            false => self.units[index].path.join("."),
+        }
+    }
+
+    /// The name of the variable a module is assigned to.
+    /// Names can not contain a `#`, so this can not clash with any other variable.
+    fn variable(&self, index: usize) -> String {
+        format!("#{}", self.name(index))
+    }
+
+    fn child(&self, index: usize, name: &str) -> Option<usize> {
+        self.units[index]
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.units[*child].path.last().map(|last| last.as_str()) == Some(name))
+    }
+
+    /// Reads a module and expands its macros,
+    /// then replaces each `use` with an assignment of the module it uses.
+    fn expand(&self, index: usize) -> Result<Linked, Diagnostics> {
+        let tree = expand(self.units[index].source.clone())?;
+        let lines = match tree.item {
+            TokenTree::Block(lines) => lines,
+            _ => unreachable!("Programs are always blocks"),
+        };
+
+        let mut uses = vec![];
+        let mut linked = vec![];
+        for line in lines {
+            let is_use = matches!(
+                line.item.first().map(|tree| &tree.item),
+                Some(TokenTree::Iden(iden)) if ResIden::try_new(iden) == Some(ResIden::Use)
+            );
+            if !is_use {
+                linked.push(line);
+                continue;
+            }
+
+            let path = Linker::path(&line.item[1..], &line.span)?;
+            let used = self.resolve(index, &path, &line.span)?;
+            uses.push((used, line.span.clone()));
+
+            let name = path.last().unwrap().to_string();
+            let assign = vec![
+                Spanned::new(TokenTree::Iden(name), line.span.clone()),
+                Spanned::new(TokenTree::Op("=".to_string()), line.span.clone()),
+                Spanned::new(TokenTree::Iden(self.variable(used)), line.span.clone()),
+            ];
+            linked.push(Spanned::new(assign, line.span));
+        }
+
+        Ok(Linked {
+            lines: linked,
+            span: tree.span,
+            uses,
+        })
+    }
+
+    /// Parses the path after `use`, like `math.vector`.
+    fn path(trees: &[Spanned<TokenTree>], span: &Span) -> Result<Vec<String>, Syntax> {
+        let error = || {
+            Syntax::error(
+                Code::InvalidUse,
+                "Expected the path of a module after `use`, like `use math.vector`",
+                span,
+            )
+        };
+
+        let mut path = vec![];
+        let mut trees = trees.iter().map(|tree| &tree.item);
+        loop {
+            match trees.next() {
+                Some(TokenTree::Iden(name)) => path.push(name.to_string()),
+                _ => return Err(error()),
+            }
+            match trees.next() {
+                None => return Ok(path),
+                Some(TokenTree::Op(op)) if op == "." => (),
+                _ => return Err(error()),
+            }
+        }
+    }
+
+    /// Finds the module a path refers to.
+    /// The first name in the path is a child of the module using it,
+    /// or else a sibling; the rest are each a child of the one before.
+    fn resolve(&self, index: usize, path: &[String], span: &Span) -> Result<usize, Syntax> {
+        let sibling = |name: &str| {
+            self.units[index]
+                .parent
+                .and_then(|parent| self.child(parent, name))
+        };
+        let mut found = self.child(index, &path[0]).or_else(|| sibling(&path[0]));
+        for name in path[1..].iter() {
+            found = found.and_then(|module| self.child(module, name));
+        }
+
+        found.ok_or_else(|| {
+            Syntax::error(
+                Code::ModuleNotFound,
+                &format!(
+                    "The module `{}` could not be found, as a child or a sibling of the module `{}`",
+                    path.join("."),
+                    self.name(index),
+                ),
+                span,
+            )
+        })
+    }
+
+    /// Orders the modules so each comes after the modules it uses,
+    /// with the root last.
+    fn order(&self, linked: &[Linked]) -> Result<Vec<usize>, Syntax> {
+        let mut order = vec![];
+        let mut done = vec![false; self.units.len()];
+        let mut using = vec![];
+
+        for index in (1..self.units.len()).chain([0]) {
+            self.visit(index, linked, &mut using, &mut done, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    /// Visits each module a module uses before the module itself.
+    /// `using` holds the modules being visited,
+    /// each of which uses the one after it.
+    fn visit(
+        &self,
+        index: usize,
+        linked: &[Linked],
+        using: &mut Vec<usize>,
+        done: &mut [bool],
+        order: &mut Vec<usize>,
+    ) -> Result<(), Syntax> {
+        if done[index] {
+            return Ok(());
+        }
+        if let Some(start) = using.iter().position(|module| *module == index) {
+            return Err(self.cycle(&using[start..], index, linked));
+        }
+
+        using.push(index);
+        for (used, _) in linked[index].uses.iter() {
+            self.visit(*used, linked, using, done, order)?;
+        }
+        using.pop();
+
+        done[index] = true;
+        order.push(index);
+        Ok(())
+    }
+
+    /// Reports modules that use each other in a cycle,
+    /// with a note at each `use` in the cycle.
+    fn cycle(&self, cycle: &[usize], index: usize, linked: &[Linked]) -> Syntax {
+        let mut names = cycle
+            .iter()
+            .map(|module| self.name(*module))
+            .collect::<Vec<_>>();
+        names.push(self.name(index));
+
+        let mut error = Syntax::error_no_note(
+            Code::ModuleCycle,
+            &format!(
+                "These modules use each other in a cycle: {}",
+                names.join(" -> ")
+            ),
+        );
+
+        for (position, module) in cycle.iter().enumerate() {
+            let used = cycle.get(position + 1).copied().unwrap_or(index);
+            let (_, span) = linked[*module]
+                .uses
+                .iter()
+                .find(|(other, _)| *other == used)
+                .unwrap();
+            error = error.add_note(Note::new_with_hint(
+                &format!("`{}` uses `{}` here", self.name(*module), self.name(used)),
+                span,
+            ));
+        }
+
+        error
+    }
+
+    /// Builds the program that evaluates each module in order.
+    /// The root is evaluated in a function of its own,
+    /// so other modules can not refer to its variables.
+    /// The code made up to do so has an empty span,
+    /// so it is left out of tracebacks.
+    fn program(&self, linked: Vec<Linked>, order: Vec<usize>) -> Spanned<TokenTree> {
+        let mut linked = linked.into_iter().map(Some).collect::<Vec<_>>();
+        let mut lines = vec![];
+        let root = Span::point(&self.units[0].source, 0);
+
+        for index in order {
+            let Linked {
+                lines: body, span, ..
+            } = linked[index].take().unwrap();
+
+            let line = if index == 0 {
+                let tree = |item: TokenTree| Spanned::new(item, root.clone());
+                // (() -> { .. }) ()
+                let function = vec![
+                    tree(TokenTree::Lit(Lit::Unit)),
+                    tree(TokenTree::Op("->".to_string())),
+                    tree(TokenTree::Block(body)),
+                ];
+                vec![
+                    tree(TokenTree::Form(function)),
+                    tree(TokenTree::Lit(Lit::Unit)),
+                ]
+            } else {
+                let tree = |item: TokenTree| Spanned::new(item, span.clone());
+                vec![
+                    tree(TokenTree::Iden(self.variable(index))),
+                    tree(TokenTree::Op("=".to_string())),
+                    tree(TokenTree::Iden("mod".to_string())),
+                    tree(TokenTree::Block(body)),
+                ]
+            };
+            lines.push(Spanned::new(line, root.clone()));
+        }
+
+        Spanned::new(TokenTree::Block(lines), root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        common::{closure::Closure, Data},
+        compiler::compile_module,
+        kernel,
+        vm::fiber::Fiber,
+    };
+
+    fn module(path: &str, source: &str, children: Vec<Module>) -> Module {
+        Module::new(Source::new(source, Path::new(path)), children)
+    }
+
+    fn run(module: &Module) -> Data {
+        let lambda = compile_module(module).unwrap();
+        let mut fiber = Fiber::init(Closure::wrap(lambda));
+        kernel::register(&mut fiber);
+        fiber.run().unwrap();
+        fiber.stack.pop_data()
+    }
+
+    #[test]
+    fn uses_children_and_siblings() {
+        let vector = module(
+            "src/math/vector.pn",
+            "use scale\nlength = 2 * scale.factor",
+            vec![],
+        );
+        let scale = module("src/math/scale.pn", "factor = 3", vec![]);
+        let math = module(
+            "src/math/main.pn",
+            "use vector\ntwice = x -> x * 2",
+            vec![vector, scale],
+        );
+        let unused = module("src/unused.pn", "value = 1", vec![]);
+        let root = module(
+            "src/main.pn",
+            "use math\nuse math.vector\n(math.twice vector.length, math.vector.length)",
+            vec![math, unused],
+        );
+
+        assert_eq!(
+            run(&root),
+            Data::Tuple(vec![Data::Integer(12), Data::Integer(6)])
+        );
+    }
+
+    #[test]
+    fn isolated() {
+        // modules can not see the variables of the root
+        let child = module("src/child.pn", "value = secret", vec![]);
+        let root = module("src/main.pn", "secret = 1\nuse child", vec![child]);
+        assert!(compile_module(&root).is_err());
+    }
+
+    #[test]
+    fn missing() {
+        let root = module("src/main.pn", "use nowhere", vec![]);
+        assert!(compile_module(&root).is_err());
+
+        for path in ["a.", "a b c", "(a)"] {
+            let root = module("src/main.pn", &format!("use {}", path), vec![]);
+            assert!(compile_module(&root).is_err());
+        }
+    }
+
+    #[test]
+    fn polymorphic_root() {
+        // functions in the root are generalized, like in a single file
+        let root = module("src/main.pn", "id = x -> x\n(id 1, id \"a\")", vec![]);
+        assert_eq!(
+            run(&root),
+            Data::Tuple(vec![Data::Integer(1), Data::String("a".to_string())])
+        );
+    }
+
+    #[test]
+    fn traceback() {
+        let source = format!(
+            "{}g = l -> match l {{ [] -> 0 }}\ng [1, 2]",
+            "\n".repeat(10)
+        );
+        let root = module("src/main.pn", &source, vec![]);
+        let lambda = compile_module(&root).unwrap();
+        let mut fiber = Fiber::init(Closure::wrap(lambda));
+        kernel::register(&mut fiber);
+
+        // the call that runs the root is not shown,
+        // but the call at the end of the root is
+        let trace = fiber.run().unwrap_err().to_string();
+        assert!(trace.contains("11 | g = l -> match"));
+        assert!(trace.contains("12 | g [1, 2]"));
+        assert!(!trace.contains(" > "));
+    }
+
+    #[test]
+    fn labels_scoped() {
+        // labels with the same name in different modules are different
+        let a = module(
+            "src/a.pn",
+            "err = Error 1\nown = e -> match e { Error _ -> True, _ -> False }",
+            vec![],
+        );
+        let root = module(
+            "src/main.pn",
+            "use a\n(a.err == Error 1, a.own a.err, a.own (Error 1))",
+            vec![a],
+        );
+        assert_eq!(
+            run(&root),
+            Data::Tuple(vec![
+                Data::Boolean(false),
+                Data::Boolean(true),
+                Data::Boolean(false),
+            ])
+        );
+    }
+
+    #[test]
+    fn effects_shared() {
+        // effects are not scoped, so they can be handled in any module
+        let a = module("src/a.pn", "effect Log\nlog = x -> Log x", vec![]);
+        let root = module(
+            "src/main.pn",
+            "use a\nhandle { a.log 1 } with { Log x -> x + 1 }",
+            vec![a],
+        );
+        assert_eq!(run(&root), Data::Integer(2));
+    }
+
+    #[test]
+    fn operators_scoped() {
+        // an operator declared in a module can not be used outside of it
+        let a = || {
+            module(
+                "src/a.pn",
+                "add = (a, b) -> a + b\ninfix <> left + add\nthree = 1 <> 2",
+                vec![],
+            )
+        };
+        let root = module("src/main.pn", "use a\na.three", vec![a()]);
+        assert_eq!(run(&root), Data::Integer(3));
+
+        let root = module("src/main.pn", "use a\n1 <> 2", vec![a()]);
+        assert!(compile_module(&root).is_err());
+    }
+
+    #[test]
+    fn cycle() {
+        let a = module("src/a.pn", "use b", vec![]);
+        let b = module("src/b.pn", "use c", vec![]);
+        let c = module("src/c.pn", "use a", vec![]);
+        let root = module("src/main.pn", "use a", vec![a, b, c]);
+
+        let error = compile_module(&root).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("a -> b -> c -> a"));
+        assert!(message.contains("`c` uses `a` here"));
+    }
+}
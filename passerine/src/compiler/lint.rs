@@ -0,0 +1,547 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    common::span::{Span, Spanned},
+    compiler::syntax::{write_notes, Note},
+    construct::{symbol::UniqueSymbol, tree::Type},
+    explain::Code,
+};
+
+/// The kinds of code that compile, but probably don't do what was meant.
+/// Each lint has a stable name, used to silence it.
+///
+/// To silence a lint, write a comment like `# allow unused_variable`,
+/// either at the end of the line the warning is on,
+/// or on a line of its own just before it.
+/// More than one lint can be listed, separated by commas,
+/// and `# allow all` silences every lint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A variable that is assigned, or bound by a match arm, but never used.
+    UnusedVariable,
+    /// A function argument that is never used.
+    UnusedArgument,
+    /// A function argument with the same name
+    /// as a variable in an enclosing scope.
+    ShadowedBinding,
+    /// A value assigned to a variable
+    /// that is overwritten or never read afterwards.
+    UnreadAssignment,
+    /// A variable assigned after a function has captured it.
+    CapturedMutation,
+    /// A label or effect in a type annotation, which is not checked.
+    UncheckedAnnotation,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::UnusedVariable,
+        Lint::UnusedArgument,
+        Lint::ShadowedBinding,
+        Lint::UnreadAssignment,
+        Lint::CapturedMutation,
+        Lint::UncheckedAnnotation,
+    ];
+
+    /// The stable name of the lint.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused_variable",
+            Lint::UnusedArgument => "unused_argument",
+            Lint::ShadowedBinding => "shadowed_binding",
+            Lint::UnreadAssignment => "unread_assignment",
+            Lint::CapturedMutation => "captured_mutation",
+            Lint::UncheckedAnnotation => "unchecked_annotation",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+
+    /// The stable code of the lint, which can be explained at length.
+    pub fn code(&self) -> Code {
+        match self {
+            Lint::UnusedVariable => Code::UnusedVariable,
+            Lint::UnusedArgument => Code::UnusedArgument,
+            Lint::ShadowedBinding => Code::ShadowedBinding,
+            Lint::UnreadAssignment => Code::UnreadAssignment,
+            Lint::CapturedMutation => Code::CapturedMutation,
+            Lint::UncheckedAnnotation => Code::UncheckedAnnotation,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Represents a static warning found at compile time.
+/// Unlike a `Syntax` error, a warning does not stop compilation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: Lint,
+    pub reason: String,
+    pub notes: Vec<Note>,
+}
+
+impl Warning {
+    /// Creates a new warning with a single note that does not have a hint.
+    pub fn new(lint: Lint, reason: &str, span: &Span) -> Warning {
+        Warning {
+            lint,
+            reason: reason.to_string(),
+            notes: vec![Note::new(span.clone())],
+        }
+    }
+
+    /// Extend a warning by adding another note to the warning.
+    pub fn add_note(mut self, note: Note) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    /// Whether a comment silences this warning,
+    /// either on the line the warning is on or the line before.
+    pub fn is_allowed(&self) -> bool {
+        let span = &self.notes[0].span;
+        let line = span.line(span.end() - span.len());
+        let contents = &span.source().contents;
+        let mut lines = contents.lines().skip(line.saturating_sub(1));
+
+        let before = match line {
+            0 => None,
+            _ => lines.next(),
+        };
+        let current = lines.next();
+
+        // a comment on the line before must be on a line of its own
+        let before = before.filter(|before| before.trim_start().starts_with('#'));
+        before
+            .into_iter()
+            .chain(current)
+            .any(|line| Warning::allows(line, self.lint))
+    }
+
+    /// Whether a line ends with a comment that allows a lint.
+    fn allows(line: &str, lint: Lint) -> bool {
+        let comment = match line.rfind('#') {
+            Some(index) => line[index + 1..].trim(),
+            None => return false,
+        };
+        let lints = match comment.strip_prefix("allow ") {
+            Some(lints) => lints,
+            None => return false,
+        };
+
+        lints
+            .split(',')
+            .map(|name| name.trim())
+            .any(|name| name == "all" || name == lint.name())
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_notes(f, &self.notes)?;
+        write!(
+            f,
+            "Warning [{} {}]: {}",
+            self.lint.code(),
+            self.lint,
+            self.reason
+        )
+    }
+}
+
+/// A variable, and where it was declared.
+#[derive(Debug, Clone)]
+struct Binding {
+    span: Span,
+    /// Whether the variable is an argument of a function.
+    argument: bool,
+}
+
+/// An assignment to a variable.
+#[derive(Debug, Clone)]
+struct Write {
+    symbol: UniqueSymbol,
+    span: Span,
+    /// The branches the assignment is in, outermost first.
+    branches: Vec<usize>,
+}
+
+/// Keeps track of how each variable is used while hoisting,
+/// and of the annotations that are not checked,
+/// so that lints can be checked once hoisting is done.
+/// The hoister walks the program in the order it is run,
+/// except that each branch of a conditional is walked in turn.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Usage {
+    /// Each variable, in the order it was declared.
+    bindings: Vec<(UniqueSymbol, Binding)>,
+    /// The variables that are read at least once.
+    reads: HashSet<UniqueSymbol>,
+    /// Assignments that have not been read yet.
+    unread: Vec<Write>,
+    /// Assignments that were overwritten before they were read.
+    overwritten: Vec<Write>,
+    /// Where each captured variable was first captured.
+    captures: HashMap<UniqueSymbol, Span>,
+    /// Variables that were used by a function before they were declared,
+    /// with how deeply nested the function was.
+    hoisted: HashMap<UniqueSymbol, (usize, Span)>,
+    /// Assignments to variables that were already captured.
+    mutations: Vec<(UniqueSymbol, Span)>,
+    /// Arguments, and the variables they shadow.
+    shadows: Vec<(UniqueSymbol, UniqueSymbol)>,
+    /// The parts of type annotations that are not checked,
+    /// with a description of each.
+    unchecked: Vec<(Span, String)>,
+    /// The branches being walked, outermost first.
+    branches: Vec<usize>,
+    next_branch: usize,
+}
+
+impl Usage {
+    pub fn declare(&mut self, symbol: UniqueSymbol, span: &Span, argument: bool) {
+        let binding = Binding {
+            span: span.clone(),
+            argument,
+        };
+        self.bindings.push((symbol, binding));
+    }
+
+    /// Reads a variable, which reads every assignment to it so far.
+    pub fn read(&mut self, symbol: UniqueSymbol) {
+        self.reads.insert(symbol);
+        self.unread.retain(|write| write.symbol != symbol);
+    }
+
+    /// Assigns to a variable.
+    /// Earlier assignments that have not been read are overwritten,
+    /// unless they are in a branch that this assignment is not.
+    pub fn write(&mut self, symbol: UniqueSymbol, span: &Span) {
+        let branches = self.branches.clone();
+        let (overwritten, unread) = self
+            .unread
+            .drain(..)
+            .partition(|write| write.symbol == symbol && write.branches.starts_with(&branches));
+        self.overwritten.extend::<Vec<_>>(overwritten);
+        self.unread = unread;
+
+        self.unread.push(Write {
+            symbol,
+            span: span.clone(),
+            branches,
+        });
+    }
+
+    /// Records that a function captures a variable from an enclosing scope.
+    pub fn capture(&mut self, symbol: UniqueSymbol, span: &Span) {
+        self.captures.entry(symbol).or_insert_with(|| span.clone());
+    }
+
+    /// Records an assignment to an existing variable,
+    /// which is a mutation if a function captured the variable elsewhere.
+    pub fn reassign(&mut self, symbol: UniqueSymbol, span: &Span) {
+        if let Some(captured) = self.captures.get(&symbol) {
+            if captured != span {
+                self.mutations.push((symbol, span.clone()));
+            }
+        }
+    }
+
+    /// Records a variable that was used before it was declared,
+    /// at a given depth of nested functions.
+    pub fn hoist(&mut self, symbol: UniqueSymbol, depth: usize, span: &Span) {
+        self.hoisted.insert(symbol, (depth, span.clone()));
+    }
+
+    /// Records the declaration of a variable that was used beforehand.
+    /// If it was used in a function nested in the declaring scope,
+    /// the function captured it.
+    pub fn unhoist(&mut self, symbol: UniqueSymbol, depth: usize) {
+        if let Some((used, span)) = self.hoisted.remove(&symbol) {
+            if used > depth {
+                self.capture(symbol, &span);
+            }
+        }
+    }
+
+    /// Records that a variable declared like an argument
+    /// is bound by the pattern of a match arm instead,
+    /// so it is linted like any other variable.
+    pub fn bind(&mut self, symbol: UniqueSymbol) {
+        if let Some((_, binding)) = self.bindings.iter_mut().find(|(other, _)| *other == symbol) {
+            binding.argument = false;
+        }
+    }
+
+    pub fn shadow(&mut self, argument: UniqueSymbol, shadowed: UniqueSymbol) {
+        self.shadows.push((argument, shadowed));
+    }
+
+    /// Records the parts of a type annotation the unifier does not check yet,
+    /// which are labels and effects.
+    pub fn annotate(&mut self, ty: &Spanned<Type>) {
+        match &ty.item {
+            Type::Name(_) | Type::Var(_) => (),
+            Type::Tuple(items) => items.iter().for_each(|item| self.annotate(item)),
+            Type::List(item) => self.annotate(item),
+            Type::Map(key, value) => {
+                self.annotate(key);
+                self.annotate(value);
+            }
+            Type::Function { arg, body, effects } => {
+                self.annotate(arg);
+                self.annotate(body);
+                for effect in effects {
+                    let described = format!("effect `{}`", effect.item);
+                    self.unchecked.push((effect.span.clone(), described));
+                }
+            }
+            Type::Label(name, inner) => {
+                let described = format!("label `{}`", name.item);
+                self.unchecked.push((ty.span.clone(), described));
+                self.annotate(inner);
+            }
+        }
+    }
+
+    /// Starts walking one branch of a conditional.
+    pub fn enter_branch(&mut self) {
+        self.branches.push(self.next_branch);
+        self.next_branch += 1;
+    }
+
+    pub fn exit_branch(&mut self) {
+        self.branches.pop();
+    }
+
+    fn binding(&self, symbol: UniqueSymbol) -> Option<&Binding> {
+        self.bindings
+            .iter()
+            .find(|(other, _)| *other == symbol)
+            .map(|(_, binding)| binding)
+    }
+
+    /// Checks each lint, once the whole program has been walked.
+    /// `name` gives the name of a variable,
+    /// or `None` if the variable should not be linted.
+    /// Warnings silenced by a comment are left out.
+    ///
+    /// Variables that are not written in the source where they are declared,
+    /// like the continuation of a handler or variables made by macros,
+    /// are not linted either.
+    pub fn warnings(&self, name: impl Fn(UniqueSymbol) -> Option<String>) -> Vec<Warning> {
+        let written = |symbol: UniqueSymbol| {
+            let name = name(symbol)?;
+            let binding = self.binding(symbol)?;
+            match binding.span.contents() == name {
+                true => Some((name, binding)),
+                false => None,
+            }
+        };
+        let mut warnings = vec![];
+
+        for (symbol, _) in self.bindings.iter() {
+            let (name, binding) = match written(*symbol) {
+                Some(written) => written,
+                None => continue,
+            };
+
+            if !self.reads.contains(symbol) {
+                let (lint, kind) = match binding.argument {
+                    true => (Lint::UnusedArgument, "argument"),
+                    false => (Lint::UnusedVariable, "variable"),
+                };
+                warnings.push(Warning::new(
+                    lint,
+                    &format!("The {} `{}` is never used", kind, name),
+                    &binding.span,
+                ));
+                continue;
+            }
+
+            // a function that captures the variable may read it at any time
+            if self.captures.contains_key(symbol) {
+                continue;
+            }
+            for write in self.overwritten.iter().chain(self.unread.iter()) {
+                if write.symbol == *symbol {
+                    warnings.push(Warning::new(
+                        Lint::UnreadAssignment,
+                        &format!("The value assigned to `{}` is never read", name),
+                        &write.span,
+                    ));
+                }
+            }
+        }
+
+        for (argument, shadowed) in self.shadows.iter() {
+            let (name, binding) = match written(*argument) {
+                Some((name, binding)) if binding.argument => (name, binding),
+                _ => continue,
+            };
+            let mut warning = Warning::new(
+                Lint::ShadowedBinding,
+                &format!(
+                    "The argument `{}` shadows a variable in an enclosing scope",
+                    name
+                ),
+                &binding.span,
+            );
+            if let Some(shadowed) = self.binding(*shadowed) {
+                warning = warning.add_note(Note::new_with_hint(
+                    "the shadowed variable is declared here",
+                    &shadowed.span,
+                ));
+            }
+            warnings.push(warning);
+        }
+
+        for (symbol, span) in self.mutations.iter() {
+            let name = match written(*symbol) {
+                Some((name, _)) => name,
+                None => continue,
+            };
+            warnings.push(
+                Warning::new(
+                    Lint::CapturedMutation,
+                    &format!(
+                        "The variable `{}` is assigned after a function captures it",
+                        name
+                    ),
+                    span,
+                )
+                .add_note(Note::new_with_hint(
+                    "the variable is captured here",
+                    &self.captures[symbol],
+                )),
+            );
+        }
+
+        for (span, described) in self.unchecked.iter() {
+            warnings.push(Warning::new(
+                Lint::UncheckedAnnotation,
+                &format!("The {} in this annotation is not checked", described),
+                span,
+            ));
+        }
+
+        warnings.retain(|warning| !warning.is_allowed());
+        warnings.sort_by_key(|warning| {
+            let span = &warning.notes[0].span;
+            (span.path(), span.end())
+        });
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Source;
+
+    /// Each warning raised, with the code it points to.
+    fn lints(source: &str) -> Vec<(Lint, String)> {
+        crate::compiler::lint(Source::source(source))
+            .unwrap()
+            .into_iter()
+            .map(|warning| (warning.lint, warning.notes[0].span.contents()))
+            .collect()
+    }
+
+    #[test]
+    fn unused() {
+        assert_eq!(
+            lints("x = 1\nf = a -> b -> a\n_ignored = f 1 2"),
+            vec![
+                (Lint::UnusedVariable, "x".to_string()),
+                (Lint::UnusedArgument, "b".to_string()),
+            ]
+        );
+        assert!(lints("x = 1\ny = x\ny").is_empty());
+    }
+
+    #[test]
+    fn unused_bindings() {
+        let source = "match (1, 2) {\n    (a, b) -> a\n}\n(c, d) = (3, 4)\nc";
+        assert_eq!(
+            lints(source),
+            vec![
+                (Lint::UnusedVariable, "b".to_string()),
+                (Lint::UnusedVariable, "d".to_string()),
+            ]
+        );
+        assert!(lints(
+            "x = 1
+match x {
+    x -> x
+}"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn unchecked_annotations() {
+        // both annotations are wrong, but compile anyway
+        let source = "effect Log\n\
+            x: Some Integer = Some \"one\"\n\
+            f = (n: Integer) -> n\n\
+            g: (Integer -> Integer / Log) = f\n\
+            (g 1, x)";
+        assert_eq!(
+            lints(source),
+            vec![
+                (Lint::UncheckedAnnotation, "Some Integer".to_string()),
+                (Lint::UncheckedAnnotation, "Log".to_string()),
+            ]
+        );
+        assert!(lints("x: (Integer, [String]) = (1, [\"a\"])\nx").is_empty());
+    }
+
+    #[test]
+    fn shadowed() {
+        assert_eq!(
+            lints("x = 1\nf = x -> x\nf x"),
+            vec![(Lint::ShadowedBinding, "x".to_string())]
+        );
+    }
+
+    #[test]
+    fn unread() {
+        assert_eq!(
+            lints("x = 1\nx = 2\nx"),
+            vec![(Lint::UnreadAssignment, "x".to_string())]
+        );
+        assert_eq!(lints("x = 1\nx\nx = 2").len(), 1);
+        assert!(lints("x = 1\nif True { x = 2 } else { () }\nx").is_empty());
+        assert!(lints("x = 1\nx = x + 1\nx").is_empty());
+    }
+
+    #[test]
+    fn captured_mutation() {
+        assert_eq!(
+            lints("x = 1\nf = () -> x\nx = 2\nf ()"),
+            vec![(Lint::CapturedMutation, "x".to_string())]
+        );
+        assert!(lints("x = 1\ninc = () -> { x = x + 1 }\ninc ()\nx").is_empty());
+    }
+
+    #[test]
+    fn allowed() {
+        let source = "x = 1 # allow unused_variable\n# allow all\ny = 2\nz = 3";
+        assert_eq!(lints(source), vec![(Lint::UnusedVariable, "z".to_string())]);
+        assert_eq!(
+            Lint::from_name("unread_assignment"),
+            Some(Lint::UnreadAssignment)
+        );
+    }
+}
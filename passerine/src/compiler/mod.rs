@@ -11,8 +11,11 @@ pub use lex::Lexer;
 pub mod read;
 pub use read::Reader;
 
-// pub mod expand;
-// pub use expand::Expander;
+pub mod expand;
+pub use expand::Expander;
+
+pub mod link;
+pub use link::{Linker, Paths};
 
 pub mod parse;
 pub use parse::Parser;
@@ -23,18 +26,27 @@ pub use desugar::Desugarer;
 pub mod hoist;
 pub use hoist::Hoister;
 
-// pub mod unify;
+pub mod unify;
+pub use unify::Unifier;
+
+pub mod decision;
 
 pub mod compile;
 pub use compile::Compiler;
 
 pub mod syntax;
-pub use syntax::Syntax;
+pub use syntax::{Diagnostics, Syntax};
+
+pub mod lint;
+pub use lint::{Lint, Warning};
+
+pub mod session;
+pub use session::Session;
 
 use std::{collections::HashMap, rc::Rc};
 
 use crate::{
-    common::{lambda::Lambda, Source, Spanned},
+    common::{lambda::Lambda, Module, Source, Spanned},
     construct::{
         scope::Scope,
         symbol::SharedSymbol,
@@ -49,40 +61,62 @@ pub fn lex(source: Rc<Source>) -> Result<Spanned<Tokens>, Syntax> {
 }
 
 #[inline(always)]
-pub fn read(source: Rc<Source>) -> Result<Spanned<TokenTree>, Syntax> {
+pub fn read(source: Rc<Source>) -> Result<Spanned<TokenTree>, Diagnostics> {
     let tokens = lex(source)?;
     Reader::read(tokens)
 }
 
 #[inline(always)]
-pub fn parse(source: Rc<Source>) -> Result<(Spanned<AST>, HashMap<String, SharedSymbol>), Syntax> {
+pub fn expand(source: Rc<Source>) -> Result<Spanned<TokenTree>, Diagnostics> {
     let token_tree = read(source)?;
+    Expander::expand(token_tree)
+}
+
+#[inline(always)]
+pub fn parse(source: Rc<Source>) -> Result<(Spanned<AST>, HashMap<String, SharedSymbol>), Diagnostics> {
+    let token_tree = expand(source)?;
     Parser::parse(token_tree)
 }
 
 #[inline(always)]
 pub fn desugar(
     source: Rc<Source>,
-) -> Result<(Spanned<CST>, HashMap<String, SharedSymbol>), Syntax> {
+) -> Result<(Spanned<CST>, HashMap<String, SharedSymbol>), Diagnostics> {
     let (ast, symbols) = parse(source)?;
     Ok((Desugarer::desugar(ast), symbols))
 }
 
 #[inline(always)]
-pub fn hoist(source: Rc<Source>) -> Result<(Spanned<SST>, Scope), Syntax> {
+pub fn hoist(source: Rc<Source>) -> Result<(Spanned<SST>, Scope), Diagnostics> {
     let (cst, symbols) = desugar(source)?;
     Hoister::hoist(cst, symbols)
 }
 
+/// Finds the warnings in a source.
+#[inline(always)]
+pub fn lint(source: Rc<Source>) -> Result<Vec<Warning>, Diagnostics> {
+    let (cst, symbols) = desugar(source)?;
+    let (_, _, warnings) = Hoister::hoist_with_warnings(cst, symbols)?;
+    Ok(warnings)
+}
+
 #[inline(always)]
-pub fn gen(source: Rc<Source>) -> Result<Rc<Lambda>, Syntax> {
+pub fn unify(source: Rc<Source>) -> Result<(Spanned<SST>, Scope), Diagnostics> {
     let (sst, scope) = hoist(source)?;
-    Compiler::compile(sst, scope)
+    Unifier::unify(&sst)?;
+    Ok((sst, scope))
 }
 
 #[inline(always)]
-pub fn compile_sst(sst: Spanned<SST>, scope: Scope) -> Result<Rc<Lambda>, Syntax> {
-    Compiler::compile(sst, scope)
+pub fn gen(source: Rc<Source>) -> Result<Rc<Lambda>, Diagnostics> {
+    let (sst, scope) = unify(source)?;
+    Ok(Compiler::compile(sst, scope)?)
+}
+
+#[inline(always)]
+pub fn compile_sst(sst: Spanned<SST>, scope: Scope) -> Result<Rc<Lambda>, Diagnostics> {
+    Unifier::unify(&sst)?;
+    Ok(Compiler::compile(sst, scope)?)
 }
 
 // TODO: convert symbols to type alias somewhere
@@ -90,7 +124,7 @@ pub fn compile_sst(sst: Spanned<SST>, scope: Scope) -> Result<Rc<Lambda>, Syntax
 pub fn compile_cst(
     cst: Spanned<CST>,
     symbols: HashMap<String, SharedSymbol>,
-) -> Result<Rc<Lambda>, Syntax> {
+) -> Result<Rc<Lambda>, Diagnostics> {
     let (sst, scope) = Hoister::hoist(cst, symbols)?;
     compile_sst(sst, scope)
 }
@@ -99,25 +133,49 @@ pub fn compile_cst(
 pub fn compile_ast(
     ast: Spanned<AST>,
     symbols: HashMap<String, SharedSymbol>,
-) -> Result<Rc<Lambda>, Syntax> {
+) -> Result<Rc<Lambda>, Diagnostics> {
     let cst = Desugarer::desugar(ast);
     compile_cst(cst, symbols)
 }
 
 #[inline(always)]
-pub fn compile_token_tree(token_tree: Spanned<TokenTree>) -> Result<Rc<Lambda>, Syntax> {
+pub fn compile_token_tree(token_tree: Spanned<TokenTree>) -> Result<Rc<Lambda>, Diagnostics> {
+    let token_tree = Expander::expand(token_tree)?;
     let (ast, symbols) = Parser::parse(token_tree)?;
     compile_ast(ast, symbols)
 }
 
 #[inline(always)]
-pub fn compile_tokens(tokens: Spanned<Tokens>) -> Result<Rc<Lambda>, Syntax> {
+pub fn compile_tokens(tokens: Spanned<Tokens>) -> Result<Rc<Lambda>, Diagnostics> {
     let token_tree = Reader::read(tokens)?;
     compile_token_tree(token_tree)
 }
 
 #[inline(always)]
-pub fn compile_source(source: Rc<Source>) -> Result<Rc<Lambda>, Syntax> {
+pub fn compile_source(source: Rc<Source>) -> Result<Rc<Lambda>, Diagnostics> {
     let tokens = Lexer::lex(source)?;
     compile_tokens(tokens)
 }
+
+/// Compiles a module, along with all modules nested in it,
+/// to a single program.
+#[inline(always)]
+pub fn compile_module(module: &Module) -> Result<Rc<Lambda>, Diagnostics> {
+    let (lambda, _) = compile_module_with_warnings(module)?;
+    Ok(lambda)
+}
+
+/// Compiles a module like `compile_module`,
+/// also returning the warnings found in all the modules.
+#[inline(always)]
+pub fn compile_module_with_warnings(
+    module: &Module,
+) -> Result<(Rc<Lambda>, Vec<Warning>), Diagnostics> {
+    let (token_tree, paths) = Linker::link(module)?;
+    let (ast, symbols, effects) = Parser::parse_linked(token_tree)?;
+    let cst = Desugarer::desugar(ast);
+    let (sst, scope, warnings) = Hoister::hoist_with_warnings(cst, symbols)?;
+    Unifier::unify(&sst)?;
+    let lambda = Compiler::compile_linked(sst, scope, &paths, effects)?;
+    Ok((lambda, warnings))
+}
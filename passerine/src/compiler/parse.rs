@@ -1,16 +1,25 @@
-use std::{collections::HashMap, convert::TryFrom, mem, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    mem,
+    rc::Rc,
+};
 
 use crate::{
     common::{
         lit::Lit,
         span::{Span, Spanned},
     },
-    compiler::{read::Reader, syntax::Syntax},
+    compiler::{
+        read::Reader,
+        syntax::{Diagnostics, Note, Syntax},
+    },
     construct::{
         symbol::SharedSymbol,
         token::{Delim, ResIden, ResOp, TokenTree, TokenTrees},
-        tree::{Base, Lambda, Pattern, Sugar, AST},
+        tree::{Base, BinOp, Fields, Lambda, Pattern, Sugar, Type, UnOp, AST},
     },
+    explain::Code,
 };
 
 // TODO: Document how parser advances
@@ -33,8 +42,12 @@ pub enum Prec {
     Compose,
     /// `->`
     Lambda,
-    /// Boolean logic.
-    Logic,
+    /// `||`
+    Or,
+    /// `&&`
+    And,
+    /// `==`, `<`, etc.
+    Compare,
     /// `+`, `-`
     AddSub,
     /// `*`, `/`, etc.
@@ -71,12 +84,41 @@ impl Prec {
     }
 }
 
+/// An operator declared with `infix`.
+#[derive(Debug, Clone)]
+struct Infix {
+    prec: Prec,
+    is_left: bool,
+    /// A hidden variable bound to the function applied to the operands,
+    /// which can not be shadowed.
+    fun: SharedSymbol,
+}
+
+/// A program parsed by `Parser::parse_linked`,
+/// along with its symbols and the names of the effects it declares.
+pub type Linked = (Spanned<AST>, HashMap<String, SharedSymbol>, HashSet<String>);
+
 #[derive(Debug)]
 pub struct Parser {
     /// Symbols with the same name are interned.
     /// We don't do this during lexing so that token-based
     /// macros can work with strings.
     symbols: HashMap<String, SharedSymbol>,
+    /// The names of all effects declared so far.
+    /// Applying one of these labels raises an effect.
+    effects: HashSet<String>,
+    /// The operators declared so far with `infix`, by name.
+    operators: HashMap<String, Infix>,
+    /// Errors found so far in the current entry.
+    /// The parser recovers at the end of each line and each group,
+    /// so it can keep going and report the errors after them.
+    errors: Vec<Syntax>,
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
 }
 
 impl Parser {
@@ -85,15 +127,61 @@ impl Parser {
     /// Also returns the symbol interning table.
     pub fn parse(
         token_tree: Spanned<TokenTree>,
-    ) -> Result<(Spanned<AST>, HashMap<String, SharedSymbol>), Syntax> {
-        // build base parser
-        let mut parser = Parser {
+    ) -> Result<(Spanned<AST>, HashMap<String, SharedSymbol>), Diagnostics> {
+        let mut parser = Parser::new();
+        let ast = parser.parse_entry(&token_tree)?;
+        Ok((ast, parser.symbols))
+    }
+
+    /// Parses a program produced by the `Linker` like `parse`,
+    /// also returning the names of the effects declared in it,
+    /// which are shared by all of its modules.
+    pub fn parse_linked(token_tree: Spanned<TokenTree>) -> Result<Linked, Diagnostics> {
+        let mut parser = Parser::new();
+        let ast = parser.parse_entry(&token_tree)?;
+        Ok((ast, parser.symbols, parser.effects))
+    }
+
+    /// Builds a base parser, with no symbols, effects, or operators.
+    pub fn new() -> Parser {
+        Parser {
             symbols: HashMap::new(),
+            effects: HashSet::new(),
+            operators: HashMap::new(),
+            errors: vec![],
+        }
+    }
+
+    /// Parses one entry of a `Session`.
+    /// Symbols, effects, and operators from earlier entries are kept,
+    /// so later entries can refer to them.
+    /// Every error found in the entry is returned.
+    pub fn parse_entry(
+        &mut self,
+        token_tree: &Spanned<TokenTree>,
+    ) -> Result<Spanned<AST>, Diagnostics> {
+        // the program itself is always a block, never a record
+        let result = match &token_tree.item {
+            TokenTree::Block(lines) => self.lines(lines, &token_tree.span),
+            _ => self.rule_prefix(token_tree),
         };
 
-        let ast = parser.rule_prefix(&token_tree)?;
+        let mut errors = mem::take(&mut self.errors);
+        match result {
+            Ok(ast) => Diagnostics::check(errors).map(|()| ast),
+            Err(error) => {
+                errors.push(error);
+                Err(Diagnostics::new(errors).unwrap())
+            }
+        }
+    }
 
-        Ok((ast, parser.symbols))
+    /// Records an error, so that parsing can carry on after it.
+    /// Returns a placeholder expression to use in place of
+    /// the code that could not be parsed.
+    fn recover(&mut self, error: Syntax, span: &Span) -> Spanned<AST> {
+        self.errors.push(error);
+        Spanned::new(AST::Base(Base::Lit(Lit::Unit)), span.clone())
     }
 
     // TODO: rename to `walk` or something?
@@ -101,54 +189,61 @@ impl Parser {
     fn rule_prefix(&mut self, token_tree: &Spanned<TokenTree>) -> Result<Spanned<AST>, Syntax> {
         let result = match &token_tree.item {
             TokenTree::Lit(_) => self.literal(token_tree)?,
+            // prefix operators are handled by `unary`
             TokenTree::Op(name) => {
-                if let ResOp::Sub = Parser::to_op(name, &token_tree.span)? {
-                    todo!()
-                } else {
-                    return Err(Syntax::error(
-                        &format!("Unexpected operator `{}`", name),
-                        &token_tree.span,
-                    ));
-                }
+                return Err(Syntax::error(
+                    Code::UnexpectedToken,
+                    &format!("Unexpected operator `{}`", name),
+                    &token_tree.span,
+                ));
             }
             TokenTree::Label(_) => self.label(token_tree)?,
             TokenTree::Iden(_) => self.symbol(token_tree)?,
-            TokenTree::Form(trees) => {
-                dbg!(&trees);
-                // TODO: handle builtin keywords
-                if let Some(Spanned { item, .. }) = trees.first() {
-                    if let TokenTree::Iden(iden) = item {
-                        if let Some(keyword) = ResIden::try_new(iden) {
-                            // TODO: this deep clone isn't necessary,
-                            // we could either pass the vectors owned
-                            // or use slices instead.
-                            dbg!(&trees);
-                            self.keyword(
-                                &trees[1..]
-                                    .into_iter()
-                                    .map(|x| x.clone())
-                                    .collect::<Vec<_>>(),
-                                keyword,
-                            )?;
-                        }
-                    }
-                }
-
-                self.expr(trees, &mut 0, Prec::None)?
+            TokenTree::Form(trees) => match self.expr(trees, &mut 0, Prec::None) {
+                Ok(expression) => expression,
+                Err(error) => self.recover(error, &token_tree.span),
+            },
+            TokenTree::Block(lines) if Parser::is_update(lines) => {
+                self.update(lines, &token_tree.span)?
             }
-            // TODO: instead of expr, use prefix.
-            TokenTree::Block(trees) => {
-                let mut expressions = vec![];
-                for tree in trees {
-                    expressions.push(self.expr(&tree.item, &mut 0, Prec::None)?);
-                }
-                Spanned::new(AST::Base(Base::Block(expressions)), token_tree.span.clone())
+            TokenTree::Block(lines) if Parser::is_record(lines) => {
+                let lines = lines
+                    .iter()
+                    .map(|line| line.item.clone())
+                    .collect::<Vec<_>>();
+                let fields = self.fields(&lines, ResOp::Is)?;
+                Spanned::new(AST::Base(Base::Record(fields)), token_tree.span.clone())
             }
-            TokenTree::List(_) => unimplemented!(),
+            TokenTree::Block(lines) => self.lines(lines, &token_tree.span)?,
+            TokenTree::List(items) if Parser::is_map(items) => self.map(items, &token_tree.span)?,
+            TokenTree::List(items) => self.list(items, &token_tree.span)?,
         };
         Ok(result)
     }
 
+    /// Parses each line of a block as an expression.
+    /// If a line can not be parsed, the error is recorded
+    /// and parsing carries on with the next line.
+    fn lines(
+        &mut self,
+        lines: &[Spanned<TokenTrees>],
+        span: &Span,
+    ) -> Result<Spanned<AST>, Syntax> {
+        // TODO: instead of expr, use prefix.
+        let mut expressions = vec![];
+        for line in lines {
+            let expression = match self.expr(&line.item, &mut 0, Prec::None) {
+                Ok(expression) => expression,
+                Err(error) => self.recover(error, &line.span),
+            };
+            expressions.push(expression);
+        }
+        Ok(Spanned::new(
+            AST::Base(Base::Block(expressions)),
+            span.clone(),
+        ))
+    }
+
     // TODO: replace hacky trees/trees_idx
     fn expr(
         &mut self,
@@ -158,13 +253,23 @@ impl Parser {
     ) -> Result<Spanned<AST>, Syntax> {
         if *trees_idx >= trees.len() {
             return Err(Syntax::error(
+                Code::UnexpectedToken,
                 "Expected an expression",
                 &trees.last().unwrap().span,
             ));
         }
 
-        let mut left = self.rule_prefix(&trees[*trees_idx])?;
-        *trees_idx += 1;
+        let mut left = match self.keyword(trees, trees_idx)? {
+            Some(keyword) => keyword,
+            None => match self.unary(trees, trees_idx, prec)? {
+                Some(unary) => unary,
+                None => {
+                    let prefix = self.rule_prefix(&trees[*trees_idx])?;
+                    *trees_idx += 1;
+                    prefix
+                }
+            },
+        };
 
         while *trees_idx < trees.len() {
             if self.prec(&trees[*trees_idx])? < prec {
@@ -190,6 +295,9 @@ impl Parser {
         use ResOp::*;
         let tree: &Spanned<TokenTree> = &trees[*trees_idx];
         match &tree.item {
+            TokenTree::Op(name) if self.operators.contains_key(name) => {
+                self.infix_use(left, trees, trees_idx)
+            }
             TokenTree::Op(name) => match Parser::to_op(name, &tree.span)? {
                 // Pattern-based
                 Assign => self.assign(left, trees, trees_idx),
@@ -199,12 +307,18 @@ impl Parser {
                 Compose => self.binop(left, trees, trees_idx, true, Compose, |l, r| {
                     AST::Sugar(Sugar::comp(l, r))
                 }),
-                Is => self.binop(left, trees, trees_idx, true, Is, |l, r| {
-                    AST::Sugar(Sugar::is(l, r))
-                }),
-                Field => self.binop(left, trees, trees_idx, true, Field, |l, r| {
-                    AST::Sugar(Sugar::field(l, r))
-                }),
+                Is => self.is(left, trees, trees_idx),
+                Field => self.field(left, trees, trees_idx),
+                Spread => Err(Syntax::error(
+                    Code::InvalidCollection,
+                    "Only the last item in a list can be spread with `..`",
+                    &tree.span,
+                )),
+                Entry => Err(Syntax::error(
+                    Code::InvalidCollection,
+                    "`=>` can only be used between a key and its value in a map",
+                    &tree.span,
+                )),
 
                 // Tuples
                 Pair => {
@@ -226,13 +340,32 @@ impl Parser {
                 }
 
                 // Builtins
-                Add => todo!(),
-                Sub => todo!(),
-                Mul => todo!(),
-                Div => todo!(),
-                Rem => todo!(),
-                Equal => todo!(),
-                Pow => todo!(),
+                Add => self.operator(left, trees, trees_idx, true, Add, BinOp::Add),
+                Sub => self.operator(left, trees, trees_idx, true, Sub, BinOp::Sub),
+                Mul => self.operator(left, trees, trees_idx, true, Mul, BinOp::Mul),
+                Div => self.operator(left, trees, trees_idx, true, Div, BinOp::Div),
+                Rem => self.operator(left, trees, trees_idx, true, Rem, BinOp::Rem),
+                Equal => self.operator(left, trees, trees_idx, true, Equal, BinOp::Equal),
+                NotEqual => self.operator(left, trees, trees_idx, true, NotEqual, BinOp::NotEqual),
+                Less => self.operator(left, trees, trees_idx, true, Less, BinOp::Less),
+                Greater => self.operator(left, trees, trees_idx, true, Greater, BinOp::Greater),
+                LessEqual => {
+                    self.operator(left, trees, trees_idx, true, LessEqual, BinOp::LessEqual)
+                }
+                GreaterEqual => self.operator(
+                    left,
+                    trees,
+                    trees_idx,
+                    true,
+                    GreaterEqual,
+                    BinOp::GreaterEqual,
+                ),
+                Pow => self.operator(left, trees, trees_idx, false, Pow, BinOp::Pow),
+                And => self.operator(left, trees, trees_idx, false, And, BinOp::And),
+                Or => self.operator(left, trees, trees_idx, false, Or, BinOp::Or),
+
+                // A prefix operator starts the argument of a call
+                Not => self.call(left, trees, trees_idx),
             },
 
             _ => self.call(left, trees, trees_idx),
@@ -243,8 +376,23 @@ impl Parser {
     /// Raises a syntax error if the operator string is
     /// invalid.
     fn to_op(name: &str, span: &Span) -> Result<ResOp, Syntax> {
-        ResOp::try_new(name)
-            .ok_or_else(|| Syntax::error(&format!("Invalid operator `{}`", name), span))
+        ResOp::try_new(name).ok_or_else(|| {
+            Syntax::error(
+                Code::UnexpectedToken,
+                &format!("Invalid operator `{}`", name),
+                span,
+            )
+        })
+    }
+
+    /// The precedence of the operand of a prefix operator.
+    /// `-a ** b` is `-(a ** b)`, but `-a * b` is `(-a) * b`.
+    /// `!a == b` is `(!a) == b`.
+    fn prefix_prec(op: UnOp) -> Prec {
+        match op {
+            UnOp::Neg => Prec::Pow,
+            UnOp::Not => Prec::AddSub,
+        }
     }
 
     fn op_prec(op: ResOp) -> Prec {
@@ -253,13 +401,22 @@ impl Parser {
             ResOp::Lambda => Prec::Lambda,
             ResOp::Pair => Prec::Pair,
             ResOp::Field => Prec::Field,
+            ResOp::Spread | ResOp::Entry => Prec::None,
             ResOp::Compose => Prec::Compose,
             ResOp::Is => Prec::Is,
 
             ResOp::Add | ResOp::Sub => Prec::AddSub,
             ResOp::Mul | ResOp::Div | ResOp::Rem => Prec::MulDiv,
 
-            ResOp::Equal => Prec::Logic,
+            ResOp::Equal
+            | ResOp::NotEqual
+            | ResOp::Less
+            | ResOp::Greater
+            | ResOp::LessEqual
+            | ResOp::GreaterEqual => Prec::Compare,
+            ResOp::And => Prec::And,
+            ResOp::Or => Prec::Or,
+            ResOp::Not => Prec::Call,
             ResOp::Pow => Prec::Pow,
         }
     }
@@ -277,37 +434,701 @@ impl Parser {
             | TokenTree::Form(_) => Prec::Call,
 
             // Infix ops
-            TokenTree::Op(name) => Parser::op_prec(Parser::to_op(name, &tree.span)?),
+            TokenTree::Op(name) => match self.operators.get(name) {
+                Some(infix) => infix.prec,
+                None => Parser::op_prec(Parser::to_op(name, &tree.span)?),
+            },
         };
 
         Ok(result)
     }
 
-    /// Try to parse a keyword expression
-    fn keyword(&mut self, trees: &TokenTrees, keyword: ResIden) -> Result<Spanned<AST>, Syntax> {
-        use ResIden::*;
-        match keyword {
-            Macro => todo!(),
-            Type => todo!(),
-            Effect => {
-                dbg!(trees);
-                let rest = self.expr(trees, &mut 0, Prec::End);
+    /// Try to parse a prefix operator applied to an operand,
+    /// if the current token is a prefix operator.
+    /// How far the operand extends is determined by `Parser::prefix_prec`.
+    /// Returns `None` if there is no prefix operator to parse.
+    fn unary(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+        prec: Prec,
+    ) -> Result<Option<Spanned<AST>>, Syntax> {
+        let tree = &trees[*trees_idx];
+        let op = match &tree.item {
+            TokenTree::Op(name) => match Parser::to_op(name, &tree.span)? {
+                ResOp::Sub => UnOp::Neg,
+                ResOp::Not => UnOp::Not,
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        *trees_idx += 1;
+        if *trees_idx >= trees.len() {
+            return Err(Syntax::error(
+                Code::UnexpectedToken,
+                &format!("Expected an expression after `{}`", op.symbol()),
+                &tree.span,
+            ));
+        }
+
+        let operand = self.expr(trees, trees_idx, prec.max(Parser::prefix_prec(op)))?;
+        let span = Span::combine(&tree.span, &operand.span);
+        Ok(Some(Spanned::new(
+            AST::Base(Base::un_op(op, operand)),
+            span,
+        )))
+    }
+
+    /// Try to parse a keyword expression,
+    /// if the current token is a keyword that begins one.
+    /// Returns `None` if there is no keyword expression to parse.
+    fn keyword(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Option<Spanned<AST>>, Syntax> {
+        let tree = &trees[*trees_idx];
+        let keyword = match &tree.item {
+            TokenTree::Iden(iden) => ResIden::try_new(iden),
+            _ => None,
+        };
+
+        let result = match keyword {
+            Some(ResIden::If) => self.if_(trees, trees_idx)?,
+            Some(ResIden::Match) => self.match_(trees, trees_idx)?,
+            Some(ResIden::Effect) => self.effect(trees, trees_idx)?,
+            Some(ResIden::Handle) => self.handle(trees, trees_idx)?,
+            Some(ResIden::Infix) => self.infix(trees, trees_idx)?,
+            Some(ResIden::Mod) => self.module(trees, trees_idx)?,
+            Some(ResIden::Else) => {
+                return Err(Syntax::error(
+                    Code::MisplacedKeyword,
+                    "Found an `else` without a preceding `if`",
+                    &tree.span,
+                ))
+            }
+            Some(ResIden::With) => {
+                return Err(Syntax::error(
+                    Code::MisplacedKeyword,
+                    "Found a `with` without a preceding `handle`",
+                    &tree.span,
+                ))
+            }
+            // macro definitions and syntax rules are expanded away before parsing
+            Some(ResIden::Macro) => {
+                return Err(Syntax::error(
+                    Code::MisplacedKeyword,
+                    "Macros can only be defined at the start of a line",
+                    &tree.span,
+                ))
+            }
+            Some(ResIden::Syntax) => {
+                return Err(Syntax::error(
+                    Code::MisplacedKeyword,
+                    "Syntax rules can only be defined at the start of a line",
+                    &tree.span,
+                ))
+            }
+            Some(ResIden::Use) => {
+                return Err(Syntax::error(
+                    Code::MisplacedKeyword,
+                    "Modules can only be used on their own line, at the top level of a file in a package",
+                    &tree.span,
+                ))
+            }
+            // TODO: type expressions
+            _ => return Ok(None),
+        };
+
+        Ok(Some(result))
+    }
+
+    /// Parses an if expression, like `if cond { a } else { b }`.
+    /// The condition runs up until the first block.
+    /// The `else` branch is optional,
+    /// and may be another if expression.
+    /// If there is no `else` branch, the expression evaluates to `()`.
+    fn if_(&mut self, trees: &TokenTrees, trees_idx: &mut usize) -> Result<Spanned<AST>, Syntax> {
+        let keyword_span = trees[*trees_idx].span.clone();
+        let cond = self.before_block(trees, trees_idx, "condition")?;
+        let then = self.rule_prefix(&trees[*trees_idx])?;
+        *trees_idx += 1;
+
+        let is_else = |tree: Option<&Spanned<TokenTree>>| matches!(tree, Some(Spanned { item: TokenTree::Iden(iden), .. }) if iden == "else");
+
+        if !is_else(trees.get(*trees_idx)) {
+            let span = Span::combine(&keyword_span, &then.span);
+            let unit = Spanned::new(AST::Base(Base::Lit(Lit::Unit)), span.clone());
+            let then = Spanned::new(
+                AST::Base(Base::Block(vec![then, unit.clone()])),
+                span.clone(),
+            );
+            return Ok(Spanned::new(AST::Base(Base::if_(cond, then, unit)), span));
+        }
+
+        let else_span = trees[*trees_idx].span.clone();
+        *trees_idx += 1; // move on from `else`
+
+        let else_ = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Block(_),
+                ..
+            }) => {
+                let block = self.rule_prefix(&trees[*trees_idx])?;
+                *trees_idx += 1;
+                block
+            }
+            Some(Spanned {
+                item: TokenTree::Iden(iden),
+                ..
+            }) if iden == "if" => self.if_(trees, trees_idx)?,
+            _ => {
+                return Err(Syntax::error(
+                    Code::IncompleteKeyword,
+                    "Expected a block or another `if` after `else`",
+                    &else_span,
+                ))
+            }
+        };
+
+        let span = Span::combine(&keyword_span, &else_.span);
+        Ok(Spanned::new(AST::Base(Base::if_(cond, then, else_)), span))
+    }
+
+    /// Parses a match expression, like `match value { pattern -> expression, ... }`.
+    /// Each arm is a function, so arms are separated by commas or newlines.
+    /// Note that this means the body of an arm must be wrapped in parenthesis
+    /// if it is a tuple.
+    fn match_(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let keyword_span = trees[*trees_idx].span.clone();
+        let value = self.before_block(trees, trees_idx, "value to match")?;
+        let block = self.rule_prefix(&trees[*trees_idx])?;
+        *trees_idx += 1;
+
+        let span = Span::combine(&keyword_span, &block.span);
+        let arms = Parser::arms(block)?;
+        Ok(Spanned::new(AST::Base(Base::match_(value, arms)), span))
+    }
+
+    /// Collects the arms in a block, like the one following a `match`.
+    /// Each arm must be a function whose pattern does not chain.
+    fn arms(block: Spanned<AST>) -> Result<Vec<Spanned<AST>>, Syntax> {
+        let lines = match block.item {
+            AST::Base(Base::Block(lines)) => lines,
+            _ => unreachable!("Expected a block of arms"),
+        };
+
+        let mut arms = vec![];
+        for line in lines {
+            let items = match line.item {
+                AST::Base(Base::Tuple(items)) => items,
+                _ => vec![line],
+            };
+
+            for arm in items {
+                let pattern = match &arm.item {
+                    AST::Lambda(lambda) => &lambda.arg,
+                    _ => {
+                        return Err(Syntax::error(
+                            Code::InvalidArm,
+                            "Expected a match arm of the form `pattern -> expression`",
+                            &arm.span,
+                        ))
+                    }
+                };
+
+                if let Some(span) = Parser::chained(pattern) {
+                    return Err(Syntax::error(
+                        Code::InvalidArm,
+                        "Chained patterns can not be used in a match arm",
+                        span,
+                    ));
+                }
+                arms.push(arm);
+            }
+        }
+
+        if arms.is_empty() {
+            return Err(Syntax::error(
+                Code::InvalidArm,
+                "Expected at least one arm",
+                &block.span,
+            ));
+        }
+
+        Ok(arms)
+    }
+
+    /// Parses an effect declaration, like `effect Write`.
+    /// Once declared, applying the effect to some data,
+    /// like `Write "Hello"`, raises the effect instead of constructing a label.
+    /// Effects must be declared before they are raised or handled.
+    fn effect(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let keyword_span = trees[*trees_idx].span.clone();
+        *trees_idx += 1; // move on from the keyword
+
+        let (name, name_span) = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Label(name),
+                span,
+            }) => (name.to_string(), span.clone()),
+            _ => {
+                return Err(Syntax::error(
+                    Code::IncompleteKeyword,
+                    "Expected the name of an effect after `effect`, like `effect Write`",
+                    &keyword_span,
+                ))
+            }
+        };
+        *trees_idx += 1;
+
+        self.effects.insert(name);
+        let span = Span::combine(&keyword_span, &name_span);
+        Ok(Spanned::new(AST::Base(Base::Lit(Lit::Unit)), span))
+    }
+
+    /// Parses a module, like `mod { x = 1 }`.
+    /// A module is a block with its own scope,
+    /// which evaluates to a record of the variables assigned at its top level.
+    fn module(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let keyword_span = trees[*trees_idx].span.clone();
+        *trees_idx += 1; // move on from the keyword
+
+        let (lines, body_span) = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Block(lines),
+                span,
+            }) => (lines, span.clone()),
+            _ => {
+                return Err(Syntax::error(
+                    Code::IncompleteKeyword,
+                    "Expected a block after `mod`, like `mod { x = 1 }`",
+                    &keyword_span,
+                ))
+            }
+        };
+        *trees_idx += 1;
+
+        // operators declared in a module are not visible outside of it
+        let operators = self.operators.clone();
+        let items = lines
+            .iter()
+            .map(|line| self.expr(&line.item, &mut 0, Prec::None))
+            .collect::<Result<Vec<_>, _>>();
+        self.operators = operators;
+        let mut items = items?;
+
+        // names introduced by macros can not be written as fields
+        let mut exports: Fields<Spanned<AST>> = vec![];
+        for item in items.iter() {
+            let pattern = match &item.item {
+                AST::Base(Base::Assign(pattern, _)) => pattern,
+                _ => continue,
+            };
+            for symbol in pattern.item.symbols() {
+                let name = self.symbol_name(*symbol);
+                if name.contains('#') || exports.iter().any(|(field, _)| field.item == name) {
+                    continue;
+                }
+                let value = AST::Base(Base::Symbol(*symbol));
+                exports.push((
+                    Spanned::new(name, pattern.span.clone()),
+                    Spanned::new(value, pattern.span.clone()),
+                ));
+            }
+        }
+        items.push(Spanned::new(
+            AST::Base(Base::Record(exports)),
+            body_span.clone(),
+        ));
+
+        let body = Spanned::new(AST::Base(Base::Block(items)), body_span.clone());
+        Ok(Spanned::new(
+            AST::Base(Base::module(body)),
+            Span::combine(&keyword_span, &body_span),
+        ))
+    }
+
+    /// Parses an operator declaration, like `infix ++ left + concat`.
+    /// This declares `++` as a left-associative operator
+    /// with the same precedence as `+`;
+    /// `right` would make it right-associative instead.
+    /// Once declared, `a ++ b` calls `concat (a, b)`,
+    /// where `concat` is whatever it refers to where the operator is declared.
+    /// Operators must be declared before they are used,
+    /// and builtin operators can not be redeclared.
+    /// An operator declared in a module can only be used in that module.
+    fn infix(&mut self, trees: &TokenTrees, trees_idx: &mut usize) -> Result<Spanned<AST>, Syntax> {
+        let keyword_span = trees[*trees_idx].span.clone();
+        *trees_idx += 1; // move on from the keyword
+        let usage = "Expected an operator declaration, like `infix ++ left + concat`";
+
+        let (name, name_span) = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Op(name),
+                span,
+            }) => (name.to_string(), span.clone()),
+            _ => {
+                return Err(Syntax::error(
+                    Code::InvalidOperatorDeclaration,
+                    usage,
+                    &keyword_span,
+                ))
+            }
+        };
+        if ResOp::try_new(&name).is_some() {
+            return Err(Syntax::error(
+                Code::InvalidOperatorDeclaration,
+                &format!(
+                    "`{}` is a builtin operator, so it can not be redeclared",
+                    name
+                ),
+                &name_span,
+            ));
+        }
+        *trees_idx += 1;
+
+        let is_left = match trees.get(*trees_idx).map(|tree| &tree.item) {
+            Some(TokenTree::Iden(assoc)) if assoc == "left" => true,
+            Some(TokenTree::Iden(assoc)) if assoc == "right" => false,
+            _ => {
+                return Err(Syntax::error(
+                    Code::InvalidOperatorDeclaration,
+                    "Expected `left` or `right` after the operator being declared",
+                    &name_span,
+                ))
+            }
+        };
+        *trees_idx += 1;
+
+        let prec = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Op(like),
+                span,
+            }) => {
+                let prec = match self.operators.get(like) {
+                    Some(infix) => infix.prec,
+                    None => Parser::op_prec(Parser::to_op(like, span)?),
+                };
+                if !matches!(
+                    prec,
+                    Prec::Compose
+                        | Prec::Or
+                        | Prec::And
+                        | Prec::Compare
+                        | Prec::AddSub
+                        | Prec::MulDiv
+                        | Prec::Pow
+                ) {
+                    return Err(Syntax::error(
+                        Code::InvalidOperatorDeclaration,
+                        &format!("An operator can not have the same precedence as `{}`", like),
+                        span,
+                    ));
+                }
+                prec
+            }
+            _ => {
+                return Err(Syntax::error(
+                    Code::InvalidOperatorDeclaration,
+                    usage,
+                    &keyword_span,
+                ))
+            }
+        };
+        *trees_idx += 1;
+
+        let (bound, fun_span) = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Iden(fun),
+                span,
+            }) if ResIden::try_new(fun).is_none() => (self.intern_symbol(fun), span.clone()),
+            _ => {
+                return Err(Syntax::error(
+                    Code::InvalidOperatorDeclaration,
+                    usage,
+                    &keyword_span,
+                ))
+            }
+        };
+        *trees_idx += 1;
+
+        // `#++ = x -> concat x`, so that `concat` is looked up here,
+        // and the operator stays polymorphic if `concat` is;
+        // each declaration gets a variable of its own,
+        // as the number of symbols grows with each one
+        let hidden = format!("#{}{}", name, self.symbols.len());
+        let fun = self.intern_symbol(&hidden);
+        let arg = self.intern_symbol("#operands");
+        let span = Span::combine(&keyword_span, &fun_span);
+        let tree = |item: AST| Spanned::new(item, fun_span.clone());
+        let call = Base::call(
+            tree(AST::Base(Base::Symbol(bound))),
+            tree(AST::Base(Base::Symbol(arg))),
+        );
+        let function = AST::Lambda(Lambda::new(
+            Spanned::new(Pattern::Symbol(arg), fun_span.clone()),
+            tree(AST::Base(call)),
+        ));
+        let pattern = Spanned::new(Pattern::Symbol(fun), span.clone());
+
+        self.operators.insert(name, Infix { prec, is_left, fun });
+        Ok(Spanned::new(
+            AST::Base(Base::assign(pattern, tree(function))),
+            span,
+        ))
+    }
+
+    /// Parses a use of an operator declared with `infix`,
+    /// which calls the function bound to it.
+    fn infix_use(
+        &mut self,
+        left: Spanned<AST>,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let tree = &trees[*trees_idx];
+        let infix = match &tree.item {
+            TokenTree::Op(name) => self.operators[name].clone(),
+            _ => unreachable!("Only operators can be declared with `infix`"),
+        };
+
+        let prec = if infix.is_left {
+            infix.prec.left()
+        } else {
+            infix.prec
+        };
+        let fun = Spanned::new(AST::Base(Base::Symbol(infix.fun)), tree.span.clone());
+        *trees_idx += 1; // move on from operator
+        let right = self.expr(trees, trees_idx, prec)?;
+
+        let combined = Span::combine(&left.span, &right.span);
+        Ok(Spanned::new(
+            AST::Sugar(Sugar::infix(fun, left, right)),
+            combined,
+        ))
+    }
 
-                dbg!(rest);
+    /// Parses a handle expression, like `handle { body } with resume { Write x -> ... }`.
+    /// The body is wrapped in a function so that it can be suspended
+    /// when it raises an effect.
+    /// Each arm handles a declared effect.
+    /// If a name follows `with`, like `resume` above, the arms can call it
+    /// with a value to continue the body from where the effect was raised.
+    /// The arms are compiled to a single handler that matches
+    /// on the effect and its continuation, i.e. `(Write x, resume)`.
+    /// An effect that none of the arms match is raised again,
+    /// to be handled by an enclosing handler,
+    /// and the body is continued with whatever that handler resumes with.
+    fn handle(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let keyword_span = trees[*trees_idx].span.clone();
+        *trees_idx += 1; // move on from the keyword
 
-                // if rest.len() != 1 {
-                //     return Err(Syntax::error(
-                //         "Expected a single type",
-                //         rest.span,
-                //     ));
-                // }
+        let body = self.block(trees, trees_idx, &keyword_span, "`handle`")?;
+        match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Iden(iden),
+                ..
+            }) if iden == "with" => *trees_idx += 1,
+            _ => {
+                return Err(Syntax::error(
+                    Code::IncompleteKeyword,
+                    "Expected `with` followed by the arms that handle this body",
+                    &Span::combine(&keyword_span, &body.span),
+                ))
+            }
+        }
+        let with_span = trees[*trees_idx - 1].span.clone();
 
-                todo!()
+        // the continuation is only bound to a name if one is given
+        let continuation = self.intern_symbol("#resume");
+        let resume = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Iden(name),
+                ..
+            }) => {
+                *trees_idx += 1;
+                self.intern_symbol(name)
             }
-            If => todo!(),
-            Match => todo!(),
-            Mod => todo!(),
+            _ => continuation,
+        };
+        let block = self.block(trees, trees_idx, &with_span, "`with`")?;
+        let span = Span::combine(&keyword_span, &block.span);
+
+        let mut effects = vec![];
+        let mut arms = vec![];
+        for arm in Parser::arms(block)? {
+            let Lambda { arg, body } = match arm.item {
+                AST::Lambda(lambda) => lambda,
+                _ => unreachable!("Arms are always functions"),
+            };
+
+            match &arg.item {
+                Pattern::Label(name, _) if self.effects.contains(&name.item) => {
+                    if !effects.contains(&name.item) {
+                        effects.push(name.item.clone());
+                    }
+                }
+                _ => {
+                    return Err(Syntax::error(
+                        Code::InvalidArm,
+                        "Expected an arm that handles a declared effect, like `Write x -> ...`",
+                        &arg.span,
+                    ))
+                }
+            }
+
+            let arg_span = arg.span.clone();
+            let resume = Spanned::new(Pattern::Symbol(resume), arg_span.clone());
+            let arg = Spanned::new(Pattern::Tuple(vec![arg, resume]), arg_span);
+            arms.push(Spanned::new(AST::Lambda(Lambda { arg, body }), arm.span));
+        }
+
+        // raise effects that no arm matches again, i.e.
+        // `(Write #payload, #resume: Any) -> #resume (Write #payload)`,
+        // where the continuation is not typed, like the effect it resumes
+        let payload = self.intern_symbol("#payload");
+        for effect in effects.iter() {
+            let name = Spanned::new(effect.clone(), span.clone());
+            let symbol = |symbol| Spanned::new(AST::Base(Base::Symbol(symbol)), span.clone());
+            let bind = |symbol| Spanned::new(Pattern::Symbol(symbol), span.clone());
+
+            let label = Pattern::label(name.clone(), bind(payload));
+            let any = Spanned::new(Type::Name("Any".to_string()), span.clone());
+            let resume = Pattern::is(bind(continuation), any);
+            let arg = Pattern::Tuple(vec![
+                Spanned::new(label, span.clone()),
+                Spanned::new(resume, span.clone()),
+            ]);
+            let raise = Spanned::new(AST::Base(Base::effect(name, symbol(payload))), span.clone());
+            let body = Spanned::new(
+                AST::Base(Base::call(symbol(continuation), raise)),
+                span.clone(),
+            );
+            let forward = Lambda::new(Spanned::new(arg, span.clone()), body);
+            arms.push(Spanned::new(AST::Lambda(forward), span.clone()));
+        }
+
+        let handled = self.intern_symbol("#handled");
+        let value = Spanned::new(AST::Base(Base::Symbol(handled)), span.clone());
+        let handler = Spanned::new(
+            AST::Lambda(Lambda::new(
+                Spanned::new(Pattern::Symbol(handled), span.clone()),
+                Spanned::new(AST::Base(Base::match_(value, arms)), span.clone()),
+            )),
+            span.clone(),
+        );
+
+        let body = Spanned::new(
+            AST::Lambda(Lambda::new(
+                Spanned::new(Pattern::Lit(Lit::Unit), body.span.clone()),
+                body,
+            )),
+            span.clone(),
+        );
+
+        Ok(Spanned::new(
+            AST::Base(Base::handle(body, handler, effects)),
+            span,
+        ))
+    }
+
+    /// Parses the block that must follow a keyword.
+    fn block(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+        keyword_span: &Span,
+        keyword: &str,
+    ) -> Result<Spanned<AST>, Syntax> {
+        match trees.get(*trees_idx) {
+            Some(
+                tree @ Spanned {
+                    item: TokenTree::Block(_),
+                    ..
+                },
+            ) => {
+                *trees_idx += 1;
+                self.rule_prefix(tree)
+            }
+            _ => Err(Syntax::error(
+                Code::IncompleteKeyword,
+                &format!("Expected a block after {}", keyword),
+                keyword_span,
+            )),
+        }
+    }
+
+    /// Finds the first chained pattern nested within a pattern, if any.
+    fn chained<S>(pattern: &Spanned<Pattern<S>>) -> Option<&Span> {
+        match &pattern.item {
+            Pattern::Symbol(_) | Pattern::Lit(_) => None,
+            Pattern::Label(_, inner) => Parser::chained(inner),
+            Pattern::Tuple(items) => items.iter().find_map(Parser::chained),
+            Pattern::Record(fields) => fields.iter().find_map(|(_, field)| Parser::chained(field)),
+            Pattern::List(items, rest) => items
+                .iter()
+                .chain(rest.as_deref())
+                .find_map(Parser::chained),
+            Pattern::Chain(_) => Some(&pattern.span),
+            Pattern::Is(inner, _) => Parser::chained(inner),
+        }
+    }
+
+    /// Parses everything after a keyword up until the next block,
+    /// for instance, the condition in `if cond { ... }`.
+    /// Leaves `trees_idx` pointing at the block.
+    fn before_block(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+        expected: &str,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let keyword = &trees[*trees_idx];
+        *trees_idx += 1; // move on from the keyword
+
+        let start = *trees_idx;
+        while *trees_idx < trees.len() && !matches!(trees[*trees_idx].item, TokenTree::Block(_)) {
+            *trees_idx += 1;
+        }
+
+        if start == *trees_idx {
+            return Err(Syntax::error(
+                Code::IncompleteKeyword,
+                &format!(
+                    "Expected a {} after `{}`",
+                    expected,
+                    keyword.span.contents()
+                ),
+                &keyword.span,
+            ));
         }
+        if *trees_idx >= trees.len() {
+            return Err(Syntax::error(
+                Code::IncompleteKeyword,
+                &format!("Expected a block after the {}", expected),
+                &Span::combine(&keyword.span, &trees[*trees_idx - 1].span),
+            ));
+        }
+
+        self.expr(&trees[start..*trees_idx].to_vec(), &mut 0, Prec::None)
     }
 
     /// Constructs the AST for a literal, such as a number
@@ -317,6 +1138,7 @@ impl Parser {
             AST::Base(Base::Lit(lit.clone()))
         } else {
             return Err(Syntax::error(
+                Code::UnexpectedToken,
                 &format!("Expected a literal, found {}", &tree.item),
                 &tree.span,
             ));
@@ -338,19 +1160,29 @@ impl Parser {
         }
     }
 
+    /// Looks up the name a symbol was interned from.
+    fn symbol_name(&self, symbol: SharedSymbol) -> String {
+        self.symbols
+            .iter()
+            .find(|(_, interned)| **interned == symbol)
+            .map(|(name, _)| name.to_string())
+            .expect("symbols are always interned from a name")
+    }
+
     /// Parses a Label.
     fn label(&mut self, tree: &Spanned<TokenTree>) -> Result<Spanned<AST>, Syntax> {
         // TODO: keep track of labels for typedefs?
-        let symbol = if let TokenTree::Label(label) = &tree.item {
-            self.intern_symbol(label)
+        let name = if let TokenTree::Label(label) = &tree.item {
+            label.to_string()
         } else {
             return Err(Syntax::error(
+                Code::UnexpectedToken,
                 &format!("Expected a label, found {}", &tree.item),
                 &tree.span,
             ));
         };
         Ok(Spanned::new(
-            AST::Base(Base::Symbol(symbol)),
+            AST::Base(Base::Label(name)),
             tree.span.clone(),
         ))
     }
@@ -380,6 +1212,7 @@ impl Parser {
             self.intern_symbol(iden)
         } else {
             return Err(Syntax::error(
+                Code::UnexpectedToken,
                 &format!("Expected an identifier, found {}", &tree.item),
                 &tree.span,
             ));
@@ -407,6 +1240,15 @@ impl Parser {
         let argument = self.expr(trees, trees_idx, Prec::Call.left())?;
         let combined = Span::combine(&left.span, &argument.span);
 
+        // applying a declared effect raises it
+        if let AST::Base(Base::Label(name)) = &left.item {
+            if self.effects.contains(name) {
+                let name = Spanned::new(name.to_string(), left.span.clone());
+                let effect = Base::effect(name, argument);
+                return Ok(Spanned::new(AST::Base(effect), combined));
+            }
+        }
+
         let mut form = match left.item {
             AST::Sugar(Sugar::Form(f)) => f,
             _ => vec![left],
@@ -439,6 +1281,499 @@ impl Parser {
         Ok(Spanned::new(make_ast(left, right), combined))
     }
 
+    /// Parses a type annotation, like `x: Integer`.
+    /// A single type follows the `:`, so types with operators in them,
+    /// like functions, must be wrapped in parentheses,
+    /// as in `x: (Integer -> Integer) -> x 1`.
+    /// The exception is an annotated assignment,
+    /// like `f: Integer -> Integer = x -> x`,
+    /// where nothing but the type can come before the `=`.
+    fn is(
+        &mut self,
+        left: Spanned<AST>,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let is_span = trees[*trees_idx].span.clone();
+        *trees_idx += 1; // move on from operator
+        let assigned = trees[*trees_idx..]
+            .iter()
+            .any(|tree| Parser::is_op(Some(tree), ResOp::Assign));
+        let ty = if assigned {
+            self.type_function(trees, trees_idx, &is_span)?
+        } else {
+            self.type_term(trees, trees_idx, &is_span)?
+        };
+
+        let combined = Span::combine(&left.span, &ty.span);
+        Ok(Spanned::new(AST::Base(Base::is(left, ty)), combined))
+    }
+
+    /// Parses a single type:
+    /// a named type like `Integer`, a type variable like `a`,
+    /// a label wrapping a type like `Some Integer`,
+    /// a list type like `[Integer]`, a map type like `[String => Integer]`,
+    /// or any type in parentheses.
+    fn type_term(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+        after: &Span,
+    ) -> Result<Spanned<Type>, Syntax> {
+        let tree = match trees.get(*trees_idx) {
+            Some(tree) => tree,
+            None => return Err(Syntax::error(Code::InvalidType, "Expected a type", after)),
+        };
+        *trees_idx += 1;
+
+        let ty = match &tree.item {
+            TokenTree::Label(name) => match trees.get(*trees_idx).map(|next| &next.item) {
+                Some(
+                    TokenTree::Label(_)
+                    | TokenTree::Iden(_)
+                    | TokenTree::Form(_)
+                    | TokenTree::List(_)
+                    | TokenTree::Lit(Lit::Unit),
+                ) => {
+                    let inner = self.type_term(trees, trees_idx, &tree.span)?;
+                    let span = Span::combine(&tree.span, &inner.span);
+                    let name = Spanned::new(name.to_string(), tree.span.clone());
+                    return Ok(Spanned::new(Type::label(name, inner), span));
+                }
+                _ => Type::Name(name.to_string()),
+            },
+            TokenTree::Iden(name) => Type::Var(name.to_string()),
+            TokenTree::Lit(Lit::Unit) => Type::Tuple(vec![]),
+            TokenTree::Form(inner) => return self.type_group(inner, &tree.span),
+            TokenTree::List(inner) => {
+                let mut inner_idx = 0;
+                let item = self.type_function(inner, &mut inner_idx, &tree.span)?;
+                let ty = if Parser::is_op(inner.get(inner_idx), ResOp::Entry) {
+                    let entry = inner[inner_idx].span.clone();
+                    inner_idx += 1;
+                    Type::map(item, self.type_function(inner, &mut inner_idx, &entry)?)
+                } else {
+                    Type::list(item)
+                };
+
+                if let Some(extra) = inner.get(inner_idx) {
+                    return Err(Syntax::error(
+                        Code::InvalidType,
+                        &format!("Unexpected {} in type", extra.item),
+                        &extra.span,
+                    ));
+                }
+                ty
+            }
+            other => {
+                return Err(Syntax::error(
+                    Code::InvalidType,
+                    &format!("Expected a type, found {}", other),
+                    &tree.span,
+                ))
+            }
+        };
+
+        Ok(Spanned::new(ty, tree.span.clone()))
+    }
+
+    /// Parses the type inside a pair of parentheses,
+    /// which may be a tuple, like `(Integer, String)`,
+    /// or a function, like `(Integer -> String)`.
+    fn type_group(&mut self, trees: &TokenTrees, span: &Span) -> Result<Spanned<Type>, Syntax> {
+        let mut items = vec![];
+        let mut trailing = false;
+        let mut trees_idx = 0;
+
+        while trees_idx < trees.len() {
+            items.push(self.type_function(trees, &mut trees_idx, span)?);
+            trailing = Parser::is_op(trees.get(trees_idx), ResOp::Pair);
+            if trailing {
+                trees_idx += 1;
+            } else if let Some(tree) = trees.get(trees_idx) {
+                return Err(Syntax::error(
+                    Code::InvalidType,
+                    &format!("Unexpected {} in type", tree.item),
+                    &tree.span,
+                ));
+            }
+        }
+
+        if items.len() == 1 && !trailing {
+            let item = items.pop().unwrap();
+            return Ok(Spanned::new(item.item, span.clone()));
+        }
+        Ok(Spanned::new(Type::Tuple(items), span.clone()))
+    }
+
+    /// Parses a function type, which associates right.
+    /// The effects a function may raise are listed after a `/`,
+    /// either one, like `Integer -> () / Write`,
+    /// or many in parentheses, like `Integer -> () / (Write, Ask)`.
+    fn type_function(
+        &mut self,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+        after: &Span,
+    ) -> Result<Spanned<Type>, Syntax> {
+        let arg = self.type_term(trees, trees_idx, after)?;
+        if !Parser::is_op(trees.get(*trees_idx), ResOp::Lambda) {
+            return Ok(arg);
+        }
+        let arrow_span = trees[*trees_idx].span.clone();
+        *trees_idx += 1;
+
+        let body = self.type_function(trees, trees_idx, &arrow_span)?;
+        let mut span = Span::combine(&arg.span, &body.span);
+
+        let mut effects = vec![];
+        if Parser::is_op(trees.get(*trees_idx), ResOp::Div) {
+            let div_span = trees[*trees_idx].span.clone();
+            *trees_idx += 1;
+            let names =
+                match trees.get(*trees_idx) {
+                    Some(
+                        tree @ Spanned {
+                            item: TokenTree::Label(_),
+                            ..
+                        },
+                    ) => vec![tree],
+                    Some(Spanned {
+                        item: TokenTree::Form(names),
+                        ..
+                    }) => names
+                        .iter()
+                        .filter(|name| !Parser::is_op(Some(name), ResOp::Pair))
+                        .collect(),
+                    _ => return Err(Syntax::error(
+                        Code::InvalidType,
+                        "Expected the effects this function may raise after `/`, like `/ Write`",
+                        &div_span,
+                    )),
+                };
+            span = Span::combine(&span, &trees[*trees_idx].span);
+            *trees_idx += 1;
+
+            for name in names {
+                match &name.item {
+                    TokenTree::Label(effect) if self.effects.contains(effect) => {
+                        effects.push(Spanned::new(effect.to_string(), name.span.clone()))
+                    }
+                    TokenTree::Label(effect) => {
+                        return Err(Syntax::error(
+                            Code::UndeclaredEffect,
+                            &format!("The effect `{}` has not been declared", effect),
+                            &name.span,
+                        ))
+                    }
+                    other => {
+                        return Err(Syntax::error(
+                            Code::InvalidType,
+                            &format!("Expected the name of an effect, found {}", other),
+                            &name.span,
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(Spanned::new(Type::function(arg, body, effects), span))
+    }
+
+    /// Returns whether a block is a record, like `{ x: 1, y: 2 }`,
+    /// rather than a series of expressions.
+    /// Each line of a record starts with a field name followed by a `:`.
+    /// Blocks that assign to an annotated variable, like `{ x: Integer = 1 }`,
+    /// are not records.
+    pub(crate) fn is_record(lines: &[Spanned<TokenTrees>]) -> bool {
+        let fields = lines.iter().all(|line| {
+            matches!(
+                line.item.first(),
+                Some(Spanned {
+                    item: TokenTree::Iden(_),
+                    ..
+                })
+            ) && Parser::is_op(line.item.get(1), ResOp::Is)
+        });
+        let assigns = lines.iter().any(|line| {
+            line.item
+                .iter()
+                .any(|tree| Parser::is_op(Some(tree), ResOp::Assign))
+        });
+        !lines.is_empty() && fields && !assigns
+    }
+
+    /// Whether a block is a record update, like `{ point with x = 1 }`.
+    pub(crate) fn is_update(lines: &[Spanned<TokenTrees>]) -> bool {
+        let first = match lines.first() {
+            Some(first) => &first.item,
+            None => return false,
+        };
+
+        // a `with` that belongs to a `handle` is not an update
+        for tree in first.iter() {
+            match &tree.item {
+                TokenTree::Iden(iden) if iden == "handle" => return false,
+                TokenTree::Iden(iden) if iden == "with" => return true,
+                _ => (),
+            }
+        }
+        false
+    }
+
+    /// Parses a record update, like `{ point with x = 1, y = 2 }`,
+    /// which copies a record, replacing the value of some of its fields.
+    fn update(
+        &mut self,
+        lines: &[Spanned<TokenTrees>],
+        span: &Span,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let first = &lines[0].item;
+        let with = first
+            .iter()
+            .position(|tree| matches!(&tree.item, TokenTree::Iden(iden) if iden == "with"))
+            .unwrap();
+
+        if with == 0 {
+            return Err(Syntax::error(
+                Code::InvalidRecord,
+                "Expected the record to update before `with`",
+                &first[0].span,
+            ));
+        }
+        let record = self.expr(&first[..with].to_vec(), &mut 0, Prec::None)?;
+
+        let mut rest = vec![first[with + 1..].to_vec()];
+        rest.extend(lines[1..].iter().map(|line| line.item.clone()));
+        rest.retain(|line| !line.is_empty());
+        if rest.is_empty() {
+            return Err(Syntax::error(
+                Code::InvalidRecord,
+                "Expected the fields to update after `with`",
+                &first[with].span,
+            ));
+        }
+
+        let fields = self.fields(&rest, ResOp::Assign)?;
+        Ok(Spanned::new(
+            AST::Base(Base::update(record, fields)),
+            span.clone(),
+        ))
+    }
+
+    /// Parses the fields of a record, like `x: 1, y: 2`,
+    /// where the name of each field is followed by a `separator`.
+    /// Fields are separated by commas or newlines.
+    fn fields(
+        &mut self,
+        lines: &[TokenTrees],
+        separator: ResOp,
+    ) -> Result<Fields<Spanned<AST>>, Syntax> {
+        let symbol = match separator {
+            ResOp::Assign => "=",
+            _ => ":",
+        };
+        let mut fields: Fields<Spanned<AST>> = vec![];
+
+        for line in lines {
+            let mut idx = 0;
+            while idx < line.len() {
+                let name = match &line[idx].item {
+                    TokenTree::Iden(name) => Spanned::new(name.to_string(), line[idx].span.clone()),
+                    other => {
+                        return Err(Syntax::error(
+                            Code::InvalidRecord,
+                            &format!("Expected the name of a field, found {}", other),
+                            &line[idx].span,
+                        ))
+                    }
+                };
+                idx += 1;
+
+                if !Parser::is_op(line.get(idx), separator) {
+                    return Err(Syntax::error(
+                        Code::InvalidRecord,
+                        &format!(
+                            "Expected `{}` followed by the value of the field `{}`",
+                            symbol, name.item
+                        ),
+                        &name.span,
+                    ));
+                }
+                idx += 1;
+
+                let value = self.expr(line, &mut idx, Prec::Pair.left())?;
+                if let Some((first, _)) = fields.iter().find(|(field, _)| field.item == name.item) {
+                    return Err(Syntax::error_with_note(
+                        Code::DuplicateField,
+                        &format!("The field `{}` is given more than once", name.item),
+                        Note::new_with_hint("first given here", &first.span),
+                    )
+                    .add_note(Note::new_with_hint("and again here", &name.span)));
+                }
+                fields.push((name, value));
+
+                if Parser::is_op(line.get(idx), ResOp::Pair) {
+                    idx += 1;
+                } else if idx < line.len() {
+                    return Err(Syntax::error(
+                        Code::InvalidRecord,
+                        "Expected a comma between the fields of a record",
+                        &line[idx].span,
+                    ));
+                }
+            }
+        }
+
+        Ok(fields)
+    }
+
+    /// Parses a list, like `[1, 2, 3]`.
+    /// The last item may be spread with `..`, as in `[0, ..rest]`,
+    /// which adds the items before it to the front of another list.
+    fn list(&mut self, trees: &TokenTrees, span: &Span) -> Result<Spanned<AST>, Syntax> {
+        let mut items = vec![];
+        let mut rest = None;
+        let mut idx = 0;
+
+        while idx < trees.len() {
+            let spread = Parser::is_op(trees.get(idx), ResOp::Spread);
+            if spread {
+                idx += 1;
+            }
+            let item = self.expr(trees, &mut idx, Prec::Pair.left())?;
+
+            if Parser::is_op(trees.get(idx), ResOp::Pair) {
+                idx += 1;
+            } else if let Some(tree) = trees.get(idx) {
+                return Err(Syntax::error(
+                    Code::InvalidCollection,
+                    "Expected a comma between the items of a list",
+                    &tree.span,
+                ));
+            }
+
+            if !spread {
+                items.push(item);
+            } else if idx < trees.len() {
+                return Err(Syntax::error(
+                    Code::InvalidCollection,
+                    "Only the last item in a list can be spread with `..`",
+                    &item.span,
+                ));
+            } else {
+                rest = Some(item);
+            }
+        }
+
+        Ok(Spanned::new(
+            AST::Base(Base::list(items, rest)),
+            span.clone(),
+        ))
+    }
+
+    /// Whether the items in square brackets are a map, like `[1 => 2]`,
+    /// rather than a list.
+    fn is_map(items: &TokenTrees) -> bool {
+        items
+            .iter()
+            .any(|tree| Parser::is_op(Some(tree), ResOp::Entry))
+    }
+
+    /// Parses a map, like `["one" => 1, "two" => 2]`.
+    /// The empty map is written `[=>]`.
+    fn map(&mut self, trees: &TokenTrees, span: &Span) -> Result<Spanned<AST>, Syntax> {
+        let mut entries = vec![];
+        let mut idx = 0;
+
+        if trees.len() == 1 {
+            return Ok(Spanned::new(AST::Base(Base::Map(entries)), span.clone()));
+        }
+
+        while idx < trees.len() {
+            let key = self.expr(trees, &mut idx, Prec::Pair.left())?;
+            if !Parser::is_op(trees.get(idx), ResOp::Entry) {
+                return Err(Syntax::error(
+                    Code::InvalidCollection,
+                    "Expected `=>` followed by the value of this key",
+                    &key.span,
+                ));
+            }
+            idx += 1;
+
+            let value = self.expr(trees, &mut idx, Prec::Pair.left())?;
+            entries.push((key, value));
+
+            if Parser::is_op(trees.get(idx), ResOp::Pair) {
+                idx += 1;
+            } else if let Some(tree) = trees.get(idx) {
+                return Err(Syntax::error(
+                    Code::InvalidCollection,
+                    "Expected a comma between the entries of a map",
+                    &tree.span,
+                ));
+            }
+        }
+
+        Ok(Spanned::new(AST::Base(Base::Map(entries)), span.clone()))
+    }
+
+    /// Parses a field access, like `point.x`.
+    fn field(
+        &mut self,
+        left: Spanned<AST>,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+    ) -> Result<Spanned<AST>, Syntax> {
+        let dot = trees[*trees_idx].span.clone();
+        *trees_idx += 1;
+
+        let name = match trees.get(*trees_idx) {
+            Some(Spanned {
+                item: TokenTree::Iden(name),
+                span,
+            }) => Spanned::new(name.to_string(), span.clone()),
+            _ => {
+                return Err(Syntax::error(
+                    Code::InvalidRecord,
+                    "Expected the name of a field after `.`",
+                    &dot,
+                ))
+            }
+        };
+        *trees_idx += 1;
+
+        let span = Span::combine(&left.span, &name.span);
+        Ok(Spanned::new(AST::Base(Base::field(left, name)), span))
+    }
+
+    /// Returns whether a token tree is a specific operator.
+    fn is_op(tree: Option<&Spanned<TokenTree>>, op: ResOp) -> bool {
+        match tree {
+            Some(Spanned {
+                item: TokenTree::Op(name),
+                ..
+            }) => ResOp::try_new(name) == Some(op),
+            _ => false,
+        }
+    }
+
+    /// Parses a builtin binary operator, like `+` or `**`,
+    /// which is compiled directly to the corresponding opcode.
+    fn operator(
+        &mut self,
+        left: Spanned<AST>,
+        trees: &TokenTrees,
+        trees_idx: &mut usize,
+        is_left: bool,
+        op: ResOp,
+        bin_op: BinOp,
+    ) -> Result<Spanned<AST>, Syntax> {
+        self.binop(left, trees, trees_idx, is_left, op, |l, r| {
+            AST::Base(Base::bin_op(bin_op, l, r))
+        })
+    }
+
     /// Parses a lambda definition, associates right.
     fn lambda(
         &mut self,
@@ -449,7 +1784,7 @@ impl Parser {
         let left_span = left.span.clone();
         let pattern = left
             .try_map(Pattern::try_from)
-            .map_err(|e| Syntax::error(&e, &left_span))?;
+            .map_err(|e| Syntax::error(Code::InvalidPattern, &e, &left_span))?;
         self.binop(pattern, trees, trees_idx, false, ResOp::Lambda, |l, r| {
             AST::Lambda(Lambda::new(l, r))
         })
@@ -465,7 +1800,7 @@ impl Parser {
         let left_span = left.span.clone();
         let pattern = left
             .try_map(Pattern::<SharedSymbol>::try_from)
-            .map_err(|e| Syntax::error(&e, &left_span))?;
+            .map_err(|e| Syntax::error(Code::InvalidPattern, &e, &left_span))?;
         self.binop(pattern, trees, trees_idx, false, ResOp::Assign, |l, r| {
             AST::Base(Base::assign(l, r))
         })
@@ -532,14 +1867,235 @@ mod tests {
         test_source("effect Write\n")
     }
 
+    #[test]
+    fn arithmetic() {
+        test_source("x = 1 + 2 * 3 ** 2 ** 2 - 4 / 5 % 6")
+    }
+
+    #[test]
+    fn if_else() {
+        test_source("x = if a { b } else if c { d } else { e }")
+    }
+
+    #[test]
+    fn match_arms() {
+        test_source("match x {\n    (0, y) -> y,\n    Some z -> z\n    _ -> 1\n}")
+    }
+
+    #[test]
+    fn handle_effects() {
+        test_source("effect Ask\nhandle { Ask () + 1 } with resume {\n    Ask () -> resume 2\n}")
+    }
+
+    #[test]
+    fn annotations() {
+        // the parentheses around the type of an assignment are optional
+        for ty in [
+            "(Integer -> (a, b) -> Some a / (Write,))",
+            "Integer -> (a, b) -> Some a / (Write,)",
+        ] {
+            let source = format!(
+                "effect Write\nf: {} = x: Integer -> (y, z): (a, b) -> y",
+                ty
+            );
+            let tokens = Lexer::lex(Source::source(&source)).unwrap();
+            let (ast, _) = Parser::parse(Reader::read(tokens).unwrap()).unwrap();
+
+            let f = match ast.item {
+                AST::Base(Base::Block(mut block)) => block.pop().unwrap(),
+                _ => unreachable!(),
+            };
+            let ty = match f.item {
+                AST::Base(Base::Assign(
+                    Spanned {
+                        item: Pattern::Is(_, ty),
+                        ..
+                    },
+                    _,
+                )) => ty.item,
+                other => panic!("expected an annotated assignment, found {:?}", other),
+            };
+            let (arg, body) = match ty {
+                Type::Function { arg, body, effects } if effects.is_empty() => {
+                    (arg.item, body.item)
+                }
+                other => panic!("expected a function type, found {:?}", other),
+            };
+            assert_eq!(arg, Type::Name("Integer".to_string()));
+            match body {
+                Type::Function { arg, body, effects } => {
+                    assert!(matches!(arg.item, Type::Tuple(items) if items.len() == 2));
+                    assert!(matches!(body.item, Type::Label(name, _) if name.item == "Some"));
+                    assert_eq!(effects[0].item, "Write");
+                }
+                other => panic!("expected a function type, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn undeclared_effect_in_type() {
+        let tokens = Lexer::lex(Source::source("f: (() -> () / Write) = x -> x")).unwrap();
+        let result = Parser::parse(Reader::read(tokens).unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn records() {
+        test_source("point = { x: 1, y: 2 }\nmoved = { point with x = 3 }.x");
+        test_source("{\n    x: 1\n    y: 2,\n}");
+        test_source("{ x: Integer = 1\nx }");
+        test_source("effect Ask\n{ handle { Ask () } with { Ask x -> 2 } }");
+    }
+
+    #[test]
+    fn duplicate_fields() {
+        let tokens = Lexer::lex(Source::source("{ x: 1, y: 2, x: 3 }")).unwrap();
+        let error = Parser::parse(Reader::read(tokens).unwrap())
+            .unwrap_err()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(error.reason, "The field `x` is given more than once");
+        assert_eq!(error.notes.len(), 2);
+    }
+
+    #[test]
+    fn recovers() {
+        let source = "a = (1 +)\nb = 2\nc = [1, f (2 *), 3]\nd = 4 4 =";
+        let tokens = Lexer::lex(Source::source(source)).unwrap();
+        let errors = Parser::parse(Reader::read(tokens).unwrap()).unwrap_err();
+        let lines = errors
+            .errors()
+            .iter()
+            .map(|error| error.notes[0].span.format().start)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn lists() {
+        test_source("xs = [1, 2, 3]\nys = [0, ..xs]\n[]");
+        test_source("[\n    1\n    , 2,\n]");
+        test_source("first = [head, .._] -> head");
+        test_source("xs: [Integer] = [1]");
+
+        let tokens = Lexer::lex(Source::source("[..xs, 1]")).unwrap();
+        let error = Parser::parse(Reader::read(tokens).unwrap())
+            .unwrap_err()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(
+            error.reason,
+            "Only the last item in a list can be spread with `..`"
+        );
+    }
+
+    #[test]
+    fn maps() {
+        test_source("ages = [\"ann\" => 31, \"bo\" => 27]\nempty = [=>]");
+        test_source("[\n    1 => x -> x,\n    2 => x -> x + 1,\n]");
+        test_source("m: [String => [Integer]] = [\"a\" => [1]]");
+
+        let tokens = Lexer::lex(Source::source("[1 => 2, 3]")).unwrap();
+        let error = Parser::parse(Reader::read(tokens).unwrap())
+            .unwrap_err()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(
+            error.reason,
+            "Expected `=>` followed by the value of this key"
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        test_source("x = 1 + 2 == 3\ny = 1 < 2\nz = (1, 2) != (2, 1)");
+
+        let tokens = Lexer::lex(Source::source("1 + 2 <= 3 * 4")).unwrap();
+        let (ast, _) = Parser::parse(Reader::read(tokens).unwrap()).unwrap();
+        let line = match ast.item {
+            AST::Base(Base::Block(mut lines)) => lines.remove(0).item,
+            other => panic!("expected a block, found {:?}", other),
+        };
+        assert!(matches!(
+            line,
+            AST::Base(Base::BinOp(BinOp::LessEqual, _, _))
+        ));
+    }
+
+    #[test]
+    fn logic() {
+        test_source("x = !a && b || c == d\ny = f !x");
+
+        let tokens = Lexer::lex(Source::source("!a == b || c && d")).unwrap();
+        let (ast, _) = Parser::parse(Reader::read(tokens).unwrap()).unwrap();
+        let line = match ast.item {
+            AST::Base(Base::Block(mut lines)) => lines.remove(0).item,
+            other => panic!("expected a block, found {:?}", other),
+        };
+        let (left, right) = match line {
+            AST::Base(Base::BinOp(BinOp::Or, left, right)) => (left.item, right.item),
+            other => panic!("expected `||`, found {:?}", other),
+        };
+        assert!(matches!(left, AST::Base(Base::BinOp(BinOp::Equal, _, _))));
+        assert!(matches!(right, AST::Base(Base::BinOp(BinOp::And, _, _))));
+    }
+
+    #[test]
+    fn infix() {
+        let tokens = Lexer::lex(Source::source(
+            "infix ++ left + concat\ninfix <> right ++ compose\na ++ b * c ++ d\na <> b <> c",
+        ))
+        .unwrap();
+        let (ast, _) = Parser::parse(Reader::read(tokens).unwrap()).unwrap();
+        let lines = match ast.item {
+            AST::Base(Base::Block(lines)) => lines,
+            other => panic!("expected a block, found {:?}", other),
+        };
+        let operands = |line: &Spanned<AST>| match &line.item {
+            AST::Sugar(Sugar::Infix(_, left, right)) => (left.item.clone(), right.item.clone()),
+            other => panic!("expected a declared operator, found {:?}", other),
+        };
+
+        // `++` associates left, and `*` binds tighter
+        let (left, right) = operands(&lines[2]);
+        assert!(matches!(left, AST::Sugar(Sugar::Infix(_, _, _))));
+        assert!(matches!(right, AST::Base(Base::Symbol(_))));
+
+        // `<>` associates right
+        let (left, right) = operands(&lines[3]);
+        assert!(matches!(left, AST::Base(Base::Symbol(_))));
+        assert!(matches!(right, AST::Sugar(Sugar::Infix(_, _, _))));
+
+        let undeclared = Lexer::lex(Source::source("a ++ b")).unwrap();
+        assert!(Parser::parse(Reader::read(undeclared).unwrap()).is_err());
+        let builtin = Lexer::lex(Source::source("infix + left * add")).unwrap();
+        assert!(Parser::parse(Reader::read(builtin).unwrap()).is_err());
+    }
+
     #[test]
     fn test_trailing_comma() {
         test_source("((),)")
     }
 
-    // TODO: once effects are in place
-    // #[test]
-    // fn negation() {
-    //     test_source("- 1")
-    // }
+    #[test]
+    fn negation() {
+        test_source("- 1");
+        test_source("x = -a ** 2 * -b\ny = f (-1) - 1\nz = 2*-1");
+
+        let tokens = Lexer::lex(Source::source("-a ** 2 * b")).unwrap();
+        let (ast, _) = Parser::parse(Reader::read(tokens).unwrap()).unwrap();
+        let line = match ast.item {
+            AST::Base(Base::Block(mut lines)) => lines.remove(0).item,
+            other => panic!("expected a block, found {:?}", other),
+        };
+        let left = match line {
+            AST::Base(Base::BinOp(BinOp::Mul, left, _)) => left.item,
+            other => panic!("expected `*`, found {:?}", other),
+        };
+        assert!(matches!(left, AST::Base(Base::UnOp(UnOp::Neg, _))));
+    }
 }
@@ -1,7 +1,10 @@
+use std::mem;
+
 use crate::{
     common::span::{Span, Spanned},
-    compiler::syntax::{Note, Syntax},
+    compiler::syntax::{Diagnostics, Note, Syntax},
     construct::token::{Delim, Token, TokenTree, TokenTrees, Tokens},
+    explain::Code,
 };
 
 pub struct Reader {
@@ -9,31 +12,52 @@ pub struct Reader {
     index: usize,
     // stack of nested groupings
     opening: Vec<Spanned<Delim>>,
+    /// Errors found so far.
+    /// The reader recovers from each error and keeps reading.
+    errors: Vec<Syntax>,
 }
 
 // TODO: return Token
 
 impl Reader {
-    pub fn read(tokens: Spanned<Tokens>) -> Result<Spanned<TokenTree>, Syntax> {
-        let mut reader = Reader {
+    fn new(tokens: Spanned<Tokens>) -> Reader {
+        Reader {
             tokens,
             index: 0,
             opening: vec![],
-        };
+            errors: vec![],
+        }
+    }
 
-        let result = reader.block()?;
+    /// Reads a stream of tokens into a tree of nested groups.
+    /// Every mismatched or unexpected closing delimiter is reported,
+    /// as is every opening delimiter that is never closed.
+    pub fn read(tokens: Spanned<Tokens>) -> Result<Spanned<TokenTree>, Diagnostics> {
+        let mut reader = Reader::new(tokens);
+
+        let result = reader.block();
 
         // if there are still unclosed delimiters on the opening
         // stack
-        if !reader.opening.is_empty() {
-            let still_opened = reader.opening.last().unwrap();
-            Err(Syntax::error(
+        for still_opened in mem::take(&mut reader.opening) {
+            reader.errors.push(Syntax::error(
+                Code::UnclosedDelimiter,
                 &format!("Unclosed opening {}", still_opened.item,),
                 &still_opened.span,
-            ))
-        } else {
-            Ok(result)
+            ));
         }
+
+        Diagnostics::check(reader.errors)?;
+        Ok(result)
+    }
+
+    /// Returns whether the tokens run out while some delimiters are still open,
+    /// i.e. whether more tokens are needed before they can be read.
+    /// This is used by the REPL to read entries that span multiple lines.
+    pub fn is_unclosed(tokens: Spanned<Tokens>) -> bool {
+        let mut reader = Reader::new(tokens);
+        reader.block();
+        !reader.opening.is_empty()
     }
 
     /// Returns the next token, advancing the lexer by 1.
@@ -64,28 +88,24 @@ impl Reader {
         Some(token)
     }
 
-    fn form(&mut self) -> Result<Spanned<TokenTrees>, Syntax> {
+    fn form(&mut self) -> Spanned<TokenTrees> {
         let mut tokens: TokenTrees = vec![];
 
         let entire_span = loop {
             let token = match self.next_token() {
                 Some(t) => t,
-                None => {
-                    // TODO: unwrap to the end of source span?
-                    return Err(Syntax::error(
-                        "Unexpected end of source while parsing form",
-                        &Spanned::build(&tokens).unwrap_or_else(|| self.tokens.span.clone()),
-                    ));
-                }
+                // The opening delimiter is still on the stack,
+                // so it is reported as unclosed once reading is done.
+                None => break Spanned::build(&tokens).unwrap_or_else(|| self.tokens.span.clone()),
             };
 
             let span = token.span;
             let item = match token.item {
-                Token::Open(delim) => self.enter_group(Spanned::new(delim, span.clone()))?,
-                Token::Close(delim) => {
-                    let span = self.exit_group(Spanned::new(delim, span))?;
-                    break span;
-                }
+                Token::Open(delim) => self.enter_group(Spanned::new(delim, span.clone())),
+                Token::Close(delim) => match self.exit_group(Spanned::new(delim, span)) {
+                    Some(span) => break span,
+                    None => continue,
+                },
                 Token::Sep => continue,
 
                 // Trivial conversion
@@ -95,10 +115,10 @@ impl Reader {
             tokens.push(item);
         };
 
-        Ok(Spanned::new(tokens, entire_span))
+        Spanned::new(tokens, entire_span)
     }
 
-    fn block(&mut self) -> Result<Spanned<TokenTree>, Syntax> {
+    fn block(&mut self) -> Spanned<TokenTree> {
         let mut lines: Vec<Spanned<TokenTrees>> = vec![];
         let mut line: TokenTrees = vec![];
         let mut after_sep = false;
@@ -109,7 +129,7 @@ impl Reader {
                 Some(t) => t,
                 // We didn't hit a closing `}`, so this must either be the main
                 // body, or we're missing a closing `}`. The missing closing `}`
-                // is handled by `exit_group`, so we just need to break with a
+                // is reported by `read`, so we just need to break with a
                 // realistic span.
                 // TODO: get a realistic span!
                 None => break self.tokens.span.clone(),
@@ -117,17 +137,24 @@ impl Reader {
 
             let span = token.span;
             let item = match token.item {
-                Token::Open(delim) => self.enter_group(Spanned::new(delim, span.clone()))?,
-                Token::Close(delim) => {
-                    let span = self.exit_group(Spanned::new(delim, span))?;
-                    break span;
-                }
+                Token::Open(delim) => self.enter_group(Spanned::new(delim, span.clone())),
+                Token::Close(delim) => match self.exit_group(Spanned::new(delim, span)) {
+                    Some(span) => break span,
+                    None => continue,
+                },
                 Token::Sep => {
                     after_sep = true;
                     continue;
                 }
 
                 Token::Op(op) => {
+                    // `!` is only ever a prefix operator,
+                    // so it always starts a new line
+                    if op == "!" && after_sep && !after_op && !line.is_empty() {
+                        let line_span = Spanned::build(&line).unwrap();
+                        lines.push(Spanned::new(mem::take(&mut line), line_span));
+                    }
+
                     let spanned = Spanned::new(TokenTree::Op(op), span);
                     line.push(spanned);
                     after_sep = false;
@@ -158,44 +185,53 @@ impl Reader {
             lines.push(spanned_line);
         }
 
-        Ok(Spanned::new(TokenTree::Block(lines), entire_span))
+        Spanned::new(TokenTree::Block(lines), entire_span)
     }
 
-    fn enter_group(&mut self, delim: Spanned<Delim>) -> Result<Spanned<TokenTree>, Syntax> {
+    fn enter_group(&mut self, delim: Spanned<Delim>) -> Spanned<TokenTree> {
         self.opening.push(delim.clone());
 
-        let tree = match delim.item {
-            Delim::Curly => self.block()?,
-            Delim::Paren => self.form()?.map(|x| TokenTree::Form(x)),
-            Delim::Square => self.form()?.map(|x| TokenTree::List(x)),
-        };
-
-        return Ok(tree);
+        match delim.item {
+            Delim::Curly => self.block(),
+            Delim::Paren => self.form().map(TokenTree::Form),
+            Delim::Square => self.form().map(TokenTree::List),
+        }
     }
 
-    fn exit_group(&mut self, closing_delim: Spanned<Delim>) -> Result<Span, Syntax> {
-        let opening_delim = self.opening.pop().ok_or_else(|| {
-            Syntax::error(
-                &format!("Unexpected closing {}", closing_delim.item),
-                &closing_delim.span,
-            )
-        })?;
+    /// Closes the innermost open group, returning its span.
+    /// A closing delimiter that does not match the opening one
+    /// still closes the group, after reporting an error.
+    /// A closing delimiter with no open group is reported and skipped,
+    /// in which case this returns `None`.
+    fn exit_group(&mut self, closing_delim: Spanned<Delim>) -> Option<Span> {
+        let opening_delim = match self.opening.pop() {
+            Some(opening_delim) => opening_delim,
+            None => {
+                self.errors.push(Syntax::error(
+                    Code::UnexpectedDelimiter,
+                    &format!("Unexpected closing {}", closing_delim.item),
+                    &closing_delim.span,
+                ));
+                return None;
+            }
+        };
 
-        if opening_delim.item == closing_delim.item {
-            return Ok(Span::combine(&opening_delim.span, &closing_delim.span));
+        let span = Span::combine(&opening_delim.span, &closing_delim.span);
+        if opening_delim.item != closing_delim.item {
+            let error = Syntax::error(
+                Code::MismatchedDelimiter,
+                &format!(
+                    "Mismatched opening {} and closing {}",
+                    opening_delim.item, closing_delim.item,
+                ),
+                &span,
+            )
+            .add_note(Note::new(opening_delim.span))
+            .add_note(Note::new(closing_delim.span));
+            self.errors.push(error);
         }
 
-        let error = Syntax::error(
-            &format!(
-                "Mismatched opening {} and closing {}",
-                closing_delim.item, opening_delim.item,
-            ),
-            &Span::combine(&opening_delim.span, &closing_delim.span),
-        )
-        .add_note(Note::new(opening_delim.span))
-        .add_note(Note::new(closing_delim.span));
-
-        Err(error)
+        Some(span)
     }
 }
 
@@ -300,6 +336,15 @@ mod test {
         assert!(token_tree.is_err());
     }
 
+    #[test]
+    fn unclosed() {
+        let unclosed = |source| Reader::is_unclosed(Lexer::lex(Source::source(source)).unwrap());
+        assert!(unclosed("f = x -> {\n    x"));
+        assert!(unclosed("print (1 +"));
+        assert!(!unclosed("f = x -> { x }"));
+        assert!(!unclosed("x }"));
+    }
+
     #[test]
     fn form_with_group() {
         let source = Source::source("print (1 + 2)");
@@ -341,6 +386,17 @@ mod test {
         assert!(token_tree.is_ok())
     }
 
+    #[test]
+    fn prefix_not_line() {
+        let source = Source::source("a = b\n!a\nc\n- d");
+        let tokens = Lexer::lex(source).unwrap();
+        let lines = match Reader::read(tokens).unwrap().item {
+            TokenTree::Block(lines) => lines,
+            _ => unreachable!(),
+        };
+        assert_eq!(lines.len(), 3);
+    }
+
     #[test]
     fn unclosed_opening_paren() {
         let source = Source::source("(");
@@ -357,6 +413,27 @@ mod test {
         assert!(token_tree.is_err());
     }
 
+    #[test]
+    fn recovers() {
+        let source = Source::source("a = (1 ]\nb = }\nc = {[2 + 3\nd = 4");
+        let tokens = Lexer::lex(source).unwrap();
+        let errors = Reader::read(tokens).unwrap_err();
+        let reasons = errors
+            .errors()
+            .iter()
+            .map(|error| error.reason.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                "Mismatched opening parenthesis and closing square brackets",
+                "Unexpected closing curly brackets",
+                "Unclosed opening curly brackets",
+                "Unclosed opening square brackets",
+            ]
+        );
+    }
+
     #[test]
     fn multiline_block() {
         let tokens = Lexer::lex(Source::source("2\n+2")).unwrap();
@@ -0,0 +1,170 @@
+use std::rc::Rc;
+
+use crate::{
+    common::{closure::Closure, lambda::Lambda, Labels, Source},
+    compiler::{
+        Compiler, Desugarer, Diagnostics, Expander, Hoister, Lexer, Parser, Reader, Unifier,
+    },
+    vm::fiber::Fiber,
+};
+
+/// Compiles a program one entry at a time, as in a REPL.
+/// Variables (and their types), effects, and labels from earlier entries
+/// remain visible in later ones, as do macros.
+///
+/// Each entry is compiled to a root `Lambda` whose locals extend
+/// the locals of the entry before it, so it should be run on the
+/// `Fiber` that ran the previous entries, through `Fiber::extend`.
+/// That fiber should be made with `Session::fiber`.
+pub struct Session {
+    expander: Expander,
+    parser: Parser,
+    hoister: Hoister,
+    unifier: Unifier,
+    /// The registry of all labels interned so far.
+    labels: Labels,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            expander: Expander::new(),
+            parser: Parser::new(),
+            hoister: Hoister::new(),
+            unifier: Unifier::new(),
+            labels: Labels::new(),
+        }
+    }
+
+    /// A new `Fiber` to run the entries on,
+    /// which shares the registry labels are interned in.
+    pub fn fiber(&self) -> Fiber {
+        let mut lambda = Lambda::empty();
+        lambda.labels = self.labels.clone();
+        Fiber::init(Closure::wrap(Rc::new(lambda)))
+    }
+
+    /// Compiles the next entry.
+    /// If the entry does not compile,
+    /// it is as if the entry was never compiled at all
+    /// (though labels it mentions stay registered, which is harmless).
+    pub fn compile(&mut self, source: Rc<Source>) -> Result<Rc<Lambda>, Diagnostics> {
+        let expander = self.expander.clone();
+        let hoister = self.hoister.clone();
+        let unifier = self.unifier.clone();
+
+        let result = self.entry(source);
+        if result.is_err() {
+            self.expander = expander;
+            self.hoister = hoister;
+            self.unifier = unifier;
+        }
+
+        return result;
+    }
+
+    fn entry(&mut self, source: Rc<Source>) -> Result<Rc<Lambda>, Diagnostics> {
+        let tokens = Lexer::lex(source)?;
+        let token_tree = Reader::read(tokens)?;
+        let token_tree = self.expander.expand_entry(token_tree)?;
+        let ast = self.parser.parse_entry(&token_tree)?;
+        let cst = Desugarer::desugar(ast);
+        let (sst, scope) = self.hoister.hoist_entry(cst)?;
+        self.unifier.unify_entry(&sst)?;
+        Ok(Compiler::compile_entry(sst, scope, &self.labels)?)
+    }
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{label::ROOT, Data},
+        vm::trace::TraceKind,
+    };
+
+    fn run(fiber: &mut Fiber, session: &mut Session, source: &str) -> Data {
+        let lambda = session.compile(Source::source(source)).unwrap();
+        fiber.extend(Closure::wrap(lambda));
+        fiber.run().unwrap();
+        fiber.stack.pop_data()
+    }
+
+    #[test]
+    fn keeps_bindings() {
+        let mut session = Session::new();
+        let mut fiber = session.fiber();
+
+        run(&mut fiber, &mut session, "x = 2");
+        run(&mut fiber, &mut session, "double = n -> n * x");
+        run(&mut fiber, &mut session, "y = double 3");
+        assert_eq!(run(&mut fiber, &mut session, "x + y"), Data::Integer(8));
+    }
+
+    #[test]
+    fn forgets_failed_entries() {
+        let mut session = Session::new();
+        let mut fiber = session.fiber();
+
+        run(&mut fiber, &mut session, "x = 1");
+        assert!(session.compile(Source::source("y = z")).is_err());
+        assert!(session.compile(Source::source("y")).is_err());
+        assert_eq!(run(&mut fiber, &mut session, "x"), Data::Integer(1));
+    }
+
+    #[test]
+    fn keeps_types() {
+        let mut session = Session::new();
+        let mut fiber = session.fiber();
+
+        run(&mut fiber, &mut session, "id = x -> x");
+        run(&mut fiber, &mut session, "y = id 1");
+        assert!(session.compile(Source::source("y 2")).is_err());
+        assert_eq!(
+            run(&mut fiber, &mut session, "id \"one\""),
+            Data::String("one".into())
+        );
+    }
+
+    #[test]
+    fn survives_runtime_errors() {
+        let mut session = Session::new();
+        let mut fiber = session.fiber();
+
+        run(&mut fiber, &mut session, "x = 1");
+        let lambda = session.compile(Source::source("y = 1 / 0")).unwrap();
+        fiber.extend(Closure::wrap(lambda));
+        assert!(fiber.run().is_err());
+
+        assert_eq!(run(&mut fiber, &mut session, "x"), Data::Integer(1));
+        let lambda = session.compile(Source::source("y")).unwrap();
+        fiber.extend(Closure::wrap(lambda));
+        assert_eq!(fiber.run().unwrap_err().kind(), &TraceKind::Uninitialized);
+    }
+
+    #[test]
+    fn keeps_labels() {
+        let mut session = Session::new();
+        let mut fiber = session.fiber();
+
+        run(&mut fiber, &mut session, "x = Some 1");
+        let some = run(&mut fiber, &mut session, "Some (x, 2)");
+        assert_eq!(some.to_string(), "Some (Some 1, 2)");
+        assert_eq!(
+            run(&mut fiber, &mut session, "Some y = x\ny"),
+            Data::Integer(1)
+        );
+
+        let kind = fiber.labels.intern(ROOT, "Some");
+        assert_eq!(
+            run(&mut fiber, &mut session, "x"),
+            Data::Label(kind, Box::new(Data::Integer(1)))
+        );
+    }
+}
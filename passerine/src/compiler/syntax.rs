@@ -1,11 +1,11 @@
 use std::fmt;
 
-use crate::common::span::Span;
+use crate::{common::span::Span, explain::Code};
 
 /// Represents a note attached to a Syntax error,
 /// i.e. a location in source code with an optional
 /// specific hint or tip corresponding this this specific location
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Note {
     pub span: Span,
     pub hint: Option<String>,
@@ -27,16 +27,20 @@ impl Note {
 /// Represents a static error (syntax, semantics, etc.) found at compile time.
 /// Ideally, each note included should have a distinct `Span` and hint.
 /// Usually, one `Note` per error is enough.
-#[derive(Debug, PartialEq, Eq)]
+/// Each error has a stable [`Code`], which can be explained at length,
+/// and can be matched on instead of the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Syntax {
+    pub code: Code,
     pub reason: String,
     pub notes: Vec<Note>,
 }
 
 impl Syntax {
     /// Creates a new static error with a single note that does not have a hint.
-    pub fn error(reason: &str, span: &Span) -> Syntax {
+    pub fn error(code: Code, reason: &str, span: &Span) -> Syntax {
         Syntax::error_with_note(
+            code,
             reason,
             Note {
                 span: span.clone(),
@@ -47,8 +51,9 @@ impl Syntax {
 
     /// Creates a new static error with a single note that may or may not have a
     /// hint.
-    pub fn error_with_note(reason: &str, note: Note) -> Syntax {
+    pub fn error_with_note(code: Code, reason: &str, note: Note) -> Syntax {
         Syntax {
+            code,
             reason: reason.to_string(),
             notes: vec![note],
         }
@@ -57,8 +62,9 @@ impl Syntax {
     /// Creates a syntax error without a note. This syntax error will not
     /// contain any location information, so only use it if you plan to add
     /// additional notes with [`add_note`] later.
-    pub fn error_no_note(reason: &str) -> Syntax {
+    pub fn error_no_note(code: Code, reason: &str) -> Syntax {
         Syntax {
+            code,
             reason: reason.to_string(),
             notes: vec![],
         }
@@ -71,39 +77,127 @@ impl Syntax {
     }
 }
 
+/// Writes each note, with the lines of source it points to.
+/// Used to show both errors and warnings.
+pub(crate) fn write_notes(f: &mut fmt::Formatter<'_>, notes: &[Note]) -> fmt::Result {
+    for note in notes.iter() {
+        let formatted = note.span.format();
+
+        if let Some(ref hint) = note.hint {
+            if formatted.is_multiline() {
+                writeln!(f, "{}", formatted)?;
+                writeln!(f, "{} |- note: {} ", formatted.gutter_padding(), hint)?;
+                writeln!(f, "{} |", " ".repeat(formatted.gutter_padding()))?;
+            } else {
+                writeln!(
+                    f,
+                    "In {}:{}:{}",
+                    formatted.path,
+                    formatted.start + 1,
+                    formatted.start_col + 1
+                )?;
+                writeln!(f, "{} |", " ".repeat(formatted.gutter_padding()))?;
+                writeln!(f, "{} | {}", formatted.start + 1, formatted.lines[0])?;
+                writeln!(
+                    f,
+                    "{} | {}{} note: {}",
+                    " ".repeat(formatted.gutter_padding()),
+                    " ".repeat(formatted.start_col),
+                    "^".repeat(formatted.carrots().unwrap()),
+                    hint,
+                )?;
+                writeln!(f, "{} |", " ".repeat(formatted.gutter_padding()))?;
+            }
+            if let Some(expansion) = &formatted.expansion {
+                writeln!(
+                    f,
+                    "{} = note: {}",
+                    " ".repeat(formatted.gutter_padding()),
+                    expansion
+                )?;
+            }
+        } else {
+            write!(f, "{}", formatted)?;
+        }
+    }
+    Ok(())
+}
+
 impl fmt::Display for Syntax {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for note in self.notes.iter() {
-            let formatted = note.span.format();
-
-            if let Some(ref hint) = note.hint {
-                if formatted.is_multiline() {
-                    writeln!(f, "{}", formatted)?;
-                    writeln!(f, "{} |- note: {} ", formatted.gutter_padding(), hint)?;
-                    writeln!(f, "{} |", " ".repeat(formatted.gutter_padding()))?;
-                } else {
-                    writeln!(
-                        f,
-                        "In {}:{}:{}",
-                        formatted.path, formatted.start, formatted.start_col
-                    )?;
-                    writeln!(f, "{} |", " ".repeat(formatted.gutter_padding()))?;
-                    writeln!(f, "{} | {}", formatted.start + 1, formatted.lines[0])?;
-                    writeln!(
-                        f,
-                        "{} | {}{} note: {}",
-                        " ".repeat(formatted.gutter_padding()),
-                        " ".repeat(formatted.start_col),
-                        "^".repeat(formatted.carrots().unwrap()),
-                        hint,
-                    )?;
-                    writeln!(f, "{} |", " ".repeat(formatted.gutter_padding()))?;
-                }
-            } else {
-                write!(f, "{}", formatted)?;
+        write_notes(f, &self.notes)?;
+        write!(f, "Syntax Error [{}]: {}", self.code, self.reason)
+    }
+}
+
+/// All the static errors found while compiling a program,
+/// in the order they were found.
+/// Compilation keeps going after an error where it can,
+/// so that as many errors as possible are reported at once.
+/// There is always at least one error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    errors: Vec<Syntax>,
+}
+
+impl Diagnostics {
+    /// Collects a list of errors,
+    /// returning `None` if the list is empty.
+    pub fn new(errors: Vec<Syntax>) -> Option<Diagnostics> {
+        match errors.is_empty() {
+            true => None,
+            false => Some(Diagnostics { errors }),
+        }
+    }
+
+    /// Returns `Ok(())` if there are no errors,
+    /// and the errors otherwise.
+    pub fn check(errors: Vec<Syntax>) -> Result<(), Diagnostics> {
+        match Diagnostics::new(errors) {
+            None => Ok(()),
+            Some(diagnostics) => Err(diagnostics),
+        }
+    }
+
+    pub fn errors(&self) -> &[Syntax] {
+        &self.errors
+    }
+
+    /// Adds the errors of another collection after these ones.
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.errors.extend(other.errors);
+    }
+}
+
+impl From<Syntax> for Diagnostics {
+    fn from(error: Syntax) -> Diagnostics {
+        Diagnostics {
+            errors: vec![error],
+        }
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Syntax;
+    type IntoIter = std::vec::IntoIter<Syntax>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.errors.iter().enumerate() {
+            if index > 0 {
+                write!(f, "\n\n")?;
             }
+            write!(f, "{}", error)?;
+        }
+        if self.errors.len() > 1 {
+            write!(f, "\n\n{} errors found", self.errors.len())?;
         }
-        write!(f, "Syntax Error: {}", self.reason)
+        Ok(())
     }
 }
 
@@ -120,6 +214,7 @@ mod test {
         // might not coincide with an actual Passerine error
         let source = Rc::new(Source::source("x = \"Hello, world\" -> y + 1"));
         let error = Syntax::error(
+            Code::UnexpectedToken,
             "Unexpected token '\"Hello, world!\"'",
             &Span::new(&source, 4, 14),
         );
@@ -128,7 +223,7 @@ mod test {
   |
 1 | x = "Hello, world" -> y + 1
   |     ^^^^^^^^^^^^^^
-Syntax Error: Unexpected token '"Hello, world!"'"#;
+Syntax Error [E0008]: Unexpected token '"Hello, world!"'"#;
 
         let result = format!("{}", error);
         assert_eq!(result, target);
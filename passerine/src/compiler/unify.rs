@@ -0,0 +1,1053 @@
+use std::collections::HashMap;
+
+use crate::{
+    common::{
+        lit::Lit,
+        span::{Span, Spanned},
+        ty::{Ty, TyPool, TySymbol},
+    },
+    compiler::syntax::{Note, Syntax},
+    construct::{
+        symbol::UniqueSymbol,
+        tree::{Base, BinOp, Fields, Pattern, ScopedLambda, Type, UnOp, SST},
+    },
+    explain::Code,
+};
+
+/// The type of a variable,
+/// which may be generic over some type variables.
+/// For example, the identity function `x -> x` has type `a -> a`
+/// for every type `a`.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<TySymbol>,
+    ty: TySymbol,
+}
+
+/// Infers the type of each node in an `SST`, Hindley-Milner style,
+/// and reports any types that do not line up.
+///
+/// Inference is gradual: constructs the unifier can not reason about yet,
+/// like labels and effects, have type `Any`,
+/// which is compatible with every type and checked at runtime instead.
+/// Functions assigned at the top level are generalized,
+/// so that they can be used at more than one type.
+#[derive(Debug, Clone, Default)]
+pub struct Unifier {
+    pool: TyPool,
+    /// The types of all variables that have been assigned so far.
+    env: HashMap<UniqueSymbol, Scheme>,
+    /// The span each type was inferred from, used when reporting errors.
+    origins: HashMap<TySymbol, Span>,
+    /// The number of functions the current node is nested in.
+    depth: usize,
+}
+
+impl Unifier {
+    pub fn new() -> Unifier {
+        Unifier::default()
+    }
+
+    /// Infers the type of an `SST`,
+    /// returning the type along with the pool it is stored in.
+    pub fn unify(tree: &Spanned<SST>) -> Result<(TySymbol, TyPool), Syntax> {
+        let mut unifier = Unifier::new();
+        let ty = unifier.walk(tree)?;
+        Ok((ty, unifier.pool))
+    }
+
+    /// Infers the type of one entry of a `Session`.
+    /// Variables assigned in earlier entries keep their types.
+    pub fn unify_entry(&mut self, tree: &Spanned<SST>) -> Result<TySymbol, Syntax> {
+        self.walk(tree)
+    }
+
+    /// Adds a type inferred from some span to the pool.
+    fn ty(&mut self, ty: Ty, span: &Span) -> TySymbol {
+        let symbol = self.pool.insert(ty);
+        self.origins.insert(symbol, span.clone());
+        symbol
+    }
+
+    /// Creates a type variable for a type that will be inferred later.
+    fn var(&mut self, span: &Span) -> TySymbol {
+        let symbol = self.pool.var();
+        self.origins.insert(symbol, span.clone());
+        symbol
+    }
+
+    /// Walks an `SST`, returning its type.
+    fn walk(&mut self, tree: &Spanned<SST>) -> Result<TySymbol, Syntax> {
+        let span = &tree.span;
+
+        match &tree.item {
+            SST::Base(Base::Lit(lit)) => Ok(self.lit(lit, span)),
+            SST::Base(Base::Symbol(unique)) => Ok(self.symbol(*unique, span)),
+            SST::Base(Base::Label(_)) => Ok(self.ty(Ty::Any, span)),
+            SST::Base(Base::Tuple(tuple)) => self.tuple(tuple, span),
+            SST::Base(Base::Record(fields)) => self.record(fields, span),
+            SST::Base(Base::List(items, rest)) => self.list(items, rest.as_deref(), span),
+            SST::Base(Base::Map(entries)) => self.map(entries, span),
+            SST::Base(Base::Field(record, name)) => self.field(record, name, span),
+            SST::Base(Base::Update(record, fields)) => self.update(record, fields),
+            // the fields of a module are not typed yet,
+            // so functions it exports stay polymorphic
+            SST::Base(Base::Module(module)) => {
+                self.walk(module)?;
+                Ok(self.ty(Ty::Any, span))
+            }
+            SST::Base(Base::Block(block)) => self.block(block, span),
+            SST::Base(Base::Call(fun, arg)) => self.call(fun, arg, span),
+            SST::Base(Base::BinOp(op, left, right)) => self.bin_op(*op, left, right, span),
+            SST::Base(Base::UnOp(op, operand)) => self.un_op(*op, operand, span),
+            SST::Base(Base::If(cond, then, else_)) => self.if_(cond, then, else_),
+            SST::Base(Base::Match(value, arms)) => self.match_(value, arms, span),
+            SST::Base(Base::Assign(pattern, expression)) => self.assign(pattern, expression, span),
+            SST::Base(Base::Effect(_, payload)) => {
+                self.walk(payload)?;
+                Ok(self.ty(Ty::Any, span))
+            }
+            SST::Base(Base::Handle(body, handler, _)) => {
+                self.walk(body)?;
+                self.walk(handler)?;
+                Ok(self.ty(Ty::Any, span))
+            }
+            SST::Base(Base::Is(expression, ty)) => {
+                let inferred = self.walk(expression)?;
+                let annotated = self.annotation(ty, &mut HashMap::new())?;
+                self.constrain(inferred, annotated)?;
+                Ok(annotated)
+            }
+            SST::ScopedLambda(lambda) => self.lambda(lambda, span, true),
+        }
+    }
+
+    /// Converts a type annotation to a type.
+    /// Type variables with the same name in an annotation stand for the same type.
+    /// Effects are not checked yet, and labels have type `Any`,
+    /// so the hoister warns about annotations with either.
+    fn annotation(
+        &mut self,
+        ty: &Spanned<Type>,
+        vars: &mut HashMap<String, TySymbol>,
+    ) -> Result<TySymbol, Syntax> {
+        let span = &ty.span;
+
+        let converted = match &ty.item {
+            Type::Name(name) => match name.as_str() {
+                "Any" => Ty::Any,
+                "Integer" => Ty::Integer,
+                "Float" => Ty::Float,
+                "Boolean" => Ty::Boolean,
+                "String" => Ty::String,
+                _ => {
+                    return Err(Syntax::error(
+                        Code::UnknownType,
+                        &format!("Unknown type `{}`", name),
+                        span,
+                    ))
+                }
+            },
+            Type::Var(name) => {
+                if let Some(var) = vars.get(name) {
+                    return Ok(*var);
+                }
+                let var = self.var(span);
+                vars.insert(name.to_string(), var);
+                return Ok(var);
+            }
+            Type::Tuple(items) if items.is_empty() => Ty::Unit,
+            Type::Tuple(items) => {
+                let mut converted = vec![];
+                for item in items {
+                    converted.push(self.annotation(item, vars)?);
+                }
+                Ty::Tuple(converted)
+            }
+            Type::List(item) => Ty::List(self.annotation(item, vars)?),
+            Type::Map(key, value) => Ty::Map {
+                key: self.annotation(key, vars)?,
+                value: self.annotation(value, vars)?,
+            },
+            Type::Function { arg, body, .. } => Ty::Function {
+                arg: self.annotation(arg, vars)?,
+                body: self.annotation(body, vars)?,
+                effect: self.var(span),
+            },
+            Type::Label(_, inner) => {
+                self.annotation(inner, vars)?;
+                Ty::Any
+            }
+        };
+
+        Ok(self.ty(converted, span))
+    }
+
+    fn lit(&mut self, lit: &Lit, span: &Span) -> TySymbol {
+        let ty = match lit {
+            Lit::Float(_) => Ty::Float,
+            Lit::Integer(_) => Ty::Integer,
+            Lit::String(_) => Ty::String,
+            Lit::Label(_, _) => Ty::Any,
+            Lit::Unit => Ty::Unit,
+            Lit::Boolean(_) => Ty::Boolean,
+        };
+        self.ty(ty, span)
+    }
+
+    /// Looks up the type of a variable.
+    /// Variables used before they are assigned,
+    /// e.g. in a recursive function, have type `Any`.
+    fn symbol(&mut self, unique: UniqueSymbol, span: &Span) -> TySymbol {
+        match self.env.get(&unique).cloned() {
+            Some(scheme) => self.instantiate(&scheme),
+            None => self.ty(Ty::Any, span),
+        }
+    }
+
+    fn tuple(&mut self, tuple: &[Spanned<SST>], span: &Span) -> Result<TySymbol, Syntax> {
+        let mut items = vec![];
+        for item in tuple {
+            items.push(self.walk(item)?);
+        }
+        Ok(self.ty(Ty::Tuple(items), span))
+    }
+
+    /// Every item in a list has the same type,
+    /// and a list spread onto another has the same type as it.
+    fn list(
+        &mut self,
+        items: &[Spanned<SST>],
+        rest: Option<&Spanned<SST>>,
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        let item = self.var(span);
+        for other in items {
+            let other = self.walk(other)?;
+            self.constrain(other, item)?;
+        }
+
+        let list = self.ty(Ty::List(item), span);
+        if let Some(rest) = rest {
+            let rest = self.walk(rest)?;
+            self.constrain(rest, list)?;
+        }
+        Ok(list)
+    }
+
+    /// Every key in a map has the same type, as does every value.
+    fn map(
+        &mut self,
+        entries: &[(Spanned<SST>, Spanned<SST>)],
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        let (key, value) = (self.var(span), self.var(span));
+        for (other_key, other_value) in entries {
+            let other_key = self.walk(other_key)?;
+            self.constrain(other_key, key)?;
+            let other_value = self.walk(other_value)?;
+            self.constrain(other_value, value)?;
+        }
+        Ok(self.ty(Ty::Map { key, value }, span))
+    }
+
+    fn record(&mut self, fields: &Fields<Spanned<SST>>, span: &Span) -> Result<TySymbol, Syntax> {
+        let mut tys = vec![];
+        for (name, value) in fields {
+            tys.push((name.item.clone(), self.walk(value)?));
+        }
+        tys.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(self.ty(Ty::Record(tys), span))
+    }
+
+    /// Looks up the type of a field in a record.
+    /// If the type of the record is not known yet,
+    /// the field is checked at runtime.
+    fn field(
+        &mut self,
+        record: &Spanned<SST>,
+        name: &Spanned<String>,
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        let record_ty = self.walk(record)?;
+
+        match self.pool.get(record_ty).cloned() {
+            None | Some(Ty::Any) => Ok(self.ty(Ty::Any, span)),
+            Some(Ty::Record(fields)) => {
+                match fields.iter().find(|(field, _)| field == &name.item) {
+                    Some((_, ty)) => Ok(*ty),
+                    None => Err(self.no_field(record_ty, name)),
+                }
+            }
+            Some(_) => Err(self.explain(
+                Syntax::error_with_note(
+                    Code::MissingField,
+                    &format!(
+                        "{} is not a record, so it has no field `{}`",
+                        self.pool.display(record_ty),
+                        name.item
+                    ),
+                    Note::new_with_hint("accessed here", &name.span),
+                ),
+                record_ty,
+            )),
+        }
+    }
+
+    /// An updated record has the same type as the original,
+    /// so each field updated must already exist and keep its type.
+    fn update(
+        &mut self,
+        record: &Spanned<SST>,
+        fields: &Fields<Spanned<SST>>,
+    ) -> Result<TySymbol, Syntax> {
+        let record_ty = self.walk(record)?;
+        let known = match self.pool.get(record_ty).cloned() {
+            Some(Ty::Record(known)) => Some(known),
+            _ => None,
+        };
+
+        for (name, value) in fields {
+            let value_ty = self.walk(value)?;
+            if let Some(known) = &known {
+                match known.iter().find(|(field, _)| field == &name.item) {
+                    Some((_, ty)) => self.constrain(value_ty, *ty)?,
+                    None => return Err(self.no_field(record_ty, name)),
+                }
+            }
+        }
+
+        Ok(record_ty)
+    }
+
+    fn no_field(&self, record: TySymbol, name: &Spanned<String>) -> Syntax {
+        self.explain(
+            Syntax::error_with_note(
+                Code::MissingField,
+                &format!(
+                    "The record {} has no field `{}`",
+                    self.pool.display(record),
+                    name.item
+                ),
+                Note::new_with_hint("accessed here", &name.span),
+            ),
+            record,
+        )
+    }
+
+    fn block(&mut self, block: &[Spanned<SST>], span: &Span) -> Result<TySymbol, Syntax> {
+        let mut last = None;
+        for child in block {
+            last = Some(self.walk(child)?);
+        }
+        Ok(last.unwrap_or_else(|| self.ty(Ty::Unit, span)))
+    }
+
+    fn call(
+        &mut self,
+        fun: &Spanned<SST>,
+        arg: &Spanned<SST>,
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        // a function of `()` that is called right away runs once, like a block,
+        // so the functions it assigns are generalized as if it were not there;
+        // the root module of a package is wrapped in one
+        let fun_ty = match (&fun.item, &arg.item) {
+            (SST::ScopedLambda(lambda), SST::Base(Base::Lit(Lit::Unit)))
+                if lambda.arg.item == Pattern::Lit(Lit::Unit) =>
+            {
+                self.lambda(lambda, &fun.span, false)?
+            }
+            _ => self.walk(fun)?,
+        };
+        let arg_ty = self.walk(arg)?;
+
+        match self.pool.get(fun_ty) {
+            Some(Ty::Any) => return Ok(self.ty(Ty::Any, span)),
+            Some(Ty::Function { .. }) | None => (),
+            Some(_) => {
+                let found = self.pool.display(fun_ty);
+                return Err(self.explain(
+                    Syntax::error_with_note(
+                        Code::NotAFunction,
+                        &format!("{} is not a function and can not be called", found),
+                        Note::new_with_hint("called here", &fun.span),
+                    ),
+                    fun_ty,
+                ));
+            }
+        }
+
+        let body = self.var(span);
+        let effect = self.var(span);
+        let expected = self.ty(
+            Ty::Function {
+                arg: arg_ty,
+                body,
+                effect,
+            },
+            &fun.span,
+        );
+        self.constrain(fun_ty, expected)?;
+        Ok(body)
+    }
+
+    /// Numbers can be mixed freely: an integer and a float make a float.
+    /// Strings can be added together.
+    /// If the type of an operand is not known yet,
+    /// the result is checked at runtime.
+    fn bin_op(
+        &mut self,
+        op: BinOp,
+        left: &Spanned<SST>,
+        right: &Spanned<SST>,
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        let left_ty = self.walk(left)?;
+        let right_ty = self.walk(right)?;
+
+        if op.is_comparison() {
+            return self.comparison(op, (left, left_ty), (right, right_ty), span);
+        }
+
+        if op.is_logic() {
+            let boolean = self.ty(Ty::Boolean, span);
+            self.constrain(left_ty, boolean)?;
+            self.constrain(right_ty, boolean)?;
+            return Ok(boolean);
+        }
+
+        for (operand, ty) in [(left, left_ty), (right, right_ty)] {
+            match self.pool.get(ty) {
+                None | Some(Ty::Any | Ty::Integer | Ty::Float | Ty::String) => (),
+                Some(_) => {
+                    let found = self.pool.display(ty);
+                    return Err(Syntax::error_with_note(
+                        Code::InvalidOperands,
+                        &format!(
+                            "The operator '{}' can not be applied to {}",
+                            op.symbol(),
+                            found
+                        ),
+                        Note::new_with_hint(&format!("this has type {}", found), &operand.span),
+                    ));
+                }
+            }
+        }
+
+        let ty = match (self.pool.get(left_ty), self.pool.get(right_ty)) {
+            (None | Some(Ty::Any), _) | (_, None | Some(Ty::Any)) => Ty::Any,
+            (Some(Ty::Integer), Some(Ty::Integer)) if op == BinOp::Pow => Ty::Any,
+            (Some(Ty::Integer), Some(Ty::Integer)) => Ty::Integer,
+            (Some(Ty::Integer | Ty::Float), Some(Ty::Integer | Ty::Float)) => Ty::Float,
+            (Some(Ty::String), Some(Ty::String)) if op == BinOp::Add => Ty::String,
+            _ => {
+                let (found_left, found_right) =
+                    (self.pool.display(left_ty), self.pool.display(right_ty));
+                return Err(Syntax::error_no_note(
+                    Code::InvalidOperands,
+                    &format!(
+                        "The operator '{}' can not be applied to {} and {}",
+                        op.symbol(),
+                        found_left,
+                        found_right,
+                    ),
+                )
+                .add_note(Note::new_with_hint(
+                    &format!("this has type {}", found_left),
+                    &left.span,
+                ))
+                .add_note(Note::new_with_hint(
+                    &format!("this has type {}", found_right),
+                    &right.span,
+                )));
+            }
+        };
+
+        Ok(self.ty(ty, span))
+    }
+
+    /// Any two values of the same type can be checked for equality.
+    /// Numbers of the same type, and strings, can be ordered;
+    /// an integer is never equal to a float, so the two can not be ordered.
+    /// Either way, the result is a boolean.
+    fn comparison(
+        &mut self,
+        op: BinOp,
+        (left, left_ty): (&Spanned<SST>, TySymbol),
+        (right, right_ty): (&Spanned<SST>, TySymbol),
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        if let BinOp::Equal | BinOp::NotEqual = op {
+            self.constrain(left_ty, right_ty)?;
+            return Ok(self.ty(Ty::Boolean, span));
+        }
+
+        match (self.pool.get(left_ty), self.pool.get(right_ty)) {
+            (None | Some(Ty::Any), _) | (_, None | Some(Ty::Any)) => (),
+            (Some(Ty::Integer), Some(Ty::Integer)) => (),
+            (Some(Ty::Float), Some(Ty::Float)) => (),
+            (Some(Ty::String), Some(Ty::String)) => (),
+            _ => {
+                let (found_left, found_right) =
+                    (self.pool.display(left_ty), self.pool.display(right_ty));
+                return Err(Syntax::error_no_note(
+                    Code::InvalidOperands,
+                    &format!(
+                        "The operator '{}' can not compare {} and {}",
+                        op.symbol(),
+                        found_left,
+                        found_right,
+                    ),
+                )
+                .add_note(Note::new_with_hint(
+                    &format!("this has type {}", found_left),
+                    &left.span,
+                ))
+                .add_note(Note::new_with_hint(
+                    &format!("this has type {}", found_right),
+                    &right.span,
+                )));
+            }
+        }
+
+        Ok(self.ty(Ty::Boolean, span))
+    }
+
+    /// `-` negates an integer or a float, returning the same type.
+    /// `!` takes a boolean and returns a boolean.
+    fn un_op(&mut self, op: UnOp, operand: &Spanned<SST>, span: &Span) -> Result<TySymbol, Syntax> {
+        let operand_ty = self.walk(operand)?;
+        match op {
+            UnOp::Neg => match self.pool.get(operand_ty) {
+                None | Some(Ty::Any | Ty::Integer | Ty::Float) => Ok(operand_ty),
+                Some(_) => {
+                    let found = self.pool.display(operand_ty);
+                    Err(Syntax::error_with_note(
+                        Code::InvalidOperands,
+                        &format!(
+                            "The operator '{}' can not be applied to {}",
+                            op.symbol(),
+                            found
+                        ),
+                        Note::new_with_hint(&format!("this has type {}", found), &operand.span),
+                    ))
+                }
+            },
+            UnOp::Not => {
+                let boolean = self.ty(Ty::Boolean, span);
+                self.constrain(operand_ty, boolean)?;
+                Ok(boolean)
+            }
+        }
+    }
+
+    fn if_(
+        &mut self,
+        cond: &Spanned<SST>,
+        then: &Spanned<SST>,
+        else_: &Spanned<SST>,
+    ) -> Result<TySymbol, Syntax> {
+        let cond_ty = self.walk(cond)?;
+        let boolean = self.ty(Ty::Boolean, &cond.span);
+        self.constrain(cond_ty, boolean)?;
+
+        let then_ty = self.walk(then)?;
+        let else_ty = self.walk(else_)?;
+        self.constrain(then_ty, else_ty)?;
+        Ok(then_ty)
+    }
+
+    /// Each arm is a function that takes the value being matched,
+    /// and all arms must return the same type.
+    fn match_(
+        &mut self,
+        value: &Spanned<SST>,
+        arms: &[Spanned<SST>],
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        let value_ty = self.walk(value)?;
+        let result = self.var(span);
+
+        for arm in arms {
+            let arm_ty = self.walk(arm)?;
+            let effect = self.var(&arm.span);
+            let expected = self.ty(
+                Ty::Function {
+                    arg: value_ty,
+                    body: result,
+                    effect,
+                },
+                &arm.span,
+            );
+            self.constrain(arm_ty, expected)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Functions assigned to a variable for the first time
+    /// at the top level are generalized.
+    fn assign(
+        &mut self,
+        pattern: &Spanned<Pattern<UniqueSymbol>>,
+        expression: &Spanned<SST>,
+        span: &Span,
+    ) -> Result<TySymbol, Syntax> {
+        let ty = self.walk(expression)?;
+
+        match pattern.item {
+            Pattern::Symbol(unique)
+                if self.depth == 0
+                    && !self.env.contains_key(&unique)
+                    && matches!(expression.item, SST::ScopedLambda(_)) =>
+            {
+                let scheme = self.generalize(ty, unique);
+                self.env.insert(unique, scheme);
+            }
+            _ => {
+                let pattern_ty = self.pattern(pattern)?;
+                self.constrain(ty, pattern_ty)?;
+            }
+        }
+
+        Ok(self.ty(Ty::Unit, span))
+    }
+
+    /// Infers the type of a function.
+    /// If `nested`, its body is one function deeper than the code around it.
+    fn lambda(
+        &mut self,
+        lambda: &ScopedLambda<Spanned<SST>>,
+        span: &Span,
+        nested: bool,
+    ) -> Result<TySymbol, Syntax> {
+        self.depth += nested as usize;
+        let arg = self.pattern(&lambda.arg)?;
+        let body = self.walk(&lambda.body)?;
+        self.depth -= nested as usize;
+
+        // the locals of a function are out of scope everywhere else
+        for local in lambda.scope.locals.items() {
+            self.env.remove(&local);
+        }
+
+        let effect = self.var(span);
+        Ok(self.ty(Ty::Function { arg, body, effect }, span))
+    }
+
+    /// Returns the type of the data a pattern matches,
+    /// binding the variables in the pattern along the way.
+    fn pattern(&mut self, pattern: &Spanned<Pattern<UniqueSymbol>>) -> Result<TySymbol, Syntax> {
+        let span = &pattern.span;
+
+        match &pattern.item {
+            Pattern::Symbol(unique) => Ok(match self.env.get(unique).cloned() {
+                Some(scheme) => self.instantiate(&scheme),
+                None => {
+                    let var = self.var(span);
+                    self.env.insert(
+                        *unique,
+                        Scheme {
+                            vars: vec![],
+                            ty: var,
+                        },
+                    );
+                    var
+                }
+            }),
+            Pattern::Lit(lit) => Ok(self.lit(lit, span)),
+            Pattern::Label(_, pattern) => {
+                self.pattern(pattern)?;
+                Ok(self.ty(Ty::Any, span))
+            }
+            Pattern::Tuple(tuple) => {
+                let mut items = vec![];
+                for item in tuple {
+                    items.push(self.pattern(item)?);
+                }
+                Ok(self.ty(Ty::Tuple(items), span))
+            }
+            Pattern::Record(fields) => {
+                let mut tys = vec![];
+                for (name, field) in fields {
+                    tys.push((name.item.clone(), self.pattern(field)?));
+                }
+                tys.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Ok(self.ty(Ty::Record(tys), span))
+            }
+            Pattern::List(items, rest) => {
+                let item = self.var(span);
+                for other in items {
+                    let other = self.pattern(other)?;
+                    self.constrain(other, item)?;
+                }
+
+                let list = self.ty(Ty::List(item), span);
+                if let Some(rest) = rest {
+                    let rest = self.pattern(rest)?;
+                    self.constrain(rest, list)?;
+                }
+                Ok(list)
+            }
+            Pattern::Is(pattern, ty) => {
+                let inferred = self.pattern(pattern)?;
+                let annotated = self.annotation(ty, &mut HashMap::new())?;
+                self.constrain(inferred, annotated)?;
+                Ok(annotated)
+            }
+            Pattern::Chain(chain) => {
+                let var = self.var(span);
+                for item in chain {
+                    let item_ty = self.pattern(item)?;
+                    self.constrain(item_ty, var)?;
+                }
+                Ok(var)
+            }
+        }
+    }
+
+    /// Generalizes the type of a variable over every type variable
+    /// not shared with the other variables in scope.
+    fn generalize(&self, ty: TySymbol, unique: UniqueSymbol) -> Scheme {
+        let mut shared = vec![];
+        for (other, scheme) in self.env.iter() {
+            if *other != unique {
+                self.pool.vars(scheme.ty, &mut shared);
+            }
+        }
+
+        let mut vars = vec![];
+        self.pool.vars(ty, &mut vars);
+        vars.retain(|var| !shared.contains(var));
+        Scheme { vars, ty }
+    }
+
+    /// Replaces the type variables a scheme is generalized over
+    /// with fresh ones.
+    fn instantiate(&mut self, scheme: &Scheme) -> TySymbol {
+        if scheme.vars.is_empty() {
+            return scheme.ty;
+        }
+
+        let mut fresh = HashMap::new();
+        for var in scheme.vars.iter() {
+            let span = self.origins[var].clone();
+            fresh.insert(*var, self.var(&span));
+        }
+        self.copy(scheme.ty, &fresh)
+    }
+
+    /// Copies a type, replacing some type variables with others.
+    fn copy(&mut self, ty: TySymbol, fresh: &HashMap<TySymbol, TySymbol>) -> TySymbol {
+        let ty = self.pool.find(ty);
+        if let Some(var) = fresh.get(&ty) {
+            return *var;
+        }
+
+        let copied = match self.pool.get(ty).cloned() {
+            Some(Ty::Function { arg, body, effect }) => Ty::Function {
+                arg: self.copy(arg, fresh),
+                body: self.copy(body, fresh),
+                effect: self.copy(effect, fresh),
+            },
+            Some(Ty::Fiber { takes, yields }) => Ty::Fiber {
+                takes: self.copy(takes, fresh),
+                yields: self.copy(yields, fresh),
+            },
+            Some(Ty::Tuple(items)) => Ty::Tuple(self.copy_all(&items, fresh)),
+            Some(Ty::Record(fields)) => Ty::Record(
+                fields
+                    .into_iter()
+                    .map(|(name, field)| (name, self.copy(field, fresh)))
+                    .collect(),
+            ),
+            Some(Ty::Enum(items)) => Ty::Enum(self.copy_all(&items, fresh)),
+            Some(Ty::List(item)) => Ty::List(self.copy(item, fresh)),
+            Some(Ty::Map { key, value }) => Ty::Map {
+                key: self.copy(key, fresh),
+                value: self.copy(value, fresh),
+            },
+            // atomic types and type variables that are not replaced
+            _ => return ty,
+        };
+
+        let span = self.origins[&ty].clone();
+        self.ty(copied, &span)
+    }
+
+    fn copy_all(
+        &mut self,
+        items: &[TySymbol],
+        fresh: &HashMap<TySymbol, TySymbol>,
+    ) -> Vec<TySymbol> {
+        items.iter().map(|item| self.copy(*item, fresh)).collect()
+    }
+
+    /// Makes two types the same, or raises an error if they can't be.
+    fn constrain(&mut self, a: TySymbol, b: TySymbol) -> Result<(), Syntax> {
+        let (a, b) = (self.pool.find(a), self.pool.find(b));
+        if a == b {
+            return Ok(());
+        }
+
+        match (self.pool.get(a).cloned(), self.pool.get(b).cloned()) {
+            (None, _) => self.link(a, b),
+            (_, None) => self.link(b, a),
+            (Some(Ty::Any), _) | (_, Some(Ty::Any)) => Ok(()),
+            (
+                Some(Ty::Function { arg, body, effect }),
+                Some(Ty::Function {
+                    arg: other_arg,
+                    body: other_body,
+                    effect: other_effect,
+                }),
+            ) => {
+                self.constrain(arg, other_arg)?;
+                self.constrain(body, other_body)?;
+                self.constrain(effect, other_effect)
+            }
+            (Some(Ty::Record(fields)), Some(Ty::Record(others)))
+                if fields
+                    .iter()
+                    .map(|(name, _)| name)
+                    .eq(others.iter().map(|(name, _)| name)) =>
+            {
+                for ((_, field), (_, other)) in fields.into_iter().zip(others) {
+                    self.constrain(field, other)?;
+                }
+                Ok(())
+            }
+            (Some(Ty::Tuple(items)), Some(Ty::Tuple(others)))
+            | (Some(Ty::Enum(items)), Some(Ty::Enum(others)))
+                if items.len() == others.len() =>
+            {
+                for (item, other) in items.into_iter().zip(others) {
+                    self.constrain(item, other)?;
+                }
+                Ok(())
+            }
+            (Some(Ty::List(item)), Some(Ty::List(other))) => self.constrain(item, other),
+            (
+                Some(Ty::Map { key, value }),
+                Some(Ty::Map {
+                    key: other_key,
+                    value: other_value,
+                }),
+            ) => {
+                self.constrain(key, other_key)?;
+                self.constrain(value, other_value)
+            }
+            (
+                Some(Ty::Fiber { takes, yields }),
+                Some(Ty::Fiber {
+                    takes: other_takes,
+                    yields: other_yields,
+                }),
+            ) => {
+                self.constrain(takes, other_takes)?;
+                self.constrain(yields, other_yields)
+            }
+            (Some(ty), Some(other)) if ty == other && ty.children().is_empty() => Ok(()),
+            _ => Err(self.mismatch(a, b)),
+        }
+    }
+
+    /// Resolves a type variable to another type,
+    /// as long as that type does not contain the variable itself.
+    fn link(&mut self, var: TySymbol, ty: TySymbol) -> Result<(), Syntax> {
+        let mut vars = vec![];
+        self.pool.vars(ty, &mut vars);
+
+        if vars.contains(&var) {
+            let error = Syntax::error_no_note(
+                Code::InfiniteType,
+                &format!(
+                    "The type {} would have to contain itself",
+                    self.pool.display(ty)
+                ),
+            );
+            return Err(self.explain(error, ty));
+        }
+
+        self.pool.link(var, ty);
+        Ok(())
+    }
+
+    /// Builds an error for two types that do not match,
+    /// pointing out where each type came from.
+    fn mismatch(&self, a: TySymbol, b: TySymbol) -> Syntax {
+        let (found_a, found_b) = (self.pool.display(a), self.pool.display(b));
+        let error = Syntax::error_no_note(
+            Code::MismatchedTypes,
+            &format!("Mismatched types {} and {}", found_a, found_b),
+        );
+
+        match (self.origins.get(&a), self.origins.get(&b)) {
+            (Some(span), Some(other)) if span == other => error.add_note(Note::new_with_hint(
+                &format!(
+                    "this has type {}, but should have type {}",
+                    found_a, found_b
+                ),
+                span,
+            )),
+            _ => self.explain(self.explain(error, a), b),
+        }
+    }
+
+    /// Adds a note pointing out where a type came from to an error.
+    fn explain(&self, error: Syntax, ty: TySymbol) -> Syntax {
+        match self.origins.get(&self.pool.find(ty)) {
+            Some(span) => error.add_note(Note::new_with_hint(
+                &format!("this has type {}", self.pool.display(ty)),
+                span,
+            )),
+            None => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::Source, compiler::hoist};
+
+    fn infer(source: &str) -> Result<String, Syntax> {
+        let (sst, _scope) =
+            hoist(Source::source(source)).map_err(|errors| errors.into_iter().next().unwrap())?;
+        let (ty, pool) = Unifier::unify(&sst)?;
+        Ok(pool.display(ty))
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(infer("x -> x").unwrap(), "a -> a");
+        assert_eq!(infer("f -> x -> f (f x)").unwrap(), "(a -> a) -> a -> a");
+        assert_eq!(
+            infer("(a, b) -> if a { b } else { 0 }").unwrap(),
+            "(Boolean, Integer) -> Integer"
+        );
+    }
+
+    #[test]
+    fn let_polymorphism() {
+        let source = "id = x -> x\n(id 1, id \"one\")";
+        assert_eq!(infer(source).unwrap(), "(Integer, String)");
+    }
+
+    #[test]
+    fn not_a_function() {
+        let error = infer("x = 1\nx 2").unwrap_err();
+        assert_eq!(
+            error.reason,
+            "Integer is not a function and can not be called"
+        );
+        assert_eq!(error.notes.len(), 2);
+    }
+
+    #[test]
+    fn mismatch() {
+        let error = infer("f = x -> if x { 1 } else { 2 }\nf \"yes\"").unwrap_err();
+        assert_eq!(error.reason, "Mismatched types Boolean and String");
+        assert_eq!(error.notes.len(), 2);
+    }
+
+    #[test]
+    fn arms() {
+        assert!(infer("match 1 { 0 -> \"zero\", _ -> 1 }").is_err());
+        assert!(infer("match 1 { \"one\" -> 1, _ -> 0 }").is_err());
+    }
+
+    #[test]
+    fn records() {
+        let source = "p = { y: \"two\", x: 1 }\n{ p with x = 2 }";
+        assert_eq!(infer(source).unwrap(), "{ x: Integer, y: String }");
+        assert_eq!(infer("{ x: 1 }.x").unwrap(), "Integer");
+        assert_eq!(infer("{ x: a } -> a").unwrap(), "{ x: a } -> a");
+        assert_eq!(infer("r -> r.x").unwrap(), "a -> Any");
+
+        let error = infer("{ x: 1 }.y").unwrap_err();
+        assert_eq!(error.reason, "The record { x: Integer } has no field `y`");
+        assert!(infer("p = { x: 1 }\n{ p with x = \"one\" }").is_err());
+        assert!(infer("f = { x: a } -> a\nf { x: 1, y: 2 }").is_err());
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(infer("[1, 2]").unwrap(), "[Integer]");
+        assert_eq!(infer("[]").unwrap(), "[a]");
+        assert_eq!(
+            infer("[head, ..tail] -> (head, tail)").unwrap(),
+            "[a] -> (a, [a])"
+        );
+        assert_eq!(infer("xs: [String] = []\nxs").unwrap(), "[String]");
+        assert!(infer("[1, \"two\"]").is_err());
+        assert!(infer("[1, ..[\"two\"]]").is_err());
+    }
+
+    #[test]
+    fn maps() {
+        assert_eq!(infer("[1 => \"one\"]").unwrap(), "[Integer => String]");
+        assert_eq!(infer("[=>]").unwrap(), "[a => b]");
+        assert_eq!(
+            infer("m: [String => Float] = [=>]\nm").unwrap(),
+            "[String => Float]"
+        );
+        assert!(infer("[1 => \"one\", \"two\" => 2]").is_err());
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(infer("1.0 < 2.5").unwrap(), "Boolean");
+        assert_eq!(infer("(1, \"a\") == (2, \"b\")").unwrap(), "Boolean");
+        assert_eq!(infer("x -> x == 1").unwrap(), "Integer -> Boolean");
+        assert!(infer("1 == \"one\"").is_err());
+        assert!(infer("(1, 2) < (3, 4)").is_err());
+        assert!(infer("\"a\" >= 1").is_err());
+
+        // an integer is never equal to a float, nor ordered against one
+        for op in ["==", "!=", "<", ">", "<=", ">="] {
+            assert!(infer(&format!("1 {} 1.0", op)).is_err());
+            assert!(infer(&format!("1.0 {} 1", op)).is_err());
+        }
+    }
+
+    #[test]
+    fn logic() {
+        assert_eq!(infer("1 < 2 && !(2 < 1)").unwrap(), "Boolean");
+        assert_eq!(
+            infer("(a, b) -> a || b").unwrap(),
+            "(Boolean, Boolean) -> Boolean"
+        );
+        assert!(infer("1 && True").is_err());
+        assert!(infer("!\"no\"").is_err());
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(infer("-1").unwrap(), "Integer");
+        assert_eq!(infer("-2.5 * 2.0").unwrap(), "Float");
+        assert!(infer("-\"one\"").is_err());
+    }
+
+    #[test]
+    fn annotations() {
+        assert_eq!(infer("x: Integer -> x").unwrap(), "Integer -> Integer");
+        assert_eq!(infer("f: (a -> a) = x -> x\nf").unwrap(), "a -> a");
+        assert!(infer("x: (Integer, String) = (1, 2)").is_err());
+        assert!(infer("(x -> x + 1): (String -> Integer)").is_ok());
+        assert!(infer("x: Nat = 1").is_err());
+    }
+
+    #[test]
+    fn infinite() {
+        assert!(infer("x -> x x").is_err());
+    }
+
+    #[test]
+    fn gradual() {
+        assert_eq!(infer("half = x -> x / 2.0\nhalf 5").unwrap(), "Any");
+        assert!(infer("effect Ask\nAsk () 1").is_ok());
+        assert!(infer("() -> later 1\nlater = x -> x").is_ok());
+    }
+}
@@ -79,11 +79,17 @@ impl Display for TokenTree {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResIden {
     Macro,
+    Syntax,
     Type,
     Effect,
     If,
+    Else,
     Match,
     Mod,
+    Use,
+    Handle,
+    With,
+    Infix,
 }
 
 impl ResIden {
@@ -91,11 +97,17 @@ impl ResIden {
         use ResIden::*;
         Some(match name {
             "macro" => Macro,
+            "syntax" => Syntax,
             "type" => Type,
             "effect" => Effect,
             "if" => If,
+            "else" => Else,
             "match" => Match,
             "mod" => Mod,
+            "use" => Use,
+            "handle" => Handle,
+            "with" => With,
+            "infix" => Infix,
             _ => {
                 return None;
             }
@@ -108,9 +120,19 @@ pub enum ResOp {
     Assign,
     Lambda,
     Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    And,
+    Or,
+    Not,
     Pow,
     Compose,
     Field,
+    Spread,
+    Entry,
     Is,
     Pair,
     Add,
@@ -127,9 +149,19 @@ impl ResOp {
             "=" => Assign,
             "->" => Lambda,
             "==" => Equal,
+            "!=" => NotEqual,
+            "<" => Less,
+            ">" => Greater,
+            "<=" => LessEqual,
+            ">=" => GreaterEqual,
+            "&&" => And,
+            "||" => Or,
+            "!" => Not,
             "**" => Pow,
             "|>" => Compose,
             "." => Field,
+            ".." => Spread,
+            "=>" => Entry,
             ":" => Is,
             "," => Pair,
             "+" => Add,
@@ -9,35 +9,212 @@ use crate::{
     },
 };
 
+/// The fields of a record, each a name paired with some value.
+pub type Fields<T> = Vec<(Spanned<String>, T)>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Pattern<S> {
     Symbol(S),
     Lit(Lit),
-    Label(Spanned<S>, Box<Spanned<Self>>),
+    Label(Spanned<String>, Box<Spanned<Self>>),
     Tuple(Vec<Spanned<Self>>),
+    Record(Fields<Spanned<Self>>),
+    List(Vec<Spanned<Self>>, Option<Box<Spanned<Self>>>), // items, rest
     Chain(Vec<Spanned<Self>>),
+    Is(Box<Spanned<Self>>, Spanned<Type>), // pattern, type
 }
 
 impl<S> Pattern<S> {
-    pub fn label(symbol: Spanned<S>, pattern: Spanned<Self>) -> Self {
-        Pattern::Label(symbol, Box::new(pattern))
+    pub fn label(name: Spanned<String>, pattern: Spanned<Self>) -> Self {
+        Pattern::Label(name, Box::new(pattern))
+    }
+
+    /// The symbols a pattern binds, in the order they appear.
+    pub fn symbols(&self) -> Vec<&S> {
+        match self {
+            Pattern::Symbol(symbol) => vec![symbol],
+            Pattern::Lit(_) => vec![],
+            Pattern::Label(_, inner) | Pattern::Is(inner, _) => inner.item.symbols(),
+            Pattern::Tuple(items) | Pattern::Chain(items) => {
+                items.iter().flat_map(|item| item.item.symbols()).collect()
+            }
+            Pattern::Record(fields) => fields
+                .iter()
+                .flat_map(|(_, field)| field.item.symbols())
+                .collect(),
+            Pattern::List(items, rest) => items
+                .iter()
+                .chain(rest.as_deref())
+                .flat_map(|item| item.item.symbols())
+                .collect(),
+        }
+    }
+
+    pub fn is(pattern: Spanned<Self>, ty: Spanned<Type>) -> Self {
+        Pattern::Is(Box::new(pattern), ty)
+    }
+
+    pub fn list(items: Vec<Spanned<Self>>, rest: Option<Spanned<Self>>) -> Self {
+        Pattern::List(items, rest.map(Box::new))
+    }
+}
+
+/// A type written in source, as in the annotation `x: Integer`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    /// A named type, like `Integer`.
+    Name(String),
+    /// A type variable, like `a`, which stands for some one type.
+    Var(String),
+    /// A tuple of types, like `(Integer, String)`.
+    /// The empty tuple is the unit type, `()`.
+    Tuple(Vec<Spanned<Type>>),
+    /// A list of some type, like `[Integer]`.
+    List(Box<Spanned<Type>>),
+    /// A map from one type to another, like `[String => Integer]`.
+    Map(Box<Spanned<Type>>, Box<Spanned<Type>>),
+    /// A function type, like `Integer -> String / Write`,
+    /// along with the effects it may raise, listed after the `/`.
+    Function {
+        arg: Box<Spanned<Type>>,
+        body: Box<Spanned<Type>>,
+        effects: Vec<Spanned<String>>,
+    },
+    /// A label wrapping a type, like `Some Integer`.
+    Label(Spanned<String>, Box<Spanned<Type>>),
+}
+
+impl Type {
+    pub fn function(
+        arg: Spanned<Type>,
+        body: Spanned<Type>,
+        effects: Vec<Spanned<String>>,
+    ) -> Self {
+        Type::Function {
+            arg: Box::new(arg),
+            body: Box::new(body),
+            effects,
+        }
+    }
+
+    pub fn label(name: Spanned<String>, ty: Spanned<Type>) -> Self {
+        Type::Label(name, Box::new(ty))
+    }
+
+    pub fn list(item: Spanned<Type>) -> Self {
+        Type::List(Box::new(item))
+    }
+
+    pub fn map(key: Spanned<Type>, value: Spanned<Type>) -> Self {
+        Type::Map(Box::new(key), Box::new(value))
     }
 }
 
 // TODO: impls for boxed items.
 
+/// Binary operators that are built into the language.
+/// Most map directly to a single VM instruction,
+/// but `&&` and `||` are compiled to jumps,
+/// so that they short-circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+impl BinOp {
+    /// Returns the operator as it is written in source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Pow => "**",
+            BinOp::Equal => "==",
+            BinOp::NotEqual => "!=",
+            BinOp::Less => "<",
+            BinOp::Greater => ">",
+            BinOp::LessEqual => "<=",
+            BinOp::GreaterEqual => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+
+    /// Whether the operator compares its operands, producing a boolean.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinOp::Equal
+                | BinOp::NotEqual
+                | BinOp::Less
+                | BinOp::Greater
+                | BinOp::LessEqual
+                | BinOp::GreaterEqual
+        )
+    }
+
+    /// Whether the operator combines two booleans.
+    pub fn is_logic(&self) -> bool {
+        matches!(self, BinOp::And | BinOp::Or)
+    }
+}
+
+/// Prefix operators that are built into the language,
+/// each of which maps directly to a single VM instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+impl UnOp {
+    /// Returns the operator as it is written in source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnOp::Neg => "-",
+            UnOp::Not => "!",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Base<T, S> {
     Symbol(S),
-    Label(S),
+    Label(String),
     Lit(Lit),
     Tuple(Vec<T>),
+    Record(Fields<T>),
+    List(Vec<T>, Option<Box<T>>), // items, rest
+    Map(Vec<(T, T)>),             // keys and values
     Module(Box<T>),
 
     Block(Vec<T>),
-    Call(Box<T>, Box<T>), // fun, arg
+    Call(Box<T>, Box<T>),         // fun, arg
+    BinOp(BinOp, Box<T>, Box<T>), // op, left, right
+    UnOp(UnOp, Box<T>),           // op, operand
+    If(Box<T>, Box<T>, Box<T>),   // cond, then, else
+    Match(Box<T>, Vec<T>),        // value, arms
     Assign(Spanned<Pattern<S>>, Box<T>),
-    Effect(S),
+    Effect(Spanned<String>, Box<T>),     // name, payload
+    Handle(Box<T>, Box<T>, Vec<String>), // body, handler, effects
+    Is(Box<T>, Spanned<Type>),           // expr, type
+    Field(Box<T>, Spanned<String>),      // record, field
+    Update(Box<T>, Fields<T>),           // record, fields
 }
 
 impl<T, S> Base<T, S> {
@@ -45,6 +222,34 @@ impl<T, S> Base<T, S> {
         Base::Call(Box::new(fun), Box::new(arg))
     }
 
+    pub fn bin_op(op: BinOp, left: T, right: T) -> Self {
+        Base::BinOp(op, Box::new(left), Box::new(right))
+    }
+
+    pub fn un_op(op: UnOp, operand: T) -> Self {
+        Base::UnOp(op, Box::new(operand))
+    }
+
+    pub fn if_(cond: T, then: T, else_: T) -> Self {
+        Base::If(Box::new(cond), Box::new(then), Box::new(else_))
+    }
+
+    pub fn match_(value: T, arms: Vec<T>) -> Self {
+        Base::Match(Box::new(value), arms)
+    }
+
+    pub fn effect(name: Spanned<String>, payload: T) -> Self {
+        Base::Effect(name, Box::new(payload))
+    }
+
+    pub fn handle(body: T, handler: T, effects: Vec<String>) -> Self {
+        Base::Handle(Box::new(body), Box::new(handler), effects)
+    }
+
+    pub fn is(expr: T, ty: Spanned<Type>) -> Self {
+        Base::Is(Box::new(expr), ty)
+    }
+
     pub fn assign(pat: Spanned<Pattern<S>>, expr: T) -> Self {
         Base::Assign(pat, Box::new(expr))
     }
@@ -53,9 +258,17 @@ impl<T, S> Base<T, S> {
         Base::Module(Box::new(module))
     }
 
-    // pub fn ffi(name: &str, expr: T) -> Self {
-    //     Base::FFI(name.to_string(), Box::new(expr))
-    // }
+    pub fn field(record: T, name: Spanned<String>) -> Self {
+        Base::Field(Box::new(record), name)
+    }
+
+    pub fn update(record: T, fields: Fields<T>) -> Self {
+        Base::Update(Box::new(record), fields)
+    }
+
+    pub fn list(items: Vec<T>, rest: Option<T>) -> Self {
+        Base::List(items, rest.map(Box::new))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -64,12 +277,10 @@ pub enum Sugar<T> {
     Form(Vec<T>),
     Keyword(ResIden),
     // Pattern(Pattern<S>),
-    // Record,
-    Is(Box<T>, Box<T>), // expr, type
     // A function composition
     Comp(Box<T>, Box<T>), // arg, function
-    Field(Box<T>, Box<T>), /* struct, field
-                           * TODO: math operators */
+    // A use of an operator declared with `infix`
+    Infix(Box<T>, Box<T>, Box<T>), // function, left, right
 }
 
 impl<T> Sugar<T> {
@@ -77,16 +288,12 @@ impl<T> Sugar<T> {
         Sugar::Group(Box::new(tree))
     }
 
-    pub fn is(expr: T, ty: T) -> Self {
-        Sugar::Is(Box::new(expr), Box::new(ty))
-    }
-
     pub fn comp(arg: T, fun: T) -> Self {
         Sugar::Comp(Box::new(arg), Box::new(fun))
     }
 
-    pub fn field(record: T, name: T) -> Self {
-        Sugar::Field(Box::new(record), Box::new(name))
+    pub fn infix(fun: T, left: T, right: T) -> Self {
+        Sugar::Infix(Box::new(fun), Box::new(left), Box::new(right))
     }
 }
 
@@ -126,9 +333,15 @@ impl TryFrom<AST> for Pattern<SharedSymbol> {
         Ok(match ast {
             AST::Base(Base::Symbol(s)) => Pattern::Symbol(s),
             AST::Base(Base::Lit(d)) => Pattern::Lit(d),
+            // negative numbers
+            AST::Base(Base::UnOp(UnOp::Neg, n)) => match n.item {
+                AST::Base(Base::Lit(Lit::Integer(i))) => Pattern::Lit(Lit::Integer(-i)),
+                AST::Base(Base::Lit(Lit::Float(f))) => Pattern::Lit(Lit::Float(-f)),
+                _ => Err("Only number literals can be negated in a pattern")?,
+            },
             AST::Base(Base::Label(k)) => Err(format!(
                 "This Label used in a pattern does not unwrap any data.\n\
-                    To match a Label and ignore its contents, use `{:?} _`",
+                    To match a Label and ignore its contents, use `{} _`",
                 k,
             ))?,
             AST::Base(Base::Tuple(t)) => {
@@ -138,8 +351,43 @@ impl TryFrom<AST> for Pattern<SharedSymbol> {
                 }
                 Pattern::Tuple(patterns)
             }
+            AST::Base(Base::Record(r)) => {
+                let mut fields = vec![];
+                for (name, value) in r {
+                    fields.push((name, value.try_map(Pattern::try_from)?));
+                }
+                Pattern::Record(fields)
+            }
+            AST::Base(Base::List(l, rest)) => {
+                let mut patterns = vec![];
+                for item in l {
+                    patterns.push(item.try_map(Pattern::try_from)?);
+                }
+                let rest = match rest {
+                    Some(rest) => Some((*rest).try_map(Pattern::try_from)?),
+                    None => None,
+                };
+                Pattern::list(patterns, rest)
+            }
+
+            AST::Base(Base::Is(pattern, ty)) => {
+                Pattern::is(pattern.try_map(Pattern::try_from)?, ty)
+            }
+
+            // effects are labels, so they can be matched on like one
+            AST::Base(Base::Effect(name, payload)) => {
+                Pattern::label(name, payload.try_map(Pattern::try_from)?)
+            }
 
             // AST::Sugar(Sugar::Pattern(p)) => p,
+            AST::Sugar(Sugar::Form(mut f)) if f.len() == 2 && is_label(&f[0].item) => {
+                let pattern = f.pop().unwrap().try_map(Pattern::try_from)?;
+                let name = f.pop().unwrap().map(|label| match label {
+                    AST::Base(Base::Label(name)) => name,
+                    _ => unreachable!(),
+                });
+                Pattern::label(name, pattern)
+            }
             AST::Sugar(Sugar::Form(f)) => {
                 let mut patterns = vec![];
                 for item in f {
@@ -153,6 +401,10 @@ impl TryFrom<AST> for Pattern<SharedSymbol> {
     }
 }
 
+fn is_label(ast: &AST) -> bool {
+    matches!(ast, AST::Base(Base::Label(_)))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CST {
     Base(Base<Spanned<CST>, SharedSymbol>),
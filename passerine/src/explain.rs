@@ -0,0 +1,282 @@
+//! This module assigns a stable code to every kind of error and warning,
+//! like `E0012`, along with a long-form explanation of each.
+//! Codes never change meaning once assigned,
+//! so tools can match on them instead of on the message shown.
+//!
+//! Errors found while compiling have codes starting at `E0001`,
+//! errors raised while running have codes starting at `E0100`,
+//! and warnings have codes starting at `W0001`.
+//! The explanations live in the `explain` directory, one per code.
+
+use std::fmt;
+
+macro_rules! codes {
+    ($($(#[$doc:meta])* $code:ident = $id:literal,)*) => {
+        /// The stable code of a kind of error or warning.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Code {
+            $($(#[$doc])* $code,)*
+        }
+
+        impl Code {
+            pub const ALL: &'static [Code] = &[$(Code::$code,)*];
+
+            /// The code as shown to the user, like `E0012`.
+            pub fn id(&self) -> &'static str {
+                match self {
+                    $(Code::$code => $id,)*
+                }
+            }
+
+            /// A long-form explanation of the code, with examples.
+            pub fn explanation(&self) -> &'static str {
+                match self {
+                    $(Code::$code => include_str!(concat!("explain/", $id, ".md")),)*
+                }
+            }
+        }
+    };
+}
+
+codes! {
+    /// A character that can not start any token.
+    UnknownCharacter = "E0001",
+    /// An unknown escape code in a string.
+    InvalidEscape = "E0002",
+    /// A string that is never closed.
+    UnterminatedString = "E0003",
+    /// A number literal that can not be represented.
+    InvalidNumber = "E0004",
+    /// An opening delimiter that is never closed.
+    UnclosedDelimiter = "E0005",
+    /// A closing delimiter that closes nothing.
+    UnexpectedDelimiter = "E0006",
+    /// A closing delimiter that does not match its opening delimiter.
+    MismatchedDelimiter = "E0007",
+    /// A token where an expression was expected.
+    UnexpectedToken = "E0008",
+    /// A keyword or declaration used where it is not allowed.
+    MisplacedKeyword = "E0009",
+    /// A keyword missing the parts that must follow it.
+    IncompleteKeyword = "E0010",
+    /// A malformed arm of a `match` or `handle`.
+    InvalidArm = "E0011",
+    /// An expression used as a pattern that is not one.
+    InvalidPattern = "E0012",
+    /// A malformed declaration of an operator.
+    InvalidOperatorDeclaration = "E0013",
+    /// A malformed type annotation.
+    InvalidType = "E0014",
+    /// An effect used in a type without being declared.
+    UndeclaredEffect = "E0015",
+    /// A malformed record, record update, or field access.
+    InvalidRecord = "E0016",
+    /// A record that gives the same field more than once.
+    DuplicateField = "E0017",
+    /// A malformed list or map.
+    InvalidCollection = "E0018",
+    /// A malformed macro definition.
+    InvalidMacro = "E0019",
+    /// A malformed syntax rule.
+    InvalidSyntaxRule = "E0020",
+    /// Two syntax rules that match the same lines.
+    AmbiguousSyntaxRule = "E0021",
+    /// A macro that failed while it was being expanded.
+    MacroFailed = "E0022",
+    /// A macro whose expansion never ends.
+    RecursiveMacro = "E0023",
+    /// A malformed `use` of a module.
+    InvalidUse = "E0024",
+    /// A `use` of a module that does not exist.
+    ModuleNotFound = "E0025",
+    /// Modules that use each other in a cycle.
+    ModuleCycle = "E0026",
+    /// A variable referenced before it is assigned.
+    UnassignedVariable = "E0027",
+    /// A type annotation naming a type that does not exist.
+    UnknownType = "E0028",
+    /// A field accessed that a type does not have.
+    MissingField = "E0029",
+    /// Something that is not a function, called as one.
+    NotAFunction = "E0030",
+    /// An operator applied to types it does not support.
+    InvalidOperands = "E0031",
+    /// A type that would have to contain itself.
+    InfiniteType = "E0032",
+    /// Two types that should be the same, but are not.
+    MismatchedTypes = "E0033",
+    /// A label that does not wrap any data.
+    EmptyLabel = "E0034",
+
+    /// A variable used before it was assigned a value.
+    Uninitialized = "E0100",
+    /// An operation applied to data of the wrong type.
+    WrongType = "E0101",
+    /// Data that does not match a pattern.
+    NoMatch = "E0102",
+    /// A tuple or list indexed out of bounds.
+    OutOfBounds = "E0103",
+    /// A continuation resumed more than once.
+    ResumedTwice = "E0104",
+    /// Data that is not a function, called as one.
+    NotCallable = "E0105",
+    /// An integer operation that overflowed.
+    Overflow = "E0106",
+    /// A number divided by zero.
+    ZeroDivision = "E0107",
+    /// A field accessed that the data does not have.
+    NoSuchField = "E0108",
+    /// An effect that nothing handles.
+    UnhandledEffect = "E0109",
+    /// An effect carrying data the host can not handle.
+    InvalidPayload = "E0110",
+    /// An effect the host failed to handle.
+    HostFailed = "E0111",
+    /// A map that does not have the key looked up.
+    MissingKey = "E0112",
+
+    /// A variable that is assigned, or bound by a match arm, but never used.
+    UnusedVariable = "W0001",
+    /// A function argument that is never used.
+    UnusedArgument = "W0002",
+    /// A function argument that shadows a variable.
+    ShadowedBinding = "W0003",
+    /// A value assigned to a variable that is never read.
+    UnreadAssignment = "W0004",
+    /// A variable assigned after a function has captured it.
+    CapturedMutation = "W0005",
+    /// A label or effect in a type annotation, which is not checked.
+    UncheckedAnnotation = "W0006",
+}
+
+impl Code {
+    /// Looks up a code by its id, ignoring case.
+    pub fn from_id(id: &str) -> Option<Code> {
+        Code::ALL
+            .iter()
+            .copied()
+            .find(|code| code.id().eq_ignore_ascii_case(id.trim()))
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::Path};
+
+    use super::*;
+    use crate::{
+        common::{closure::Closure, Module, Source},
+        compiler::{compile_module_with_warnings, compile_source, lint},
+        kernel,
+        vm::fiber::Fiber,
+    };
+
+    /// The examples in an explanation marked with `error`,
+    /// which are expected to raise the code being explained.
+    fn failing_examples(code: Code) -> Vec<String> {
+        let mut examples = vec![];
+        let mut example: Option<String> = None;
+        for line in code.explanation().lines() {
+            match (&mut example, line) {
+                (None, "```passerine,error") => example = Some(String::new()),
+                (Some(_), "```") => examples.push(example.take().unwrap()),
+                (Some(example), line) => {
+                    example.push_str(line);
+                    example.push('\n');
+                }
+                (None, _) => (),
+            }
+        }
+        examples
+    }
+
+    /// Splits an example into files at each `# in src/<path>` comment,
+    /// returning the package they make up, if there are any.
+    /// `src/main.pn` is the root, and every other file is one of its children.
+    fn package(example: &str) -> Option<Module> {
+        let mut files: Vec<(String, String)> = vec![];
+        for line in example.lines() {
+            match (line.strip_prefix("# in "), files.last_mut()) {
+                (Some(path), _) => files.push((path.to_string(), String::new())),
+                (None, Some((_, contents))) => {
+                    contents.push_str(line);
+                    contents.push('\n');
+                }
+                (None, None) => (),
+            }
+        }
+
+        let root = files.iter().position(|(path, _)| path == "src/main.pn")?;
+        let module = |(path, contents): (String, String), children| {
+            Module::new(Source::new(&contents, Path::new(&path)), children)
+        };
+        let root = files.remove(root);
+        let children = files.into_iter().map(|file| module(file, vec![])).collect();
+        Some(module(root, children))
+    }
+
+    /// Compiles and runs an example,
+    /// returning the code of the first error or warning raised.
+    fn raised(example: &str) -> Option<Code> {
+        let compiled = match package(example) {
+            Some(module) => compile_module_with_warnings(&module),
+            None => {
+                let source = Source::source(example);
+                compile_source(source.clone()).map(|lambda| (lambda, lint(source).unwrap()))
+            }
+        };
+        let (lambda, warnings) = match compiled {
+            Ok(compiled) => compiled,
+            Err(diagnostics) => return Some(diagnostics.errors()[0].code),
+        };
+
+        let mut fiber = Fiber::init(Closure::wrap(lambda));
+        kernel::register(&mut fiber);
+        if let Err(trace) = fiber.run() {
+            return Some(trace.code());
+        }
+
+        warnings.first().map(|warning| warning.lint.code())
+    }
+
+    #[test]
+    fn ids() {
+        let mut seen = HashSet::new();
+        for code in Code::ALL {
+            let id = code.id();
+            assert!(seen.insert(id), "{} is used twice", id);
+            assert_eq!(id.len(), 5);
+            assert!(id.starts_with('E') || id.starts_with('W'));
+            assert!(id[1..].chars().all(|c| c.is_ascii_digit()));
+            assert_eq!(Code::from_id(&id.to_lowercase()), Some(*code));
+        }
+        assert_eq!(Code::from_id("E9999"), None);
+    }
+
+    #[test]
+    fn examples() {
+        for code in Code::ALL {
+            assert!(
+                code.explanation().contains("```passerine"),
+                "{} has no example",
+                code
+            );
+            if let Some((_, after)) = code.explanation().split_once("Erroneous code example:") {
+                assert!(
+                    after.trim_start().starts_with("```passerine,error\n"),
+                    "the erroneous example of {} is not marked with `error`",
+                    code
+                );
+            }
+            for example in failing_examples(*code) {
+                assert_eq!(raised(&example), Some(*code), "in:\n{}", example);
+            }
+        }
+    }
+}
@@ -1,11 +1,8 @@
-use passerine_derive::Effect;
-
-use crate::common::Data;
+use std::cmp::Ordering;
 
-// TODO: implement equality rather than just deriving PartialEq on Data.
+use passerine_derive::Effect;
 
-// Rust hit it right on the nose with the difference between equality and
-// partial equality TODO: equality vs partial equality in passerine?
+use crate::{common::Data, explain::Code, vm::fiber::Fiber};
 
 #[derive(Effect)]
 pub struct Equal(Data, Data);
@@ -21,3 +18,31 @@ pub struct LessEqual(Data, Data);
 
 #[derive(Effect)]
 pub struct GreaterEqual(Data, Data);
+
+/// Compares two numbers or strings, as the comparison operators do,
+/// raising an error if the data can not be compared.
+fn compare(left: &Data, right: &Data) -> Result<Ordering, String> {
+    left.compare(right)
+        .ok_or_else(|| format!("Can not compare '{}' and '{}'", left, right))
+}
+
+/// Registers host handlers for the logic effects.
+/// `Equal` checks whether two values are structurally equal,
+/// the same way `==` does.
+/// The others order two numbers or two strings,
+/// raising an error (`E0101`) for anything else.
+pub fn register(fiber: &mut Fiber) {
+    fiber.register(|Equal(left, right)| left == right);
+    fiber.try_register_with(Code::WrongType, |Less(left, right)| {
+        compare(&left, &right).map(Ordering::is_lt)
+    });
+    fiber.try_register_with(Code::WrongType, |Greater(left, right)| {
+        compare(&left, &right).map(Ordering::is_gt)
+    });
+    fiber.try_register_with(Code::WrongType, |LessEqual(left, right)| {
+        compare(&left, &right).map(Ordering::is_le)
+    });
+    fiber.try_register_with(Code::WrongType, |GreaterEqual(left, right)| {
+        compare(&left, &right).map(Ordering::is_ge)
+    });
+}
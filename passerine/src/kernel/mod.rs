@@ -1,13 +1,15 @@
 //! This module provides the standard/core language library
-//! And compiler-magic FFI bindings.
+//! And the effects through which Passerine talks to the host.
 
 // pub mod io;
 // pub mod control;
-// pub mod logic;
+pub mod logic;
 
 use passerine_derive::Effect;
 
-use crate::common::data::Data;
+use std::{collections::BTreeMap, rc::Rc};
+
+use crate::{common::data::Data, explain::Code, vm::fiber::Fiber};
 
 #[derive(Effect)]
 pub struct Write(Data);
@@ -24,3 +26,82 @@ pub struct Choice {
     then: Data,
     other: Data,
 }
+
+#[derive(Effect)]
+pub struct Length(Rc<Vec<Data>>);
+
+#[derive(Effect)]
+pub struct Index(Rc<Vec<Data>>, i64);
+
+#[derive(Effect)]
+pub struct Push(Rc<Vec<Data>>, Data);
+
+#[derive(Effect)]
+pub struct Concat(Rc<Vec<Data>>, Rc<Vec<Data>>);
+
+#[derive(Effect)]
+pub struct Get(BTreeMap<Data, Data>, Data);
+
+#[derive(Effect)]
+pub struct Insert(BTreeMap<Data, Data>, Data, Data);
+
+#[derive(Effect)]
+pub struct Remove(BTreeMap<Data, Data>, Data);
+
+#[derive(Effect)]
+pub struct Keys(BTreeMap<Data, Data>);
+
+/// Registers host handlers for the effects declared by the kernel.
+/// `Write` prints some data, `Show` converts some data to a string,
+/// and `Choice` picks `then` or `other` based on `cond`.
+/// `Length`, `Index`, `Push`, and `Concat` work with lists;
+/// `Index` raises an error if the index is out-of-bounds (`E0103`).
+/// Lists are shared, and `Push` and `Concat` only copy a list
+/// if something else still holds it.
+/// `Get`, `Insert`, `Remove`, and `Keys` work with maps;
+/// `Get` raises an error if the map does not have the key (`E0112`).
+/// The effects in `logic` compare data.
+pub fn register(fiber: &mut Fiber) {
+    fiber.register(|Write(data)| print!("{}", data));
+    fiber.register(|Show(data)| data.to_string());
+    fiber.register(|Choice { cond, then, other }| if cond { then } else { other });
+
+    fiber.register(|Length(list)| list.len() as i64);
+    fiber.try_register_with(Code::OutOfBounds, |Index(list, index)| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| list.get(i).cloned())
+            .ok_or_else(|| {
+                format!(
+                    "The list is of length {}, so the index {} is out-of-bounds",
+                    list.len(),
+                    index
+                )
+            })
+    });
+    fiber.register(|Push(mut list, item)| {
+        Rc::make_mut(&mut list).push(item);
+        list
+    });
+    fiber.register(|Concat(mut list, other)| {
+        Rc::make_mut(&mut list).extend(other.iter().cloned());
+        list
+    });
+
+    fiber.try_register_with(Code::MissingKey, |Get(map, key)| {
+        map.get(&key)
+            .cloned()
+            .ok_or_else(|| format!("The map does not have the key '{}'", key))
+    });
+    fiber.register(|Insert(mut map, key, value)| {
+        map.insert(key, value);
+        map
+    });
+    fiber.register(|Remove(mut map, key)| {
+        map.remove(&key);
+        map
+    });
+    fiber.register(|Keys(map)| map.into_keys().collect::<Vec<Data>>());
+
+    logic::register(fiber);
+}
@@ -62,6 +62,11 @@
 //! indicating that an error occured.
 //! `Syntax` is just a `Span` and a message,
 //! which can be pretty-printed.
+//! Steps that can recover from an error and keep going,
+//! like reading, parsing, and hoisting,
+//! raise `Err(Diagnostics)` instead, which holds every error found.
+//! Each error carries a stable `Code`, like `E0012`,
+//! which `explain::Code::explanation` describes at length.
 //!
 //! The first phase of compilation is lexing.
 //! The `Lexer` reads through a source, and produces a stream of
@@ -104,6 +109,7 @@
 pub use passerine_common as common;
 pub mod compiler;
 pub mod construct;
+pub mod explain;
 pub mod kernel;
 pub mod vm;
 
@@ -112,10 +118,13 @@ pub mod vm;
 
 use std::rc::Rc;
 
-pub use common::{closure::Closure, Data, Inject, Source};
+pub use common::{closure::Closure, Data, Inject, Module, Source};
 pub use compiler::{
+    compile_module,
+    compile_module_with_warnings,
     compile_source,
-    syntax::Syntax,
+    lint::{Lint, Warning},
+    syntax::{Diagnostics, Syntax},
     // TODO:
     Compiler,
     Desugarer,
@@ -124,30 +133,33 @@ pub use compiler::{
     Parser,
     Reader,
 };
+pub use explain::Code;
 pub use passerine_derive::Effect;
-pub use vm::{fiber::Fiber, trace::Trace};
+pub use vm::{
+    fiber::Fiber,
+    trace::{Trace, TraceKind},
+};
 
 /// Compiles a [`Source`] to some bytecode.
-pub fn compile(source: Rc<Source>) -> Result<Closure, Syntax> {
+pub fn compile(source: Rc<Source>) -> Result<Closure, Diagnostics> {
     let bytecode = compile_source(source)?;
     return Ok(Closure::wrap(bytecode));
 }
 
-// /// Compiles a [`Source`] to some bytecode,
-// /// With a specific [`FFI`].
-// pub fn compile_with_ffi(
-//     source: Rc<Source>,
-//     ffi: FFI,
-// ) -> Result<Closure, Syntax> {
-//     let tokens = ThinModule::thin(source).lower()?;
-//     let ast = tokens.lower()?;
-//     let cst = ast.lower()?;
-//     let sst = cst.lower()?;
-//     let sst_ffi = Module::new(sst.repr, (sst.assoc, ffi));
-//     let bytecode = sst_ffi.lower()?;
+/// Compiles a package, given the module at the root of its source tree.
+pub fn compile_package(module: &Module) -> Result<Closure, Diagnostics> {
+    let bytecode = compile_module(module)?;
+    return Ok(Closure::wrap(bytecode));
+}
 
-//     return Ok(Closure::wrap(bytecode));
-// }
+/// Compiles a package like [`compile_package`],
+/// also returning any [`Warning`]s, which do not stop compilation.
+pub fn compile_package_with_warnings(
+    module: &Module,
+) -> Result<(Closure, Vec<Warning>), Diagnostics> {
+    let (bytecode, warnings) = compile_module_with_warnings(module)?;
+    return Ok((Closure::wrap(bytecode), warnings));
+}
 
 // /// Run a compiled [`Closure`].
 // pub fn run(closure: Closure) -> Result<(), Trace> {
@@ -1,18 +1,31 @@
-use std::mem;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    mem,
+    rc::Rc,
+};
 
 use crate::{
     common::{
-        closure::Closure, data::Data, lambda::Captured, number::build_number, opcode::Opcode,
+        closure::Closure,
+        data::Data,
+        effect::{Continuation, Effect, EffectId},
+        inject::Inject,
+        label::Labels,
+        lambda::Captured,
+        number::build_number,
+        opcode::Opcode,
         span::Span,
     },
-    vm::{slot::Suspend, stack::Stack, trace::Trace},
+    explain::Code,
+    vm::{
+        handler::{Handler, HostError, HostHandler, Resume},
+        slot::Suspend,
+        stack::Stack,
+        trace::{Trace, TraceKind},
+    },
 };
 
-// TODO: algebraic effects
-// more than just Trace, Runtime - mechanism for raising effects
-// fiber scheduling environment handles FFI, no more holding refs to rust
-// functions. TODO: convert Fiber to Fiber
-
 /// A `Fiber` executes bytecode lambda closures.
 /// (That's a mouthful - think bytecode + some context).
 /// Fiber initialization overhead is tiny,
@@ -23,6 +36,14 @@ pub struct Fiber {
     pub closure: Closure,
     pub stack: Stack,
     pub ip: usize,
+    /// The handlers currently installed, innermost last.
+    pub handlers: Vec<Handler>,
+    /// Handlers registered by the host, see `Fiber::register`.
+    pub host_handlers: HashMap<EffectId, HostHandler>,
+    /// The registry of the labels used by the code this fiber runs,
+    /// taken from the lambda it was initialized with,
+    /// so the host can construct labels that match the program's.
+    pub labels: Labels,
 }
 
 unsafe impl Send for Fiber {}
@@ -37,15 +58,78 @@ impl Fiber {
     /// Initialize a new Fiber.
     /// To run the Fiber, a lambda must be passed to it through `run`.
     pub fn init(closure: Closure) -> Fiber {
+        let labels = closure.lambda.labels.clone();
         let mut fiber = Fiber {
             closure,
             stack: Stack::init(),
             ip: 0,
+            handlers: vec![],
+            host_handlers: HashMap::new(),
+            labels,
         };
         fiber.stack.declare(fiber.closure.lambda.decls);
         return fiber;
     }
 
+    /// Replaces the closure running in the root frame with another,
+    /// keeping the locals left behind by the closures run before it.
+    /// The new closure must be compiled so that its locals extend
+    /// the locals of the previous closure, as a `compiler::Session` does.
+    /// This is how each entry in a REPL is run.
+    pub fn extend(&mut self, closure: Closure) {
+        let decls = self.closure.lambda.decls;
+        self.stack.truncate_root(decls);
+        self.stack.declare(closure.lambda.decls - decls);
+
+        self.closure = closure;
+        self.ip = 0;
+        self.handlers.clear();
+    }
+
+    /// Registers a Rust function that handles an effect
+    /// whenever nothing in Passerine handles it.
+    /// The function is passed the payload of the effect,
+    /// and raising the effect evaluates to whatever it returns.
+    /// Registering a handler for an effect replaces any previous one.
+    pub fn register<E, R>(&mut self, mut handler: impl FnMut(E) -> R + 'static)
+    where
+        E: Effect,
+        R: Inject,
+    {
+        self.try_register(move |payload: E| Ok::<R, String>(handler(payload)));
+    }
+
+    /// Like `register`, but the function may fail,
+    /// returning the reason it failed.
+    /// The failure is raised as an error named after the effect.
+    pub fn try_register<E, R>(&mut self, handler: impl FnMut(E) -> Result<R, String> + 'static)
+    where
+        E: Effect,
+        R: Inject,
+    {
+        self.try_register_with(Code::HostFailed, handler);
+    }
+
+    /// Like `try_register`, but failures are raised with a given code,
+    /// which says what went wrong better than the code for any host failure,
+    /// like `Code::OutOfBounds` for an index past the end of a list.
+    pub fn try_register_with<E, R>(
+        &mut self,
+        code: Code,
+        mut handler: impl FnMut(E) -> Result<R, String> + 'static,
+    ) where
+        E: Effect,
+        R: Inject,
+    {
+        let handler = HostHandler::new(move |payload| {
+            let payload = E::deserialize(payload).ok_or(HostError::Payload)?;
+            handler(payload)
+                .map(R::serialize)
+                .map_err(|reason| HostError::Failed(code, reason))
+        });
+        self.host_handlers.insert(E::id(), handler);
+    }
+
     /// Advances to the next instruction.
     #[inline]
     fn next(&mut self) {
@@ -106,7 +190,6 @@ impl Fiber {
             Opcode::Con => self.con(),
             Opcode::NotInit => self.not_init(),
             Opcode::Del => self.del(),
-            Opcode::FFICall => self.ffi_call(),
             Opcode::Copy => self.copy_val(),
             Opcode::Capture => self.capture(),
             Opcode::Save => self.save(),
@@ -117,13 +200,45 @@ impl Fiber {
             Opcode::Return => self.return_val(),
             Opcode::Closure => self.closure(),
             Opcode::Print => self.print(),
+            Opcode::Handler => self.handler(),
+            Opcode::Effect => self.effect(),
             Opcode::Label => self.label(),
             Opcode::Tuple => self.tuple(),
+            Opcode::Record => self.record(),
             Opcode::UnData => self.un_data(),
             Opcode::UnLabel => self.un_label(),
             Opcode::UnTuple => self.un_tuple(),
+            Opcode::Add => self.add(),
+            Opcode::Sub => self.sub(),
+            Opcode::Neg => self.neg(),
+            Opcode::Mul => self.mul(),
+            Opcode::Div => self.div(),
+            Opcode::Rem => self.rem(),
+            Opcode::Pow => self.pow(),
+            Opcode::Jump => self.jump(),
+            Opcode::JumpFalse => self.jump_false(),
+            Opcode::Index => self.index(),
+            Opcode::Equal => self.equal(),
+            Opcode::IsLabel => self.is_label(),
+            Opcode::IsTuple => self.is_tuple(),
+            Opcode::NoMatch => self.no_match(),
+            Opcode::Field => self.field(),
+            Opcode::Update => self.update(),
+            Opcode::UnRecord => self.un_record(),
+            Opcode::IsRecord => self.is_record(),
+            Opcode::List => self.list(),
+            Opcode::Spread => self.spread(),
+            Opcode::UnList => self.un_list(),
+            Opcode::Rest => self.rest(),
+            Opcode::IsList => self.is_list(),
+            Opcode::Map => self.map(),
+            Opcode::NotEqual => self.not_equal(),
+            Opcode::Less => self.compare("<", Ordering::is_lt),
+            Opcode::Greater => self.compare(">", Ordering::is_gt),
+            Opcode::LessEqual => self.compare("<=", Ordering::is_le),
+            Opcode::GreaterEqual => self.compare(">=", Ordering::is_ge),
+            Opcode::Not => self.not(),
             Opcode::Noop => self.done(),
-            _ => panic!("Opcode Not Implemented"),
         }
     }
 
@@ -210,7 +325,7 @@ impl Fiber {
     fn save_cap(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
         let data = self.stack.pop_data();
-        mem::drop(self.closure.captures[index].replace(data));
+        mem::drop(self.closure.captures[index].replace(Some(data)));
         self.done()
     }
 
@@ -218,7 +333,16 @@ impl Fiber {
     #[inline]
     fn load(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
-        let mut data = self.stack.local_data(index);
+        let data = match self.stack.local_data(index) {
+            Some(data) => data,
+            None => {
+                return Err(Trace::error(
+                    TraceKind::Uninitialized,
+                    "This variable was used before it was assigned a value",
+                    vec![self.current_span()],
+                ))
+            }
+        };
         self.stack.push_data(data);
         self.done()
     }
@@ -227,7 +351,16 @@ impl Fiber {
     #[inline]
     fn load_cap(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
-        let data = self.closure.captures[index].borrow().to_owned();
+        let data = match self.closure.captures[index].borrow().to_owned() {
+            Some(data) => data,
+            None => {
+                return Err(Trace::error(
+                    TraceKind::Uninitialized,
+                    "This variable was used before it was assigned a value",
+                    vec![self.current_span()],
+                ))
+            }
+        };
         self.stack.push_data(data);
         self.done()
     }
@@ -281,13 +414,218 @@ impl Fiber {
         self.done()
     }
 
+    /// Reads the names of the fields of a record from a constant tuple.
+    fn field_names(&mut self) -> Vec<String> {
+        let index = self.next_number();
+        match &self.closure.lambda.constants[index] {
+            Data::Tuple(names) => names
+                .iter()
+                .map(|name| match name {
+                    Data::String(name) => name.to_string(),
+                    _ => unreachable!("Expected the name of a field"),
+                })
+                .collect(),
+            _ => unreachable!("Expected the names of the fields of a record"),
+        }
+    }
+
+    /// Reads the name of a single field from a constant.
+    fn field_name(&mut self) -> String {
+        let index = self.next_number();
+        match &self.closure.lambda.constants[index] {
+            Data::String(name) => name.to_string(),
+            _ => unreachable!("Expected the name of a field"),
+        }
+    }
+
+    /// Builds an error for data that does not have a field with a given name.
+    fn missing_field(&self, data: Data, name: &str) -> Trace {
+        let reason = match data {
+            Data::Record(_) => format!(
+                "The record '{}' does not have a field named '{}'",
+                data, name
+            ),
+            other => format!(
+                "The data '{}' is not a record, so it has no field named '{}'",
+                other, name
+            ),
+        };
+        Trace::error(TraceKind::Field, &reason, vec![self.current_span()])
+    }
+
+    /// Pops a record, raising an error if the data is not a record
+    /// or does not have a field with the given name.
+    fn pop_record(&mut self, name: &str) -> Result<BTreeMap<String, Data>, Trace> {
+        match self.stack.pop_data() {
+            Data::Record(record) if record.contains_key(name) => Ok(record),
+            other => Err(self.missing_field(other, name)),
+        }
+    }
+
+    #[inline]
+    fn record(&mut self) -> Result<(), Trace> {
+        let names = self.field_names();
+        let mut record = BTreeMap::new();
+        for name in names.into_iter().rev() {
+            record.insert(name, self.stack.pop_data());
+        }
+
+        self.stack.push_data(Data::Record(record));
+        self.done()
+    }
+
+    fn field(&mut self) -> Result<(), Trace> {
+        let name = self.field_name();
+        let mut record = self.pop_record(&name)?;
+        self.stack.push_data(record.remove(&name).unwrap());
+        self.done()
+    }
+
+    fn update(&mut self) -> Result<(), Trace> {
+        let names = self.field_names();
+        let mut values = vec![];
+        for _ in 0..names.len() {
+            values.push(self.stack.pop_data());
+        }
+        values.reverse();
+
+        let mut record = self.pop_record(&names[0])?;
+        for (name, value) in names.into_iter().zip(values) {
+            if !record.contains_key(&name) {
+                return Err(self.missing_field(Data::Record(record), &name));
+            }
+            record.insert(name, value);
+        }
+
+        self.stack.push_data(Data::Record(record));
+        self.done()
+    }
+
+    fn un_record(&mut self) -> Result<(), Trace> {
+        let name = self.field_name();
+        let record = self.pop_record(&name)?;
+        let data = record[&name].clone();
+        self.stack.push_data(Data::Record(record));
+        self.stack.push_data(data);
+        self.done()
+    }
+
+    #[inline]
+    fn list(&mut self) -> Result<(), Trace> {
+        let length = self.next_number();
+        let mut items = vec![];
+        for _ in 0..length {
+            items.push(self.stack.pop_data())
+        }
+
+        items.reverse();
+        self.stack.push_data(Data::List(Rc::new(items)));
+        self.done()
+    }
+
+    /// Builds a map out of pairs of keys and values.
+    /// If a key is given more than once, the last value given is kept.
+    // closures are compared by identity, so data is safe to use as a key
+    #[allow(clippy::mutable_key_type)]
+    fn map(&mut self) -> Result<(), Trace> {
+        let length = self.next_number();
+        let mut entries = vec![];
+        for _ in 0..length {
+            let value = self.stack.pop_data();
+            let key = self.stack.pop_data();
+            entries.push((key, value));
+        }
+
+        let map = entries.into_iter().rev().collect();
+        self.stack.push_data(Data::Map(map));
+        self.done()
+    }
+
+    /// Adds some items to the front of the list on the top of the stack.
+    fn spread(&mut self) -> Result<(), Trace> {
+        let length = self.next_number();
+        let rest = match self.stack.pop_data() {
+            Data::List(l) => l,
+            other => {
+                return Err(Trace::error(
+                    TraceKind::Type,
+                    &format!(
+                        "The data '{}' is not a list, so it can not be spread",
+                        other
+                    ),
+                    vec![self.current_span()],
+                ))
+            }
+        };
+
+        let mut items = vec![];
+        for _ in 0..length {
+            items.push(self.stack.pop_data())
+        }
+
+        items.reverse();
+        items.extend(rest.iter().cloned());
+        self.stack.push_data(Data::List(Rc::new(items)));
+        self.done()
+    }
+
+    /// Asserts that the data on the top of the stack is a list of some length,
+    /// or at least that length if the rest of the list is matched too.
+    /// Leaves the list on the stack to be destructured item by item.
+    fn un_list(&mut self) -> Result<(), Trace> {
+        let length = self.next_number();
+        let rest = self.next_number() == 1;
+
+        let l = match self.stack.pop_data() {
+            Data::List(l) => l,
+            other => {
+                return Err(Trace::error(
+                    TraceKind::PatternMatching,
+                    &format!("The data '{}' is not a list", other),
+                    vec![self.current_span()],
+                ))
+            }
+        };
+
+        if l.len() < length || (!rest && l.len() > length) {
+            let expected = if rest { "at least " } else { "" };
+            return Err(Trace::error(
+                TraceKind::PatternMatching,
+                &format!(
+                    "The list '{}' has {} items, but the pattern expects {}{}",
+                    Data::List(l.clone()),
+                    l.len(),
+                    expected,
+                    length
+                ),
+                vec![self.current_span()],
+            ));
+        }
+
+        self.stack.push_data(Data::List(l));
+        self.done()
+    }
+
+    /// Replaces a list with the items that come after the first few.
+    /// The list is assumed to be long enough,
+    /// as this is checked before the rest of a list is taken.
+    fn rest(&mut self) -> Result<(), Trace> {
+        let skipped = self.next_number();
+        let rest = match self.stack.pop_data() {
+            Data::List(l) if skipped <= l.len() => l[skipped..].to_vec(),
+            _ => unreachable!("Can only take the rest of a list that is long enough"),
+        };
+        self.stack.push_data(Data::List(Rc::new(rest)));
+        self.done()
+    }
+
     fn un_data(&mut self) -> Result<(), Trace> {
         let expected = self.stack.pop_data();
         let data = self.stack.pop_data();
 
         if data != expected {
             return Err(Trace::error(
-                "Pattern Matching",
+                TraceKind::PatternMatching,
                 &format!(
                     "The data '{}' does not match the expected data '{}'",
                     data, expected
@@ -309,7 +647,7 @@ impl Fiber {
             Data::Label(n, d) if n == kind => d,
             other => {
                 return Err(Trace::error(
-                    "Pattern Matching",
+                    TraceKind::PatternMatching,
                     &format!("The data '{}' does not match the Label '{}'", other, kind),
                     vec![self.current_span()],
                 ))
@@ -326,7 +664,7 @@ impl Fiber {
             Data::Tuple(t) => t,
             other => {
                 return Err(Trace::error(
-                    "Pattern Matching",
+                    TraceKind::PatternMatching,
                     &format!("The data '{}' is not a tuple", other),
                     vec![self.current_span()],
                 ))
@@ -336,7 +674,7 @@ impl Fiber {
         let length = t.len();
         if index >= length {
             return Err(Trace::error(
-                "Indexing",
+                TraceKind::Indexing,
                 &format!(
                     "The tuple '{}' is of length {}, so the index {} is out-of-bounds",
                     Data::Tuple(t),
@@ -355,18 +693,29 @@ impl Fiber {
 
     /// Call a function on the top of the stack, passing the next value as an
     /// argument.
+    /// A continuation can be called like a function,
+    /// which resumes the computation it suspended.
     fn call(&mut self) -> Result<(), Trace> {
         // get the function and argument to run
-        let fun = match self.stack.pop_data() {
-            Data::Closure(c) => *c,
-            o => {
-                return Err(Trace::error(
-                    "Call",
-                    &format!("The data '{}' is not a function and can not be called", o),
-                    vec![self.current_span()],
-                ))
-            }
-        };
+        let (fun, resume) =
+            match self.stack.pop_data() {
+                Data::Closure(c) => (*c, None),
+                Data::Continuation(k) => match k.take::<Resume>() {
+                    Some(resume) => (resume.suspend.closure.clone(), Some(resume)),
+                    None => return Err(Trace::error(
+                        TraceKind::Continuation,
+                        "This continuation has already been resumed, and can only be resumed once",
+                        vec![self.current_span()],
+                    )),
+                },
+                o => {
+                    return Err(Trace::error(
+                        TraceKind::Call,
+                        &format!("The data '{}' is not a function and can not be called", o),
+                        vec![self.current_span()],
+                    ))
+                }
+            };
         let arg = self.stack.pop_data();
 
         // TODO: make all programs end in return,
@@ -386,7 +735,8 @@ impl Fiber {
 
         // suspend the calling context
         let old_closure = mem::replace(&mut self.closure, fun);
-        let old_ip = mem::replace(&mut self.ip, 0);
+        let ip = resume.as_ref().map_or(0, |resume| resume.suspend.ip);
+        let old_ip = mem::replace(&mut self.ip, ip);
         let suspend = Suspend {
             ip: old_ip,
             closure: old_closure,
@@ -399,6 +749,21 @@ impl Fiber {
             self.stack.push_frame(suspend);
         }
 
+        if let Some(resume) = resume {
+            // put the suspended frames back on top of the caller,
+            // so that the result of the resumed computation is returned to it
+            let depth = self.stack.frames.len() - 1;
+            self.stack.join_frames(resume.slots, resume.frames);
+            self.handlers
+                .extend(resume.handlers.into_iter().map(|mut handler| {
+                    handler.depth += depth;
+                    handler
+                }));
+            // the argument is the result of raising the effect
+            self.stack.push_data(arg);
+            return Ok(());
+        }
+
         // set up the stack for the function call
         // self.stack.push_frame(suspend);
         self.stack.declare(self.closure.lambda.decls);
@@ -429,11 +794,155 @@ impl Fiber {
         self.ip = suspend.ip;
         self.closure = suspend.closure;
 
+        // remove the handler installed for the frame, if any
+        let depth = self.stack.frames.len();
+        while matches!(self.handlers.last(), Some(handler) if handler.depth >= depth) {
+            self.handlers.pop();
+        }
+
         // push return value
         self.stack.push_data(val); // push the return value
         Ok(())
     }
 
+    /// Suspends the current closure and starts running a new one,
+    /// passing it an argument.
+    /// Like a non-tail call, but the ip is expected to be past the current op.
+    fn enter(&mut self, fun: Closure, arg: Data) {
+        let suspend = Suspend {
+            ip: mem::replace(&mut self.ip, 0),
+            closure: mem::replace(&mut self.closure, fun),
+        };
+        self.stack.push_frame(suspend);
+        self.stack.declare(self.closure.lambda.decls);
+        self.stack.push_data(arg);
+    }
+
+    /// Calls the body of a handle expression with a handler installed.
+    /// Expects the stack to be `[..., Body, Handler]`.
+    /// The handler stays installed until the body returns.
+    fn handler(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let kinds = self.closure.lambda.constants[index].clone();
+        let (body, handler) = match (self.stack.pop_data(), self.stack.pop_data()) {
+            (Data::Closure(handler), Data::Closure(body)) => (*body, *handler),
+            _ => unreachable!("Expected a body and a handler"),
+        };
+
+        self.next();
+        let depth = self.stack.frames.len();
+        self.handlers.push(Handler::new(depth, kinds, handler));
+        self.enter(body, Data::Unit);
+        Ok(())
+    }
+
+    /// Raises the effect on the top of the stack.
+    /// Every frame up to and including the body of the nearest handle
+    /// expression that handles the effect is suspended and captured as a
+    /// continuation. Then, the handler is called in place of the body with
+    /// the effect and the continuation.
+    fn effect(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let name = match self.closure.lambda.constants[index].clone() {
+            Data::String(name) => name,
+            _ => unreachable!("Expected the name of an effect"),
+        };
+        let effect = self.stack.pop_data();
+        self.raise(&name, effect)
+    }
+
+    /// Raises an effect with a given name,
+    /// resuming after the current instruction.
+    fn raise(&mut self, name: &str, effect: Data) -> Result<(), Trace> {
+        let kind = match &effect {
+            Data::Label(kind, _) => kind.clone(),
+            _ => unreachable!("Expected an effect"),
+        };
+
+        let index = match self.handlers.iter().rposition(|h| h.handles(&kind)) {
+            Some(index) => index,
+            None => return self.host_effect(name, effect),
+        };
+        self.next();
+
+        // move everything from the body of the handle expression upwards
+        // off of the stack, including any handlers installed along the way
+        let depth = self.handlers[index].depth;
+        let handler = self.handlers[index].closure.clone();
+        let handlers = self
+            .handlers
+            .split_off(index)
+            .into_iter()
+            .map(|mut handler| {
+                handler.depth -= depth;
+                handler
+            })
+            .collect();
+        let (slots, frames) = self.stack.split_frames(depth);
+
+        // return to the frame that installed the handler
+        let handling = self.stack.restore_frame();
+        let suspend = Suspend {
+            ip: mem::replace(&mut self.ip, handling.ip),
+            closure: mem::replace(&mut self.closure, handling.closure),
+        };
+
+        let continuation = Continuation::new(Resume {
+            slots,
+            frames,
+            handlers,
+            suspend,
+        });
+        let arg = Data::Tuple(vec![effect, Data::Continuation(continuation)]);
+        self.enter(handler, arg);
+        Ok(())
+    }
+
+    /// Handles an effect that nothing in Passerine handles,
+    /// using the handler registered by the host for it.
+    /// The computation is resumed right away with what the handler returns.
+    fn host_effect(&mut self, name: &str, effect: Data) -> Result<(), Trace> {
+        let payload = match effect {
+            Data::Label(_, payload) => *payload,
+            _ => unreachable!("Expected an effect"),
+        };
+
+        let handler = match self.host_handlers.get_mut(&EffectId::new(name)) {
+            Some(handler) => handler,
+            None => {
+                return Err(Trace::error(
+                    TraceKind::UnhandledEffect,
+                    &format!(
+                        "The effect '{} {}' was raised, but nothing handles it",
+                        name, payload
+                    ),
+                    vec![self.current_span()],
+                ))
+            }
+        };
+
+        match handler.call(payload.clone()) {
+            Ok(resumed) => {
+                self.stack.push_data(resumed);
+                self.done()
+            }
+            Err(HostError::Failed(code, reason)) => Err(Trace::error(
+                TraceKind::Host(name.to_string(), code),
+                &reason,
+                vec![self.current_span()],
+            )),
+            Err(HostError::Payload) => Err(Trace::error(
+                TraceKind::Effect,
+                &format!(
+                    "The host can not handle the effect '{} {}', \
+                    because the data it carries is not what was expected",
+                    name, payload
+                ),
+                vec![self.current_span()],
+            )),
+        }
+    }
+
     fn closure(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
 
@@ -456,20 +965,444 @@ impl Fiber {
         self.done()
     }
 
-    fn ffi_call(&mut self) -> Result<(), Trace> {
-        panic!("FFI is depracated");
-        // let index = self.next_number();
-        // let ffi_function = &self.closure.lambda.ffi[index];
-        //
-        // let argument = self.stack.pop_data();
-        // let returned = match ffi_function.call(argument) {
-        //     Ok(d) => d,
-        //     Err(e) => return Err(Trace::error(
-        //         "FFI Call", &e, vec![self.current_span()],
-        //     )),
-        // };
-        //
-        // self.stack.push_data(returned);
-        // self.done()
+    /// Jumps to a position in the current lambda's bytecode.
+    #[inline]
+    fn jump(&mut self) -> Result<(), Trace> {
+        self.ip = self.next_number();
+        Ok(())
+    }
+
+    /// Pops a boolean off the stack, jumping if it is false.
+    #[inline]
+    fn jump_false(&mut self) -> Result<(), Trace> {
+        let target = self.next_number();
+        match self.stack.pop_data() {
+            Data::Boolean(true) => self.done(),
+            Data::Boolean(false) => {
+                self.ip = target;
+                Ok(())
+            }
+            other => Err(Trace::error(
+                TraceKind::Type,
+                &format!("The condition '{}' is not a boolean", other),
+                vec![self.current_span()],
+            )),
+        }
+    }
+
+    /// Replaces a tuple or list with one of its items.
+    /// Matching checks the length before indexing,
+    /// but an index out of bounds is still raised as an indexing error.
+    fn index(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let item = match self.stack.pop_data() {
+            Data::Tuple(mut t) if index < t.len() => t.swap_remove(index),
+            Data::List(l) if index < l.len() => l[index].clone(),
+            data @ (Data::Tuple(_) | Data::List(_)) => {
+                return Err(Trace::error(
+                    TraceKind::Indexing,
+                    &format!("The index {} is out-of-bounds in '{}'", index, data),
+                    vec![self.current_span()],
+                ))
+            }
+            _ => unreachable!("Can only index into a tuple or list"),
+        };
+        self.stack.push_data(item);
+        self.done()
+    }
+
+    /// Pops two values, pushing whether they are equal.
+    fn equal(&mut self) -> Result<(), Trace> {
+        let right = self.stack.pop_data();
+        let left = self.stack.pop_data();
+        self.stack.push_data(Data::Boolean(left == right));
+        self.done()
+    }
+
+    /// Pops two values, pushing whether they are not equal.
+    fn not_equal(&mut self) -> Result<(), Trace> {
+        let right = self.stack.pop_data();
+        let left = self.stack.pop_data();
+        self.stack.push_data(Data::Boolean(left != right));
+        self.done()
+    }
+
+    /// Pops two numbers or strings,
+    /// pushing whether their ordering passes a test.
+    /// See `Data::compare` for how data is ordered.
+    fn compare(&mut self, op: &str, test: fn(Ordering) -> bool) -> Result<(), Trace> {
+        let right = self.stack.pop_data();
+        let left = self.stack.pop_data();
+
+        let ordering = match left.compare(&right) {
+            Some(ordering) => ordering,
+            None => {
+                return Err(Trace::error(
+                    TraceKind::Type,
+                    &format!(
+                        "The operator '{}' can not compare '{}' and '{}'",
+                        op, left, right
+                    ),
+                    vec![self.current_span()],
+                ))
+            }
+        };
+
+        self.stack.push_data(Data::Boolean(test(ordering)));
+        self.done()
+    }
+
+    /// Pops a kind and some data, pushing whether the data is a label of that
+    /// kind.
+    fn is_label(&mut self) -> Result<(), Trace> {
+        let kind = match self.stack.pop_data() {
+            Data::Kind(n) => n,
+            _ => unreachable!(),
+        };
+        let is_label = matches!(self.stack.pop_data(), Data::Label(n, _) if n == kind);
+        self.stack.push_data(Data::Boolean(is_label));
+        self.done()
+    }
+
+    /// Pops some data, pushing whether it is a tuple of a specific length.
+    fn is_tuple(&mut self) -> Result<(), Trace> {
+        let length = self.next_number();
+        let is_tuple = matches!(self.stack.pop_data(), Data::Tuple(t) if t.len() == length);
+        self.stack.push_data(Data::Boolean(is_tuple));
+        self.done()
+    }
+
+    /// Pops some data, pushing whether it is a record with exactly the fields
+    /// listed in a constant tuple.
+    fn is_record(&mut self) -> Result<(), Trace> {
+        let names = self.field_names();
+        let is_record = matches!(
+            self.stack.pop_data(),
+            Data::Record(r) if r.keys().eq(names.iter())
+        );
+        self.stack.push_data(Data::Boolean(is_record));
+        self.done()
+    }
+
+    /// Pops some data, pushing whether it is a list of a specific length,
+    /// or at least that length.
+    fn is_list(&mut self) -> Result<(), Trace> {
+        let length = self.next_number();
+        let rest = self.next_number() == 1;
+        let is_list = matches!(
+            self.stack.pop_data(),
+            Data::List(l) if l.len() == length || (rest && l.len() > length)
+        );
+        self.stack.push_data(Data::Boolean(is_list));
+        self.done()
+    }
+
+    /// Raises an error because none of the arms in a match matched some data.
+    /// The constant passed as an argument lists the arms that were tried.
+    fn no_match(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let tried = match self.closure.lambda.constants[index].clone() {
+            Data::String(tried) => tried,
+            _ => unreachable!("Expected a listing of the arms tried"),
+        };
+        let data = self.stack.pop_data();
+
+        Err(Trace::error(
+            TraceKind::PatternMatching,
+            &format!(
+                "The data '{}' does not match any of the arms tried:\n{}",
+                data, tried
+            ),
+            vec![self.current_span()],
+        ))
+    }
+
+    /// Applies a binary arithmetic operation to the top two items on the stack.
+    /// If both operands are integers, the result is an integer,
+    /// and an overflow is raised as an error.
+    /// If either operand is a float, both are promoted to floats.
+    fn arithmetic(
+        &mut self,
+        op: &str,
+        int: fn(i64, i64) -> Option<i64>,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<(), Trace> {
+        let right = self.stack.pop_data();
+        let left = self.stack.pop_data();
+
+        let result = match (left, right) {
+            (Data::Integer(l), Data::Integer(r)) => match int(l, r) {
+                Some(n) => Data::Integer(n),
+                None => {
+                    return Err(Trace::error(
+                        TraceKind::Overflow,
+                        &format!("The integer operation '{} {} {}' overflowed", l, op, r),
+                        vec![self.current_span()],
+                    ))
+                }
+            },
+            (Data::Integer(l), Data::Float(r)) => Data::Float(float(l as f64, r)),
+            (Data::Float(l), Data::Integer(r)) => Data::Float(float(l, r as f64)),
+            (Data::Float(l), Data::Float(r)) => Data::Float(float(l, r)),
+            (l, r) => {
+                return Err(Trace::error(
+                    TraceKind::Type,
+                    &format!(
+                        "The operator '{}' can not be applied to '{}' and '{}'",
+                        op, l, r
+                    ),
+                    vec![self.current_span()],
+                ))
+            }
+        };
+
+        self.stack.push_data(result);
+        self.done()
+    }
+
+    /// Returns whether the number on the top of the stack is zero,
+    /// without removing it. Used before division.
+    fn divisor_is_zero(&mut self) -> bool {
+        let divisor = self.stack.pop_data();
+        let is_zero = match divisor {
+            Data::Integer(n) => n == 0,
+            Data::Float(n) => n == 0.0,
+            _ => false,
+        };
+        self.stack.push_data(divisor);
+        is_zero
+    }
+
+    /// Divides by zero, which raises the `ZeroDiv` effect with the dividend
+    /// if something handles it, so the handler can resume with a result.
+    /// Otherwise, it is an error.
+    fn zero_div(&mut self, op: &str) -> Result<(), Trace> {
+        let divisor = self.stack.pop_data();
+        let dividend = self.stack.pop_data();
+
+        let kind = self
+            .handlers
+            .iter()
+            .rev()
+            .find_map(|handler| handler.handled("ZeroDiv"))
+            .cloned();
+        if let Some(kind) = kind {
+            return self.raise("ZeroDiv", Data::Label(kind, Box::new(dividend)));
+        }
+
+        Err(Trace::error(
+            TraceKind::ZeroDiv,
+            &format!(
+                "The operator '{}' can not be applied with a divisor of '{}'",
+                op, divisor
+            ),
+            vec![self.current_span()],
+        ))
+    }
+
+    /// Adds two numbers, or concatenates two strings.
+    fn add(&mut self) -> Result<(), Trace> {
+        let right = self.stack.pop_data();
+        let left = self.stack.pop_data();
+
+        if let (Data::String(l), Data::String(r)) = (&left, &right) {
+            self.stack.push_data(Data::String(format!("{}{}", l, r)));
+            return self.done();
+        }
+
+        self.stack.push_data(left);
+        self.stack.push_data(right);
+        self.arithmetic("+", i64::checked_add, |l, r| l + r)
+    }
+
+    fn sub(&mut self) -> Result<(), Trace> {
+        self.arithmetic("-", i64::checked_sub, |l, r| l - r)
+    }
+
+    fn mul(&mut self) -> Result<(), Trace> {
+        self.arithmetic("*", i64::checked_mul, |l, r| l * r)
+    }
+
+    /// Divides two numbers. Integer division rounds towards zero.
+    fn div(&mut self) -> Result<(), Trace> {
+        if self.divisor_is_zero() {
+            return self.zero_div("/");
+        }
+        self.arithmetic("/", i64::checked_div, |l, r| l / r)
+    }
+
+    /// Takes the remainder of two numbers,
+    /// which has the same sign as the dividend.
+    fn rem(&mut self) -> Result<(), Trace> {
+        if self.divisor_is_zero() {
+            return self.zero_div("%");
+        }
+        self.arithmetic("%", i64::checked_rem, |l, r| l % r)
+    }
+
+    /// Raises a number to a power.
+    /// An integer raised to a negative integer power is a float.
+    fn pow(&mut self) -> Result<(), Trace> {
+        let right = self.stack.pop_data();
+        let left = self.stack.pop_data();
+
+        match (&left, &right) {
+            (Data::Integer(l), Data::Integer(r)) if *r < 0 => {
+                self.stack
+                    .push_data(Data::Float((*l as f64).powf(*r as f64)));
+                return self.done();
+            }
+            (Data::Float(l), Data::Integer(r)) if i32::try_from(*r).is_ok() => {
+                self.stack.push_data(Data::Float(l.powi(*r as i32)));
+                return self.done();
+            }
+            _ => (),
+        }
+
+        self.stack.push_data(left);
+        self.stack.push_data(right);
+        self.arithmetic(
+            "**",
+            |l, r| u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+            f64::powf,
+        )
+    }
+
+    /// Negates a boolean.
+    fn not(&mut self) -> Result<(), Trace> {
+        match self.stack.pop_data() {
+            Data::Boolean(b) => self.stack.push_data(Data::Boolean(!b)),
+            other => {
+                return Err(Trace::error(
+                    TraceKind::Type,
+                    &format!("The operator '!' can not be applied to '{}'", other),
+                    vec![self.current_span()],
+                ))
+            }
+        }
+        self.done()
+    }
+
+    /// Negates a number.
+    fn neg(&mut self) -> Result<(), Trace> {
+        let result = match self.stack.pop_data() {
+            Data::Integer(n) => match n.checked_neg() {
+                Some(n) => Data::Integer(n),
+                None => {
+                    return Err(Trace::error(
+                        TraceKind::Overflow,
+                        &format!("The integer '{}' can not be negated without overflowing", n),
+                        vec![self.current_span()],
+                    ))
+                }
+            },
+            Data::Float(n) => Data::Float(-n),
+            other => {
+                return Err(Trace::error(
+                    TraceKind::Type,
+                    &format!("The operator '-' can not be applied to '{}'", other),
+                    vec![self.current_span()],
+                ))
+            }
+        };
+
+        self.stack.push_data(result);
+        self.done()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use passerine_derive::Effect;
+
+    use super::*;
+    use crate::{
+        common::{label::ROOT, lambda::Lambda, number::split_number, Source},
+        compile,
+    };
+
+    #[derive(Effect)]
+    struct Double(i64);
+
+    fn run(source: &str, register: impl FnOnce(&mut Fiber)) -> Result<Data, Trace> {
+        let closure = compile(Source::source(source)).unwrap();
+        let mut fiber = Fiber::init(closure);
+        register(&mut fiber);
+        fiber.run()?;
+        Ok(fiber.stack.pop_data())
+    }
+
+    #[test]
+    fn host_handler() {
+        let result = run("effect Double\nDouble 20 + 2", |fiber| {
+            fiber.register(|Double(n)| n * 2)
+        });
+        assert_eq!(result, Ok(Data::Integer(42)));
+    }
+
+    #[test]
+    fn host_handler_is_last_resort() {
+        let source = "effect Double\nhandle { Double 1 } with { Double _ -> 0 }";
+        let result = run(source, |fiber| fiber.register(|Double(n)| n * 2));
+        assert_eq!(result, Ok(Data::Integer(0)));
+    }
+
+    #[test]
+    fn failing_host_handler() {
+        let result = run("effect Double\nDouble 20", |fiber| {
+            fiber.try_register(|Double(n)| n.checked_mul(i64::MAX).ok_or("too big".to_string()))
+        });
+        let trace = result.unwrap_err();
+        assert_eq!(
+            trace.kind(),
+            &TraceKind::Host("Double".to_string(), Code::HostFailed)
+        );
+
+        let result = run("effect Double\nDouble 20", |fiber| {
+            fiber.try_register_with(Code::Overflow, |Double(n)| {
+                n.checked_mul(i64::MAX).ok_or("too big".to_string())
+            })
+        });
+        assert_eq!(result.unwrap_err().code(), Code::Overflow);
+    }
+
+    #[test]
+    fn unhandled() {
+        let result = run("effect Double\nDouble 1", |_| ());
+        assert_eq!(result.unwrap_err().kind(), &TraceKind::UnhandledEffect);
+    }
+
+    #[test]
+    fn adopts_labels() {
+        let closure = compile(Source::source("Some 1")).unwrap();
+        let mut fiber = Fiber::init(closure);
+        let none = fiber.labels.intern(ROOT, "None");
+        let some = fiber.labels.intern(ROOT, "Some");
+        fiber.run().unwrap();
+
+        let data = fiber.stack.pop_data();
+        assert_ne!(data, Data::Label(none, Box::new(Data::Integer(1))));
+        assert_eq!(data, Data::Label(some, Box::new(Data::Integer(1))));
+    }
+
+    #[test]
+    fn index_out_of_bounds() {
+        // matching checks lengths first, so this bytecode is built by hand
+        let source = Source::source("(1,).1");
+        for data in [
+            Data::Tuple(vec![Data::Integer(1)]),
+            Data::List(Rc::new(vec![])),
+        ] {
+            let mut lambda = Lambda::empty();
+            lambda.emit_span(&Span::new(&source, 0, 6));
+            lambda.emit(Opcode::Con);
+            let mut split = split_number(lambda.index_data(data));
+            lambda.emit_bytes(&mut split);
+            lambda.emit(Opcode::Index);
+            lambda.emit_bytes(&mut split_number(1));
+
+            let mut fiber = Fiber::init(Closure::wrap(Rc::new(lambda)));
+            assert_eq!(fiber.run().unwrap_err().kind(), &TraceKind::Indexing);
+        }
     }
 }
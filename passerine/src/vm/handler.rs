@@ -0,0 +1,112 @@
+use std::fmt::{self, Debug, Formatter};
+
+use crate::{
+    common::{
+        closure::Closure,
+        data::Data,
+        label::{Kind, ROOT},
+    },
+    explain::Code,
+    vm::{slot::Suspend, tag::Tagged},
+};
+
+/// A handler installed on a `Fiber` by a handle expression.
+/// The handler is active for as long as the frame
+/// running the body of the handle expression is on the stack.
+#[derive(Debug, Clone)]
+pub struct Handler {
+    /// The index of the frame running the body,
+    /// i.e. its position in `Stack::frames`.
+    pub depth: usize,
+    /// The kinds of the effects this handler handles.
+    pub kinds: Vec<Kind>,
+    /// Called with the raised effect and its continuation.
+    pub closure: Closure,
+}
+
+impl Handler {
+    /// Returns whether this handler handles an effect of a given kind.
+    pub fn handles(&self, kind: &Kind) -> bool {
+        self.kinds.contains(kind)
+    }
+
+    /// Returns the kind of the effect with a given name this handler handles,
+    /// if any. Used by the `Fiber` to raise effects of its own.
+    pub fn handled(&self, name: &str) -> Option<&Kind> {
+        self.kinds
+            .iter()
+            .find(|kind| kind.label().module == ROOT && kind.label().name == name)
+    }
+
+    /// Builds a handler from the constant tuple of kinds passed to
+    /// `Opcode::Handler`.
+    pub fn new(depth: usize, kinds: Data, closure: Closure) -> Handler {
+        let kinds = match kinds {
+            Data::Tuple(kinds) => kinds
+                .into_iter()
+                .map(|kind| match kind {
+                    Data::Kind(kind) => kind,
+                    _ => unreachable!("Expected the kind of an effect"),
+                })
+                .collect(),
+            _ => unreachable!("Expected a tuple of effect kinds"),
+        };
+
+        Handler {
+            depth,
+            kinds,
+            closure,
+        }
+    }
+}
+
+/// Everything needed to resume a computation suspended by an effect.
+/// This is what a `Continuation` holds on to.
+///
+/// When an effect is raised, every frame from the body of the handle
+/// expression up to the frame that raised the effect is moved off the stack.
+/// Resuming pushes these frames back onto the stack,
+/// on top of the frame that called the continuation.
+pub struct Resume {
+    /// The slots moved off of the stack, starting with the body's frame.
+    pub slots: Vec<Tagged>,
+    /// The frames within those slots, relative to the body's frame.
+    pub frames: Vec<usize>,
+    /// The handlers installed within those frames,
+    /// with depths relative to the body's frame.
+    /// This includes the handler that caught the effect.
+    pub handlers: Vec<Handler>,
+    /// The state of the frame that raised the effect.
+    pub suspend: Suspend,
+}
+
+/// Why a host handler could not handle an effect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostError {
+    /// The payload of the effect is not what the handler expects.
+    Payload,
+    /// The handler itself failed, for the given reason,
+    /// which is raised as an error with the given code.
+    Failed(Code, String),
+}
+
+/// A handler for an effect implemented by the host in Rust,
+/// used when nothing in Passerine handles the effect.
+/// Takes the payload of the effect and returns the data to resume with.
+pub struct HostHandler(Box<dyn FnMut(Data) -> Result<Data, HostError>>);
+
+impl HostHandler {
+    pub fn new(handler: impl FnMut(Data) -> Result<Data, HostError> + 'static) -> HostHandler {
+        HostHandler(Box::new(handler))
+    }
+
+    pub fn call(&mut self, payload: Data) -> Result<Data, HostError> {
+        (self.0)(payload)
+    }
+}
+
+impl Debug for HostHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "HostHandler(...)")
+    }
+}
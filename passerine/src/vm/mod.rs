@@ -3,6 +3,7 @@
 //! But should never be used outside of the module by `common` or `compiler`.
 
 pub mod fiber;
+pub mod handler;
 
 pub mod slot;
 pub mod stack;
@@ -27,26 +27,34 @@ pub enum Slot {
     // Uninitialized Data
     NotInit,
 
-    // Refers to a capture stored in the current closure
-    Ref(Rc<RefCell<Data>>),
+    // Refers to a capture stored in the current closure,
+    // which holds nothing until the variable is assigned
+    Ref(Rc<RefCell<Option<Data>>>),
 }
 
 impl Slot {
     pub fn data(self) -> Data {
         match self {
             Slot::Data(d) => d,
-            Slot::Ref(r) => r.borrow().to_owned(),
+            Slot::Ref(r) => match r.borrow().to_owned() {
+                Some(d) => d,
+                None => unreachable!("expected data, found a variable that is not yet assigned"),
+            },
             Slot::Frame | Slot::Suspend(_) | Slot::NotInit => {
                 unreachable!("expected data on top of stack, found {:?}", self)
             }
         }
     }
 
-    pub fn reference(self) -> Rc<RefCell<Data>> {
+    pub fn reference(self) -> Rc<RefCell<Option<Data>>> {
         match self {
-            Slot::Data(d) => Rc::new(RefCell::new(d)),
+            Slot::Data(d) => Rc::new(RefCell::new(Some(d))),
             Slot::Ref(r) => r,
-            Slot::Frame | Slot::Suspend(_) | Slot::NotInit => {
+            // a variable may be captured before it is assigned,
+            // e.g. by a function that refers to a later definition.
+            // The reference is filled in once the assignment happens.
+            Slot::NotInit => Rc::new(RefCell::new(None)),
+            Slot::Frame | Slot::Suspend(_) => {
                 unreachable!("expected reference on top of stack, found {:?}", self)
             }
         }
@@ -91,6 +91,13 @@ impl Stack {
         }
 
         self.frames.pop();
+        self.restore_frame()
+    }
+
+    /// Makes the topmost frame the current one again,
+    /// returning the suspended closure stored in it.
+    #[inline]
+    pub fn restore_frame(&mut self) -> Suspend {
         let old_slot = self.swap(self.frame_index(), Tagged::frame()).slot();
 
         if let Slot::Suspend(s) = old_slot {
@@ -110,6 +117,38 @@ impl Stack {
         self.stack.push(Tagged::frame());
     }
 
+    /// Moves every frame from a given depth upwards off of the `Stack`.
+    /// Returns the slots that were moved,
+    /// alongside the positions of the moved frames relative to the first.
+    /// The frame below the moved frames is left suspended,
+    /// so use `restore_frame` to continue running it.
+    pub fn split_frames(&mut self, depth: usize) -> (Vec<Tagged>, Vec<usize>) {
+        let base = self.frames[depth];
+        let frames = self
+            .frames
+            .split_off(depth)
+            .into_iter()
+            .map(|frame| frame - base)
+            .collect();
+
+        (self.stack.split_off(base), frames)
+    }
+
+    /// Pushes frames moved off by `split_frames` back onto the `Stack`.
+    /// The first moved frame replaces the topmost frame,
+    /// which must not have anything on top of it.
+    pub fn join_frames(&mut self, slots: Vec<Tagged>, frames: Vec<usize>) {
+        if let Slot::Frame = self.pop().slot() {
+        } else {
+            unreachable!("Expected frame on top of stack");
+        }
+
+        let base = self.frames.pop().unwrap();
+        self.frames
+            .extend(frames.into_iter().map(|frame| frame + base));
+        self.stack.extend(slots);
+    }
+
     /// Shorcut for pushing a `Tagged(Slot::NotInit)` on top of the stack.
     #[inline]
     pub fn push_not_init(&mut self) {
@@ -125,11 +164,22 @@ impl Stack {
     }
 
     /// Truncates the stack to the last frame.
-    /// Returns `true` if the stack can not be unwound further.
+    /// Returns `false` if the stack can not be unwound further.
+    /// The root frame is left as is,
+    /// so its locals survive errors, e.g. in a REPL.
     #[inline]
     pub fn unwind_frame(&mut self) -> bool {
+        if self.frames.len() == 1 {
+            return false;
+        }
         self.stack.truncate(self.frame_index() + 1);
-        return self.frames.len() > 1;
+        return true;
+    }
+
+    /// Removes everything above the first `decls` locals of the root frame.
+    /// The stack must already be unwound to the root frame.
+    pub fn truncate_root(&mut self, decls: usize) {
+        self.stack.truncate(self.frame_index() + decls + 1);
     }
 
     /// returns a copy of the `Slot` of a local variable on the stack.
@@ -145,7 +195,7 @@ impl Stack {
         return copy;
     }
 
-    pub fn local_ref(&mut self, index: usize) -> Rc<RefCell<Data>> {
+    pub fn local_ref(&mut self, index: usize) -> Rc<RefCell<Option<Data>>> {
         let local_index = self.frame_index() + index + 1;
 
         // a little bit of shuffling involved
@@ -160,17 +210,14 @@ impl Stack {
         return copy;
     }
 
-    /// Returns a copy of the `Data` stored in a local variable on the stack.
-    pub fn local_data(&mut self, index: usize) -> Data {
-        let local_index = self.frame_index() + index + 1;
-
-        // a little bit of shuffling involved
-        // I know that something better than this can be done
-        let data = self.swap(local_index, Tagged::not_init()).slot().data();
-        let copy = data.clone();
-        mem::drop(self.swap(local_index, Tagged::new(Slot::Data(data))));
-
-        return copy;
+    /// Returns a copy of the `Data` stored in a local variable on the stack,
+    /// or `None` if the local has not been initialized.
+    pub fn local_data(&mut self, index: usize) -> Option<Data> {
+        match self.local_slot(index) {
+            Slot::NotInit => None,
+            Slot::Ref(cell) => cell.borrow().to_owned(),
+            slot => Some(slot.data()),
+        }
     }
 
     /// Sets a local - note that this function doesn't do much.
@@ -200,13 +247,13 @@ impl Stack {
             // if it is on the heap, we replace in the old value
             Slot::Ref(ref cell) => {
                 // TODO: check types?
-                mem::drop(cell.replace(self.pop_data()));
+                mem::drop(cell.replace(Some(self.pop_data())));
                 Tagged::new(slot)
             },
             // if it's anything else, we're sad.
             Slot::Frame => unreachable!("Expected data, found frame"),
             Slot::Suspend(_) => unreachable!("Expected data, found *suspended* frame buried deep in the stack, which makes even less sense, because this should be a local variable"),
-            Slot::NotInit => unreachable!("Expected data, found data. Wait! It's unitialized? Maybe this is allowed?"),
+            Slot::NotInit => self.stack.pop().unwrap(),
         };
 
         mem::drop(self.swap(local_index, tagged))
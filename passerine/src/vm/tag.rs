@@ -84,6 +84,13 @@ impl Tagged {
         Tagged::new(Slot::NotInit)
     }
 
+    /// Returns whether this is a `Tagged(Slot::NotInit)`,
+    /// without unpacking it.
+    #[inline]
+    pub fn is_not_init(&self) -> bool {
+        self.0 == (QNAN | N_FLAG)
+    }
+
     /// Returns the underlying `Data` (or a pointer to that `Data`).
     /// Unpacks the encoding used by [`Tagged`].
     ///
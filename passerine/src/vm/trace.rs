@@ -1,28 +1,119 @@
 use std::fmt;
 
-use crate::common::span::Span;
+use crate::{common::span::Span, explain::Code};
+
+/// The kinds of error that can be raised at runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceKind {
+    /// A variable was used before it was assigned.
+    Uninitialized,
+    /// An operation was applied to data of the wrong type.
+    Type,
+    /// Some data did not match a pattern.
+    PatternMatching,
+    /// A tuple was indexed out of bounds.
+    Indexing,
+    /// A continuation was resumed more than once.
+    Continuation,
+    /// Something that is not a function was called.
+    Call,
+    /// An integer operation overflowed.
+    Overflow,
+    /// A number was divided by zero.
+    ZeroDiv,
+    /// A field was accessed that the data does not have.
+    Field,
+    /// An effect was raised, but nothing handles it.
+    UnhandledEffect,
+    /// The host could not handle the data an effect carries.
+    Effect,
+    /// The host failed to handle the effect with the given name,
+    /// for a reason with the given code.
+    Host(String, Code),
+}
+
+impl TraceKind {
+    /// The stable code of this kind of error.
+    pub fn code(&self) -> Code {
+        match self {
+            TraceKind::Uninitialized => Code::Uninitialized,
+            TraceKind::Type => Code::WrongType,
+            TraceKind::PatternMatching => Code::NoMatch,
+            TraceKind::Indexing => Code::OutOfBounds,
+            TraceKind::Continuation => Code::ResumedTwice,
+            TraceKind::Call => Code::NotCallable,
+            TraceKind::Overflow => Code::Overflow,
+            TraceKind::ZeroDiv => Code::ZeroDivision,
+            TraceKind::Field => Code::NoSuchField,
+            TraceKind::UnhandledEffect => Code::UnhandledEffect,
+            TraceKind::Effect => Code::InvalidPayload,
+            TraceKind::Host(_, code) => *code,
+        }
+    }
+}
+
+impl fmt::Display for TraceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TraceKind::Uninitialized => "Uninitialized",
+            TraceKind::Type => "Type",
+            TraceKind::PatternMatching => "Pattern Matching",
+            TraceKind::Indexing => "Indexing",
+            TraceKind::Continuation => "Continuation",
+            TraceKind::Call => "Call",
+            TraceKind::Overflow => "Overflow",
+            TraceKind::ZeroDiv => "ZeroDiv",
+            TraceKind::Field => "Field",
+            TraceKind::UnhandledEffect => "Unhandled Effect",
+            TraceKind::Effect => "Effect",
+            TraceKind::Host(name, _) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
 
 /// Represents a runtime error, i.e. a traceback
 #[derive(Debug, PartialEq, Eq)]
 pub struct Trace {
-    kind: String, // TODO: enum?
+    kind: TraceKind,
     message: String,
     spans: Vec<Span>,
 }
 
 impl Trace {
     /// Creates a new traceback
-    pub fn error(kind: &str, message: &str, spans: Vec<Span>) -> Trace {
+    pub fn error(kind: TraceKind, message: &str, spans: Vec<Span>) -> Trace {
         Trace {
-            kind: kind.to_string(),
+            kind,
             message: message.to_string(),
             spans,
         }
     }
 
+    /// The kind of error this traceback represents, e.g. `ZeroDiv`.
+    /// Can be used to distinguish between different runtime errors.
+    pub fn kind(&self) -> &TraceKind {
+        &self.kind
+    }
+
+    /// The stable code of the error, which can be explained at length.
+    pub fn code(&self) -> Code {
+        self.kind.code()
+    }
+
+    /// The message describing the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
     /// Used to add context (i.e. function calls) while unwinding the stack.
+    /// Empty spans point at code made up by the compiler,
+    /// like the call that runs the root module of a package,
+    /// so they are left out.
     pub fn add_context(&mut self, span: Span) {
-        self.spans.push(span);
+        if span.len() > 0 {
+            self.spans.push(span);
+        }
     }
 }
 
@@ -35,7 +126,13 @@ impl fmt::Display for Trace {
             fmt::Display::fmt(span, f)?;
         }
 
-        write!(f, "Runtime {} Error: {}", self.kind, self.message)
+        write!(
+            f,
+            "Runtime {} Error [{}]: {}",
+            self.kind,
+            self.code(),
+            self.message
+        )
     }
 }
 
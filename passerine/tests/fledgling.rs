@@ -1,5 +1,5 @@
 ///! Snippet tests for the passerine compiler pipeline as a whole.
-use std::{collections::HashMap, fs, path::PathBuf, rc::Rc};
+use std::{collections::HashMap, fmt::Display, fs, path::PathBuf, rc::Rc};
 
 use passerine::*;
 
@@ -46,7 +46,7 @@ impl Action {
             d if d == "desugar" => Action::Desugar,
             d if d == "hoist" => Action::Hoist,
             g if g == "gen" => Action::Gen,
-            r if r == "run" => Action::Gen, // TODO: actually run code!
+            r if r == "run" => Action::Run,
             invalid => {
                 println!("invalid: '{}'", invalid);
                 panic!("invalid action in strat heading");
@@ -66,6 +66,9 @@ pub struct TestStrat {
     /// Optional data to check against.
     /// Should only be used with Action::Run
     expect: Option<Data>,
+    /// The code of the error raised while running.
+    /// Must be given if the outcome is a trace.
+    code: Option<Code>,
 }
 
 impl TestStrat {
@@ -74,36 +77,15 @@ impl TestStrat {
         let mut outcome = None;
         let mut action = None;
         let mut expect = None;
+        let mut code = None;
 
         for (strat, result) in heading.iter() {
             match strat {
                 o if o == "outcome" => outcome = Some(Outcome::parse(result)),
                 a if a == "action" => action = Some(Action::parse(result)),
-                e if e == "expect" => {
-                    // TODO: implement expectations.
-                    // println!("Warning, expectations are not implemented");
-                    // expect =
-                    // {
-                    //     let (ast, _symbols) =
-                    //         compiler::parse(Source::source(result))
-                    //             .expect("Could not parse result field");
-
-                    //     use construct::tree::{
-                    //         Base,
-                    //         AST,
-                    //     };
-
-                    //     if let AST::Base(Base::Block(ref b)) = ast.item {
-                    //         if let AST::Base(Base::Lit(ref l)) =
-                    //             b.get(0).expect("Expected an
-                    // expression").item         {
-                    //             assert_eq!(l, )
-                    //         }
-                    //     }
-
-                    //     dbg!(ast);
-                    // }
-                    // todo!();
+                e if e == "expect" => expect = Some(evaluate(result)),
+                c if c == "code" => {
+                    code = Some(Code::from_id(result).expect("invalid code in strat heading"))
                 }
                 invalid => {
                     println!("invalid: '{}'", invalid);
@@ -112,10 +94,18 @@ impl TestStrat {
             }
         }
 
+        let outcome = outcome.expect("no outcome provided");
+        match (&outcome, &code) {
+            (Outcome::Trace, None) => panic!("no code provided for a trace outcome"),
+            (Outcome::Trace, Some(_)) | (_, None) => (),
+            (_, Some(_)) => panic!("a code can only be provided for a trace outcome"),
+        }
+
         TestStrat {
-            outcome: outcome.expect("no outcome provided"),
+            outcome,
             action: action.expect("no action provided"),
             expect,
+            code,
         }
     }
 
@@ -149,7 +139,16 @@ impl TestStrat {
     }
 }
 
-fn outcome<T>(t: Result<T, Syntax>) -> Outcome {
+/// Runs a snippet's expected result, which is itself Passerine source,
+/// returning the data it evaluates to.
+fn evaluate(source: &str) -> Data {
+    let closure = compile(Source::source(source)).expect("Could not compile expected result");
+    let mut fiber = Fiber::init(closure);
+    fiber.run().expect("Could not run expected result");
+    fiber.stack.pop_data()
+}
+
+fn outcome<T, E: Display>(t: Result<T, E>) -> Outcome {
     if let Err(e) = t {
         eprintln!("{}", e);
         Outcome::Syntax
@@ -174,32 +173,30 @@ fn snippet_outcome(source: Rc<Source>, strat: &TestStrat) -> Outcome {
         Err(_) => return Outcome::Syntax,
     };
 
-    println!("{}", &lambda);
-    println!(
-        "{}",
-        if let Data::Lambda(s) = &lambda.constants[0] {
-            s
-        } else {
-            todo!()
-        }
-    );
-
-    let mut Fiber = Fiber::init(Closure::wrap(lambda));
+    let mut fiber = Fiber::init(Closure::wrap(lambda));
+    kernel::register(&mut fiber);
 
-    let run_outcome = match Fiber.run() {
+    let run_outcome = match fiber.run() {
         Ok(()) => {
             if let Some(expected) = &strat.expect {
-                let top = Fiber.stack.pop_data();
+                let top = fiber.stack.pop_data();
                 if expected != &top {
                     println!("Top: {}", top);
                     println!("Expected: {}", expected);
                     panic!("Top stack data does not match")
                 }
             }
-            return Outcome::Success;
+            Outcome::Success
         }
         Err(e) => {
             eprintln!("{}", e);
+            if let Some(code) = strat.code {
+                if e.code() != code {
+                    println!("Raised: {}", e.code());
+                    println!("Expected: {}", code);
+                    panic!("Trace code does not match")
+                }
+            }
             Outcome::Trace
         }
     };